@@ -1,6 +1,7 @@
 use crate::error::{Result, StockCalcError};
 use crate::config::AppConfig;
-use crate::models::StockInfo;
+use crate::models::{Candle, StockInfo};
+use chrono::NaiveDate;
 use regex::Regex;
 use std::time::Duration;
 
@@ -165,6 +166,146 @@ impl StockApi {
             format!("无法解析股票信息，响应: {}", response_text)
         ))
     }
+
+    /// 从东方财富获取股票所属行业，ETF、指数等没有行业分类时返回 None
+    pub async fn fetch_sector(&self, stock_code: &str) -> Result<Option<String>> {
+        let url = format!(
+            "http://push2.eastmoney.com/api/qt/stock/get?secid={}&fields=f57,f127",
+            Self::eastmoney_secid(stock_code)
+        );
+
+        for attempt in 1..=self.config.api.retry_count {
+            match self.fetch_sector_with_retry(&url).await {
+                Ok(sector) => return Ok(sector),
+                Err(e) => {
+                    if attempt == self.config.api.retry_count {
+                        return Err(e);
+                    }
+                    log::warn!("第{}次尝试失败，正在重试: {}", attempt, e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+
+        Err(StockCalcError::ParseError("所有重试都失败了".to_string()))
+    }
+
+    async fn fetch_sector_with_retry(&self, url: &str) -> Result<Option<String>> {
+        let response = self.client.get(url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(StockCalcError::ParseError(
+                format!("HTTP错误: {}", response.status())
+            ));
+        }
+
+        let text = response.text().await?;
+        self.parse_sector(&text)
+    }
+
+    /// 东方财富的证券标识：沪市为 1.代码，深市和北交所为 0.代码
+    fn eastmoney_secid(stock_code: &str) -> String {
+        let code = stock_code.to_lowercase();
+        match code.get(..2) {
+            Some("sh") => format!("1.{}", &code[2..]),
+            Some("sz") | Some("bj") => format!("0.{}", &code[2..]),
+            _ if code.starts_with(['5', '6', '9']) => format!("1.{}", code),
+            _ => format!("0.{}", code),
+        }
+    }
+
+    fn parse_sector(&self, response_text: &str) -> Result<Option<String>> {
+        let json: serde_json::Value = serde_json::from_str(response_text)?;
+
+        // 代码不存在时 data 为 null
+        if json["data"].is_null() {
+            return Err(StockCalcError::ApiResponseError(
+                format!("无法解析行业数据，响应: {}", response_text)
+            ));
+        }
+
+        Ok(json["data"]["f127"]
+            .as_str()
+            .map(str::trim)
+            .filter(|sector| !sector.is_empty() && *sector != "-")
+            .map(str::to_string))
+    }
+
+    /// 获取最近 count 个交易日的前复权日K线，按日期升序
+    pub async fn fetch_daily_candles(&self, stock_code: &str, count: usize) -> Result<Vec<Candle>> {
+        let url = format!(
+            "http://web.ifzq.gtimg.cn/appstock/app/fqkline/get?param={},day,,,{},qfq",
+            stock_code, count
+        );
+
+        for attempt in 1..=self.config.api.retry_count {
+            match self.fetch_candles_with_retry(&url, stock_code).await {
+                Ok(candles) => return Ok(candles),
+                Err(e) => {
+                    if attempt == self.config.api.retry_count {
+                        return Err(e);
+                    }
+                    log::warn!("第{}次尝试失败，正在重试: {}", attempt, e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+
+        Err(StockCalcError::ParseError("所有重试都失败了".to_string()))
+    }
+
+    async fn fetch_candles_with_retry(&self, url: &str, stock_code: &str) -> Result<Vec<Candle>> {
+        let response = self.client.get(url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(StockCalcError::ParseError(
+                format!("HTTP错误: {}", response.status())
+            ));
+        }
+
+        let text = response.text().await?;
+        self.parse_candles(&text, stock_code)
+    }
+
+    fn parse_candles(&self, response_text: &str, stock_code: &str) -> Result<Vec<Candle>> {
+        let json: serde_json::Value = serde_json::from_str(response_text)?;
+        let data = &json["data"][stock_code];
+
+        // 个股返回前复权数据 qfqday，指数只有 day
+        let rows = data["qfqday"]
+            .as_array()
+            .or_else(|| data["day"].as_array())
+            .ok_or_else(|| StockCalcError::ApiResponseError(
+                format!("无法解析K线数据，响应: {}", response_text)
+            ))?;
+
+        let field = |row: &serde_json::Value, index: usize| -> Option<f64> {
+            row.get(index)?.as_str()?.parse::<f64>().ok()
+        };
+
+        let candles = rows
+            .iter()
+            .filter_map(|row| {
+                let date = NaiveDate::parse_from_str(row.get(0)?.as_str()?, "%Y-%m-%d").ok()?;
+                Some(Candle {
+                    date,
+                    open: field(row, 1)?,
+                    close: field(row, 2)?,
+                    high: field(row, 3)?,
+                    low: field(row, 4)?,
+                    volume: field(row, 5).unwrap_or(0.0),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if candles.is_empty() {
+            return Err(StockCalcError::ApiResponseError(
+                format!("{} 没有K线数据", stock_code)
+            ));
+        }
+
+        Ok(candles)
+    }
 }
 
 #[cfg(test)]
@@ -182,4 +323,39 @@ mod tests {
         let price = api.parse_stock_price(test_response).unwrap();
         assert_eq!(price, 2.172);
     }
+
+    #[tokio::test]
+    async fn test_parse_candles() {
+        let api = StockApi::new(AppConfig::default()).unwrap();
+
+        let stock_response = r#"{"code":0,"msg":"","data":{"sh600000":{"qfqday":[["2024-01-02","6.600","6.620","6.660","6.580","123456.000"],["2024-01-03","6.620","6.700","6.720","6.600","234567.000"]]}}}"#;
+        let candles = api.parse_candles(stock_response, "sh600000").unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].date, NaiveDate::from_ymd_opt(2024, 1, 3).unwrap());
+        assert_eq!(candles[1].close, 6.7);
+        assert_eq!(candles[1].high, 6.72);
+
+        let index_response = r#"{"code":0,"msg":"","data":{"sh000300":{"day":[["2024-01-02","3431.10","3386.35","3431.10","3383.00","11236520.0"]]}}}"#;
+        let candles = api.parse_candles(index_response, "sh000300").unwrap();
+        assert_eq!(candles[0].close, 3386.35);
+
+        assert!(api.parse_candles(r#"{"code":0,"data":{}}"#, "sh000300").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_sector() {
+        let api = StockApi::new(AppConfig::default()).unwrap();
+
+        assert_eq!(StockApi::eastmoney_secid("sh600036"), "1.600036");
+        assert_eq!(StockApi::eastmoney_secid("000001"), "0.000001");
+        assert_eq!(StockApi::eastmoney_secid("513500"), "1.513500");
+
+        let response = r#"{"rc":0,"rt":4,"data":{"f57":"000001","f127":"银行"}}"#;
+        assert_eq!(api.parse_sector(response).unwrap(), Some("银行".to_string()));
+
+        let etf_response = r#"{"rc":0,"rt":4,"data":{"f57":"513500","f127":"-"}}"#;
+        assert_eq!(api.parse_sector(etf_response).unwrap(), None);
+
+        assert!(api.parse_sector(r#"{"rc":0,"rt":4,"data":null}"#).is_err());
+    }
 } 
\ No newline at end of file
@@ -1,6 +1,25 @@
+use crate::config::{AppConfig, RiskGradingConfig, ScaleConfig};
 use crate::error::{Result, StockCalcError, ValidationError};
-use crate::models::{StockData, StockAnalysis, InvestmentScale, StockStatus};
-use chrono::Utc;
+use crate::models::{
+    StockData, StockAnalysis, StockStatus, PositionSizing, SimulatedTrade,
+    TargetKind, TrailingKind, TrailingStop, ExitTier, TierSide, TierAnalysis,
+    CorporateAction, CorporateActionKind, StockInfo, PortfolioPosition, PortfolioSummary,
+    NearThreshold, NearThresholdKind, PositionDirection, Financing, FinancingAnalysis, Portfolio,
+};
+use chrono::{Local, NaiveDate, Utc};
+
+mod advice;
+mod backtest;
+mod benchmark;
+mod correlation;
+mod dca;
+mod exposure;
+mod grid;
+mod holding_cost;
+mod indicators;
+mod performance;
+mod simulation;
+mod volatility;
 
 pub struct StockCalculator;
 
@@ -47,74 +66,795 @@ impl StockCalculator {
         Ok(())
     }
 
+    /// 校验完整的持仓设置，包括移动止损和分批计划
+    pub fn validate_stock_data(stock_data: &StockData) -> Result<()> {
+        Self::validate_input(
+            &stock_data.code,
+            stock_data.quantity,
+            stock_data.avg_price,
+            stock_data.target_profit,
+            stock_data.max_loss,
+        )?;
+        Self::validate_target_kinds(
+            stock_data.direction,
+            stock_data.avg_price,
+            stock_data.target_profit,
+            stock_data.target_kind,
+            stock_data.max_loss,
+            stock_data.stop_kind,
+        )?;
+
+        if let Some(trailing) = &stock_data.trailing_stop {
+            // 百分比回撤必须在 0 到 100 之间，否则止损价为负数
+            let too_wide = trailing.kind == TrailingKind::Percent && trailing.distance >= 100.0;
+            if trailing.distance <= 0.0 || too_wide {
+                return Err(StockCalcError::ValidationError(
+                    ValidationError::InvalidTrailingStop.to_string()
+                ));
+            }
+        }
+
+        Self::validate_tiers(stock_data, &stock_data.take_profit_tiers, TierSide::TakeProfit)?;
+        Self::validate_tiers(stock_data, &stock_data.stop_loss_tiers, TierSide::StopLoss)?;
+
+        if let Some(financing) = &stock_data.financing {
+            Self::validate_financing(financing)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_financing(financing: &Financing) -> Result<()> {
+        if financing.borrowed_amount <= 0.0
+            || financing.interest_rate < 0.0
+            || financing.maintenance_ratio <= 0.0
+            || financing.collateral < 0.0
+        {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidFinancing.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 档位换算后的价格必须为正数，且止盈在成本价盈利一侧、止损在亏损一侧
+    fn validate_tiers(stock_data: &StockData, tiers: &[ExitTier], side: TierSide) -> Result<()> {
+        let sign = stock_data.direction.sign() * match side {
+            TierSide::TakeProfit => 1.0,
+            TierSide::StopLoss => -1.0,
+        };
+        let invalid = tiers.iter().any(|tier| {
+            let price = Self::resolve_price(stock_data, tier.value, tier.kind, side);
+            tier.fraction <= 0.0
+                || tier.fraction > 1.0
+                || tier.value <= 0.0
+                || price <= 0.0
+                || sign * (price - stock_data.avg_price) <= 0.0
+        });
+        let total: f64 = tiers.iter().map(|tier| tier.fraction).sum();
+
+        if invalid || total > 1.0 + 1e-6 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidTier.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 校验按百分比或价格设定的目标/止损，空头的目标价在成本价下方、止损价在上方
+    pub fn validate_target_kinds(
+        direction: PositionDirection,
+        avg_price: f64,
+        target_profit: f64,
+        target_kind: TargetKind,
+        max_loss: f64,
+        stop_kind: TargetKind,
+    ) -> Result<()> {
+        let sign = direction.sign();
+        let invalid_target = match target_kind {
+            TargetKind::Amount => false,
+            // 空头最多盈利 100%
+            TargetKind::Percent => direction == PositionDirection::Short && target_profit >= 100.0,
+            TargetKind::Price => sign * (target_profit - avg_price) <= 0.0,
+        };
+
+        if invalid_target {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidTargetProfit.to_string()
+            ));
+        }
+
+        let invalid_stop = match stop_kind {
+            TargetKind::Amount => false,
+            TargetKind::Percent => direction == PositionDirection::Long && max_loss >= 100.0,
+            TargetKind::Price => sign * (avg_price - max_loss) <= 0.0,
+        };
+
+        if invalid_stop {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidMaxLoss.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 按设定方式把金额/百分比/价格换算为触发价格，空头的止盈在成本价下方
+    fn resolve_price(stock_data: &StockData, value: f64, kind: TargetKind, side: TierSide) -> f64 {
+        let (avg_price, quantity) = (stock_data.avg_price, stock_data.quantity);
+        let direction = stock_data.direction.sign() * match side {
+            TierSide::TakeProfit => 1.0,
+            TierSide::StopLoss => -1.0,
+        };
+
+        match kind {
+            TargetKind::Amount => avg_price + direction * (value / quantity),
+            TargetKind::Percent => avg_price * (1.0 + direction * value / 100.0),
+            TargetKind::Price => value,
+        }
+    }
+
+    /// 按设定方式换算目标价格
+    pub fn resolve_target_price(stock_data: &StockData) -> f64 {
+        Self::resolve_price(
+            stock_data,
+            stock_data.target_profit,
+            stock_data.target_kind,
+            TierSide::TakeProfit,
+        )
+    }
+
+    /// 按设定方式换算止损价格
+    pub fn resolve_stop_loss_price(stock_data: &StockData) -> f64 {
+        Self::resolve_price(
+            stock_data,
+            stock_data.max_loss,
+            stock_data.stop_kind,
+            TierSide::StopLoss,
+        )
+    }
+
+    /// 计算分批止盈/止损的价格、数量和预期收益
+    pub fn analyze_tiers(stock_data: &StockData) -> Vec<TierAnalysis> {
+        let take_profit = stock_data.take_profit_tiers.iter().enumerate().map(|(i, tier)| (TierSide::TakeProfit, i + 1, tier));
+        let stop_loss = stock_data.stop_loss_tiers.iter().enumerate().map(|(i, tier)| (TierSide::StopLoss, i + 1, tier));
+
+        take_profit
+            .chain(stop_loss)
+            .map(|(side, index, tier)| {
+                let price = Self::resolve_price(stock_data, tier.value, tier.kind, side);
+                let quantity = stock_data.quantity * tier.fraction;
+
+                TierAnalysis {
+                    side,
+                    tier: index,
+                    price,
+                    quantity,
+                    expected_profit: stock_data.direction.sign() * (price - stock_data.avg_price) * quantity,
+                    triggered: tier.triggered,
+                }
+            })
+            .collect()
+    }
+
+    /// 将当前价格已触及的档位标记为已触发，返回是否有变化
+    pub fn mark_triggered_tiers(stock_data: &mut StockData, current_price: f64) -> bool {
+        let sign = stock_data.direction.sign();
+        let take_profit: Vec<bool> = stock_data
+            .take_profit_tiers
+            .iter()
+            .map(|tier| sign * (current_price - Self::resolve_price(stock_data, tier.value, tier.kind, TierSide::TakeProfit)) >= 0.0)
+            .collect();
+        let stop_loss: Vec<bool> = stock_data
+            .stop_loss_tiers
+            .iter()
+            .map(|tier| sign * (current_price - Self::resolve_price(stock_data, tier.value, tier.kind, TierSide::StopLoss)) <= 0.0)
+            .collect();
+
+        let mut changed = false;
+        let tiers = stock_data.take_profit_tiers.iter_mut().zip(take_profit)
+            .chain(stock_data.stop_loss_tiers.iter_mut().zip(stop_loss));
+        for (tier, reached) in tiers {
+            if reached && !tier.triggered {
+                tier.triggered = true;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// 用当前价格更新移动止损的最有利价格（空头为最低价），返回是否有变化
+    pub fn update_trailing_stop(stock_data: &mut StockData, current_price: f64) -> bool {
+        let sign = stock_data.direction.sign();
+        match stock_data.trailing_stop.as_mut() {
+            Some(trailing) if trailing.high_water_mark.map_or(true, |high| sign * (current_price - high) > 0.0) => {
+                trailing.high_water_mark = Some(current_price);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 把监控过程中的状态合并到数据库中的最新记录：处理到期的公司行为，
+    /// 并只更新移动止损最有利价格和分批档位的触发标记，其他字段保持最新记录的值
+    pub fn merge_monitor_state(stored: &mut StockData, monitored: &StockData, today: NaiveDate) -> Vec<CorporateAction> {
+        let applied = Self::apply_due_corporate_actions(stored, today);
+
+        if let Some(mark) = monitored.trailing_stop.as_ref().and_then(|t| t.high_water_mark) {
+            Self::update_trailing_stop(stored, mark);
+        }
+
+        let pairs = stored.take_profit_tiers.iter_mut().map(|tier| (tier, &monitored.take_profit_tiers))
+            .chain(stored.stop_loss_tiers.iter_mut().map(|tier| (tier, &monitored.stop_loss_tiers)));
+        for (tier, monitored_tiers) in pairs {
+            let triggered = monitored_tiers.iter().any(|m| {
+                m.triggered && m.kind == tier.kind && m.value == tier.value && m.fraction == tier.fraction
+            });
+            if triggered {
+                tier.triggered = true;
+            }
+        }
+
+        applied
+    }
+
+    /// 计算移动止损价格，未设置或未达到启用条件时返回 None
+    pub fn resolve_trailing_stop_price(stock_data: &StockData, current_price: f64) -> Option<(f64, f64)> {
+        let trailing = stock_data.trailing_stop.as_ref()?;
+        let sign = stock_data.direction.sign();
+        let high_water_mark = match (trailing.high_water_mark, stock_data.direction) {
+            (None, _) => current_price,
+            (Some(high), PositionDirection::Long) => high.max(current_price),
+            (Some(low), PositionDirection::Short) => low.min(current_price),
+        };
+
+        if let Some(activation) = trailing.activation_profit {
+            if sign * (high_water_mark - stock_data.avg_price * (1.0 + sign * activation / 100.0)) < 0.0 {
+                return None;
+            }
+        }
+
+        let trailing_price = match trailing.kind {
+            TrailingKind::Percent => high_water_mark * (1.0 - sign * trailing.distance / 100.0),
+            TrailingKind::Amount => high_water_mark - sign * trailing.distance,
+        };
+
+        Some((trailing_price, high_water_mark))
+    }
+
     pub fn calculate_analysis(
         stock_data: &StockData,
         current_price: f64,
     ) -> StockAnalysis {
+        Self::calculate_analysis_with_config(stock_data, current_price, &AppConfig::default(), None)
+    }
+
+    /// 按配置的状态阈值和分档计算分析结果，持仓自身设置的阈值优先。
+    /// equity 为账户权益，投资规模按权益占比分档时使用
+    pub fn calculate_analysis_with_config(
+        stock_data: &StockData,
+        current_price: f64,
+        config: &AppConfig,
+        equity: Option<f64>,
+    ) -> StockAnalysis {
+        let sign = stock_data.direction.sign();
+        // 计息天数按本地日期计算，与融资融券开始日期的默认值一致
+        let today = Local::now().date_naive();
         let investment_amount = stock_data.quantity * stock_data.avg_price;
-        let target_price = stock_data.avg_price + (stock_data.target_profit / stock_data.quantity);
-        let stop_loss_price = stock_data.avg_price - (stock_data.max_loss / stock_data.quantity);
-        let current_profit = (current_price - stock_data.avg_price) * stock_data.quantity;
-        let profit_ratio = ((current_price - stock_data.avg_price) / stock_data.avg_price) * 100.0;
-        let distance_to_target = ((target_price - current_price) / current_price) * 100.0;
-        let distance_to_stop_loss = ((current_price - stop_loss_price) / current_price) * 100.0;
-        let risk_reward_ratio = stock_data.target_profit / stock_data.max_loss;
-
-        let investment_scale = Self::classify_investment_scale(investment_amount);
-        let status = Self::determine_stock_status(
+        let target_price = Self::resolve_target_price(stock_data);
+        let fixed_stop_loss_price = Self::resolve_stop_loss_price(stock_data);
+        let target_profit = sign * (target_price - stock_data.avg_price) * stock_data.quantity;
+        let max_loss = sign * (stock_data.avg_price - fixed_stop_loss_price) * stock_data.quantity;
+
+        // 移动止损只向有利方向移动，多头取两者中较高的价格，空头取较低的价格
+        let trailing = Self::resolve_trailing_stop_price(stock_data, current_price);
+        let stop_loss_price = match (trailing, stock_data.direction) {
+            (Some((trailing_price, _)), PositionDirection::Long) => fixed_stop_loss_price.max(trailing_price),
+            (Some((trailing_price, _)), PositionDirection::Short) => fixed_stop_loss_price.min(trailing_price),
+            (None, _) => fixed_stop_loss_price,
+        };
+
+        let financing = stock_data
+            .financing
+            .as_ref()
+            .map(|financing| Self::analyze_financing(stock_data, financing, current_price, today));
+        let interest = financing.as_ref().map_or(0.0, |f| f.accrued_interest);
+
+        let current_profit = sign * (current_price - stock_data.avg_price) * stock_data.quantity;
+        let total_profit = current_profit + stock_data.realized_profit - interest;
+        let profit_ratio = sign * ((current_price - stock_data.avg_price) / stock_data.avg_price) * 100.0;
+        let distance_to_target = sign * ((target_price - current_price) / current_price) * 100.0;
+        let distance_to_stop_loss = sign * ((current_price - stop_loss_price) / current_price) * 100.0;
+        let risk_reward_ratio = target_profit / max_loss;
+
+        let investment_scale = Self::classify_investment_scale(investment_amount, equity, &config.scale);
+        let tiers = Self::analyze_tiers(stock_data);
+        let near_target = stock_data.near_target.unwrap_or(config.status.near_target);
+        let near_stop_loss = stock_data.near_stop_loss.unwrap_or(config.status.near_stop_loss);
+        let maintenance_breached = financing
+            .as_ref()
+            .is_some_and(|f| f.maintenance_ratio < f.min_maintenance_ratio);
+        let status = match Self::determine_stock_status(
+            sign,
             current_price,
             target_price,
             stop_loss_price,
             profit_ratio,
-        );
+            Self::near_target_price(&near_target, sign, target_price, stock_data.avg_price),
+            Self::near_stop_loss_price(&near_stop_loss, sign, stop_loss_price, stock_data.avg_price),
+        ) {
+            StockStatus::AtStopLoss => StockStatus::AtStopLoss,
+            _ if maintenance_breached => StockStatus::MaintenanceWarning,
+            StockStatus::AtTarget => StockStatus::AtTarget,
+            status => Self::determine_tier_status(&tiers, sign, current_price).unwrap_or(status),
+        };
 
         StockAnalysis {
             code: stock_data.code.clone(),
             current_price,
             quantity: stock_data.quantity,
             avg_price: stock_data.avg_price,
-            target_profit: stock_data.target_profit,
-            max_loss: stock_data.max_loss,
+            target_profit,
+            max_loss,
             target_price,
             stop_loss_price,
+            trailing_stop_price: trailing.map(|(price, _)| price),
+            high_water_mark: trailing.map(|(_, high)| high),
             current_profit,
+            realized_profit: stock_data.realized_profit,
+            total_profit,
             profit_ratio,
             distance_to_target,
             distance_to_stop_loss,
             risk_reward_ratio,
             investment_amount,
             investment_scale,
+            tiers,
             status,
+            volatility: None,
+            probability: None,
+            direction: stock_data.direction,
+            financing,
+            advice: Vec::new(),
+            holding_cost: Self::analyze_holding_cost(stock_data, None, &config.holding_cost, today),
             last_updated: Utc::now(),
         }
     }
 
-    fn classify_investment_scale(investment_amount: f64) -> InvestmentScale {
-        match investment_amount {
-            amount if amount < 10_000.0 => InvestmentScale::Small,
-            amount if amount <= 100_000.0 => InvestmentScale::Medium,
-            _ => InvestmentScale::Large,
+    /// 按持有天数（360 天计息）计算利息，以及维持担保比例。
+    /// 多头：(持仓市值 + 其他担保物) / (融资负债 + 利息)；
+    /// 空头：(融券卖出所得 + 其他担保物) / (买回所需市值 + 利息)
+    pub fn analyze_financing(
+        stock_data: &StockData,
+        financing: &Financing,
+        current_price: f64,
+        today: NaiveDate,
+    ) -> FinancingAnalysis {
+        let (holding_days, accrued_interest) = financing.accrued_interest(today);
+        let market_value = stock_data.quantity * current_price;
+
+        let (assets, liabilities) = match stock_data.direction {
+            PositionDirection::Long => (market_value + financing.collateral, financing.borrowed_amount + accrued_interest),
+            PositionDirection::Short => (
+                stock_data.quantity * stock_data.avg_price + financing.collateral,
+                market_value + accrued_interest,
+            ),
+        };
+
+        FinancingAnalysis {
+            borrowed_amount: financing.borrowed_amount,
+            interest_rate: financing.interest_rate,
+            holding_days,
+            accrued_interest,
+            maintenance_ratio: Self::percent_of(assets, liabilities),
+            min_maintenance_ratio: financing.maintenance_ratio,
+        }
+    }
+
+    /// 模拟补仓/加仓后的持仓，预期收益和最大亏损金额保持不变
+    pub fn simulate_trades(
+        stock_data: &StockData,
+        trades: &[SimulatedTrade],
+    ) -> Result<StockData> {
+        let mut total_quantity = stock_data.quantity;
+        let mut total_cost = stock_data.quantity * stock_data.avg_price;
+
+        for trade in trades {
+            if trade.quantity <= 0.0 {
+                return Err(StockCalcError::ValidationError(
+                    ValidationError::InvalidQuantity.to_string()
+                ));
+            }
+
+            if trade.price <= 0.0 {
+                return Err(StockCalcError::ValidationError(
+                    ValidationError::InvalidPrice.to_string()
+                ));
+            }
+
+            total_quantity += trade.quantity;
+            total_cost += trade.quantity * trade.price;
+        }
+
+        let mut simulated = stock_data.clone();
+        simulated.quantity = total_quantity;
+        simulated.avg_price = total_cost / total_quantity;
+        simulated.last_updated = Utc::now();
+
+        Ok(simulated)
+    }
+
+    pub fn validate_corporate_action(action: &CorporateAction) -> Result<()> {
+        let value = match action.kind {
+            CorporateActionKind::CashDividend { per_share } => per_share,
+            CorporateActionKind::BonusShares { per_share } => per_share,
+            CorporateActionKind::Split { ratio } => ratio,
+        };
+
+        if value <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidCorporateAction.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 应用已到除权除息日且尚未处理的公司行为，返回本次处理的记录
+    pub fn apply_due_corporate_actions(stock_data: &mut StockData, today: NaiveDate) -> Vec<CorporateAction> {
+        let mut due: Vec<usize> = (0..stock_data.corporate_actions.len())
+            .filter(|&i| {
+                let action = &stock_data.corporate_actions[i];
+                !action.applied && action.ex_date <= today
+            })
+            .collect();
+        // 同一天先派息再送转，派息按送转前的股数计算
+        due.sort_by_key(|&i| {
+            let action = &stock_data.corporate_actions[i];
+            let order = match action.kind {
+                CorporateActionKind::CashDividend { .. } => 0,
+                CorporateActionKind::BonusShares { .. } => 1,
+                CorporateActionKind::Split { .. } => 2,
+            };
+            (action.ex_date, order)
+        });
+
+        let mut applied = Vec::with_capacity(due.len());
+        for &i in &due {
+            let kind = stock_data.corporate_actions[i].kind.clone();
+            let cash_received = Self::apply_corporate_action(stock_data, &kind);
+
+            let action = &mut stock_data.corporate_actions[i];
+            action.applied = true;
+            action.cash_received = cash_received;
+            applied.push(action.clone());
+        }
+
+        applied
+    }
+
+    /// 派息计入已实现收益，送转和拆股按比例调整数量与成本价，
+    /// 目标、止损、分批档位和移动止损最高价同步除权，返回收到的现金。
+    /// 派息不改变成本价，按百分比或金额设定的阈值换算为除息后的价格，避免与已实现收益重复计算
+    pub fn apply_corporate_action(stock_data: &mut StockData, kind: &CorporateActionKind) -> f64 {
+        let cash_received = match *kind {
+            CorporateActionKind::CashDividend { per_share } => per_share * stock_data.quantity,
+            _ => 0.0,
+        };
+        stock_data.realized_profit += cash_received;
+
+        let (cash_per_share, share_factor) = match *kind {
+            CorporateActionKind::CashDividend { per_share } => (per_share, 1.0),
+            CorporateActionKind::BonusShares { per_share } => (0.0, 1.0 + per_share),
+            CorporateActionKind::Split { ratio } => (0.0, ratio),
+        };
+
+        let adjust = |price: f64| (price - cash_per_share) / share_factor;
+        // 送转和拆股后以成本价为基准的阈值自动等比例调整，派息需要把触发价下移每股派息额
+        let (sign, avg_price, quantity) = (stock_data.direction.sign(), stock_data.avg_price, stock_data.quantity);
+        let rebase = |value: f64, kind: TargetKind, side: TierSide| {
+            let direction = sign * match side {
+                TierSide::TakeProfit => 1.0,
+                TierSide::StopLoss => -1.0,
+            };
+            match kind {
+                TargetKind::Amount => value - direction * cash_per_share * quantity,
+                TargetKind::Percent => value - direction * cash_per_share / avg_price * 100.0,
+                TargetKind::Price => adjust(value),
+            }
+        };
+
+        stock_data.target_profit = rebase(stock_data.target_profit, stock_data.target_kind, TierSide::TakeProfit);
+        stock_data.max_loss = rebase(stock_data.max_loss, stock_data.stop_kind, TierSide::StopLoss);
+        for tier in &mut stock_data.take_profit_tiers {
+            tier.value = rebase(tier.value, tier.kind, TierSide::TakeProfit);
+        }
+        for tier in &mut stock_data.stop_loss_tiers {
+            tier.value = rebase(tier.value, tier.kind, TierSide::StopLoss);
+        }
+
+        stock_data.quantity *= share_factor;
+        stock_data.avg_price /= share_factor;
+
+        if let Some(trailing) = stock_data.trailing_stop.as_mut() {
+            trailing.high_water_mark = trailing.high_water_mark.map(adjust);
+            if trailing.kind == TrailingKind::Amount {
+                trailing.distance /= share_factor;
+            }
+        }
+
+        stock_data.last_updated = Utc::now();
+        cash_received
+    }
+
+    /// 买入并入持仓，手续费计入成本，返回现金变动（流出为负）
+    pub fn apply_buy(stock_data: &mut StockData, quantity: f64, price: f64, fee: f64) -> Result<f64> {
+        Self::validate_trade(quantity, price, fee)?;
+        Self::ensure_long(stock_data)?;
+
+        let cost = quantity * price + fee;
+        let total_cost = stock_data.quantity * stock_data.avg_price + cost;
+        stock_data.quantity += quantity;
+        stock_data.avg_price = total_cost / stock_data.quantity;
+        stock_data.last_updated = Utc::now();
+
+        Ok(-cost)
+    }
+
+    /// 卖出部分或全部持仓，差价扣除手续费后计入已实现收益，返回现金变动（流入为正）
+    pub fn apply_sell(stock_data: &mut StockData, quantity: f64, price: f64, fee: f64) -> Result<f64> {
+        Self::validate_trade(quantity, price, fee)?;
+        Self::ensure_long(stock_data)?;
+
+        if quantity > stock_data.quantity + 1e-9 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidQuantity.to_string()
+            ));
+        }
+
+        stock_data.realized_profit += (price - stock_data.avg_price) * quantity - fee;
+        stock_data.quantity -= quantity;
+        stock_data.last_updated = Utc::now();
+
+        Ok(quantity * price - fee)
+    }
+
+    fn ensure_long(stock_data: &StockData) -> Result<()> {
+        if stock_data.direction == PositionDirection::Short {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::ShortPositionTrade.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn validate_trade(quantity: f64, price: f64, fee: f64) -> Result<()> {
+        if quantity <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidQuantity.to_string()
+            ));
+        }
+
+        if price <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidPrice.to_string()
+            ));
+        }
+
+        if fee < 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidNumber.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 汇总所有持仓的市值、成本、盈亏、当日涨跌、权重和止损风险，并结合现金计算总权益和收益
+    pub fn calculate_portfolio_summary(
+        holdings: &[(StockData, StockInfo)],
+        cash: f64,
+        net_deposits: f64,
+        config: &AppConfig,
+    ) -> PortfolioSummary {
+        let today = Local::now().date_naive();
+        let equity = Portfolio::net_equity(cash, holdings.iter().map(|(stock_data, info)| (stock_data, info.current_price)), today);
+        let mut positions: Vec<PortfolioPosition> = holdings
+            .iter()
+            .map(|(stock_data, info)| {
+                let analysis = Self::calculate_analysis_with_config(stock_data, info.current_price, config, Some(equity));
+                let sign = stock_data.direction.sign();
+                let market_value = stock_data.signed_value(info.current_price);
+                let risk_to_stop = (sign * (info.current_price - analysis.stop_loss_price) * stock_data.quantity).max(0.0);
+
+                PortfolioPosition {
+                    name: info.name.clone(),
+                    market_value,
+                    day_change: sign * info.change_amount * stock_data.quantity,
+                    weight: 0.0,
+                    risk_to_stop,
+                    analysis,
+                }
+            })
+            .collect();
+
+        let market_value: f64 = positions.iter().map(|p| p.market_value).sum();
+        let cost: f64 = positions.iter().map(|p| p.analysis.direction.sign() * p.analysis.investment_amount).sum();
+        let realized_profit: f64 = positions.iter().map(|p| p.analysis.realized_profit).sum();
+        let day_change: f64 = positions.iter().map(|p| p.day_change).sum();
+        let risk_to_stop: f64 = positions.iter().map(|p| p.risk_to_stop).sum();
+
+        for position in &mut positions {
+            position.weight = Self::percent_of(position.market_value, equity);
+        }
+        positions.sort_by(|a, b| b.market_value.total_cmp(&a.market_value));
+
+        let unrealized_profit = market_value - cost;
+        let total_return = equity - net_deposits;
+
+        PortfolioSummary {
+            positions,
+            cash,
+            equity,
+            invested_percent: Self::percent_of(market_value, equity),
+            net_deposits,
+            total_return,
+            total_return_percent: Self::percent_of(total_return, net_deposits),
+            market_value,
+            cost,
+            unrealized_profit,
+            unrealized_profit_ratio: Self::percent_of(unrealized_profit, cost),
+            realized_profit,
+            day_change,
+            day_change_percent: Self::percent_of(day_change, market_value - day_change),
+            risk_to_stop,
+            risk_percent: Self::percent_of(risk_to_stop, market_value),
+        }
+    }
+
+    fn percent_of(value: f64, base: f64) -> f64 {
+        if base.abs() > f64::EPSILON {
+            value / base * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// 根据 ATR 倍数推算止损价格，空头的止损在入场价上方
+    pub fn stop_price_from_atr(direction: PositionDirection, entry_price: f64, atr: f64, atr_multiple: f64) -> f64 {
+        entry_price - direction.sign() * atr * atr_multiple
+    }
+
+    /// 根据账户资金和单笔风险比例反推买入数量（按每手股数向下取整）
+    pub fn calculate_position_size(
+        equity: f64,
+        risk_percent: f64,
+        entry_price: f64,
+        stop_loss_price: f64,
+        lot_size: f64,
+        reward_ratio: f64,
+    ) -> Result<PositionSizing> {
+        if equity <= 0.0 || lot_size <= 0.0 || reward_ratio <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidNumber.to_string()
+            ));
+        }
+
+        if risk_percent <= 0.0 || risk_percent > 100.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidRiskPercent.to_string()
+            ));
+        }
+
+        if entry_price <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidPrice.to_string()
+            ));
+        }
+
+        if stop_loss_price <= 0.0 || stop_loss_price >= entry_price {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidStopPrice.to_string()
+            ));
+        }
+
+        let risk_budget = equity * risk_percent / 100.0;
+        let risk_per_share = entry_price - stop_loss_price;
+
+        // 数量同时受风险预算和可用资金约束
+        let by_risk = (risk_budget / risk_per_share / lot_size).floor() * lot_size;
+        let by_equity = (equity / entry_price / lot_size).floor() * lot_size;
+        let quantity = by_risk.min(by_equity);
+
+        if quantity <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InsufficientRiskBudget.to_string()
+            ));
+        }
+
+        let target_price = entry_price + risk_per_share * reward_ratio;
+
+        Ok(PositionSizing {
+            equity,
+            risk_percent,
+            entry_price,
+            stop_loss_price,
+            risk_per_share,
+            risk_budget,
+            quantity,
+            investment_amount: quantity * entry_price,
+            max_loss: quantity * risk_per_share,
+            reward_ratio,
+            target_price,
+            target_profit: quantity * (target_price - entry_price),
+        })
+    }
+
+    /// 按配置分档；按权益占比分档但权益未知时，视为投入即全部权益
+    fn classify_investment_scale(investment_amount: f64, equity: Option<f64>, config: &ScaleConfig) -> String {
+        let value = if config.relative_to_equity {
+            match equity {
+                Some(equity) if equity > 0.0 => Self::percent_of(investment_amount, equity),
+                _ => 100.0,
+            }
+        } else {
+            investment_amount
+        };
+
+        config
+            .buckets
+            .iter()
+            .find(|bucket| bucket.below.map_or(true, |below| value < below || (bucket.inclusive && value == below)))
+            .or(config.buckets.last())
+            .map(|bucket| bucket.label.clone())
+            .unwrap_or_default()
+    }
+
+    /// 价格不低于该值时视为接近目标价（空头为不高于），sign 为持仓方向
+    pub fn near_target_price(threshold: &NearThreshold, sign: f64, target_price: f64, avg_price: f64) -> f64 {
+        match threshold.kind {
+            NearThresholdKind::Percent => target_price * (1.0 - sign * threshold.value / 100.0),
+            NearThresholdKind::Price => target_price - sign * threshold.value,
+            NearThresholdKind::Distance => target_price - (target_price - avg_price) * threshold.value / 100.0,
+        }
+    }
+
+    /// 价格不高于该值时视为接近止损价（空头为不低于），sign 为持仓方向
+    pub fn near_stop_loss_price(threshold: &NearThreshold, sign: f64, stop_loss_price: f64, avg_price: f64) -> f64 {
+        match threshold.kind {
+            NearThresholdKind::Percent => stop_loss_price * (1.0 + sign * threshold.value / 100.0),
+            NearThresholdKind::Price => stop_loss_price + sign * threshold.value,
+            NearThresholdKind::Distance => stop_loss_price + (avg_price - stop_loss_price) * threshold.value / 100.0,
         }
     }
 
+    /// 价格都乘以方向 sign 后比较，空头的目标价在下方、止损价在上方
     fn determine_stock_status(
+        sign: f64,
         current_price: f64,
         target_price: f64,
         stop_loss_price: f64,
         profit_ratio: f64,
+        near_target_price: f64,
+        near_stop_loss_price: f64,
     ) -> StockStatus {
-        if current_price >= target_price {
+        let current_price = sign * current_price;
+        if current_price >= sign * target_price {
             StockStatus::AtTarget
-        } else if current_price <= stop_loss_price {
+        } else if current_price <= sign * stop_loss_price {
             StockStatus::AtStopLoss
         } else if profit_ratio > 0.0 {
-            if current_price >= target_price * 0.95 {
+            if current_price >= sign * near_target_price {
                 StockStatus::NearTarget
             } else {
                 StockStatus::Profitable
             }
         } else {
-            if current_price <= stop_loss_price * 1.05 {
+            if current_price <= sign * near_stop_loss_price {
                 StockStatus::NearStopLoss
             } else {
                 StockStatus::Loss
@@ -122,31 +862,97 @@ impl StockCalculator {
         }
     }
 
+    /// 未触发的档位中，止损取离成本最远的已触及档，止盈取离成本最远的已触及档
+    fn determine_tier_status(tiers: &[TierAnalysis], sign: f64, current_price: f64) -> Option<StockStatus> {
+        let reached = || Self::pending_reached_tiers(tiers, sign, current_price);
+
+        let stop_loss = reached()
+            .filter(|tier| tier.side == TierSide::StopLoss)
+            .min_by(|a, b| (sign * a.price).total_cmp(&(sign * b.price)));
+        if let Some(tier) = stop_loss {
+            return Some(StockStatus::AtStopLossTier(tier.tier));
+        }
+
+        reached()
+            .filter(|tier| tier.side == TierSide::TakeProfit)
+            .max_by(|a, b| (sign * a.price).total_cmp(&(sign * b.price)))
+            .map(|tier| StockStatus::AtTakeProfitTier(tier.tier))
+    }
+
+    fn pending_reached_tiers(tiers: &[TierAnalysis], sign: f64, current_price: f64) -> impl Iterator<Item = &TierAnalysis> {
+        tiers.iter().filter(move |tier| {
+            !tier.triggered
+                && match tier.side {
+                    TierSide::StopLoss => sign * current_price <= sign * tier.price,
+                    TierSide::TakeProfit => sign * current_price >= sign * tier.price,
+                }
+        })
+    }
+
+    /// 当前价格已触及但尚未标记触发的档位，价格跳空越过多档时逐档提醒。
+    /// 触及止损档时只返回止损档
+    pub fn newly_reached_tiers(analysis: &StockAnalysis) -> Vec<&TierAnalysis> {
+        let sign = analysis.direction.sign();
+        let reached: Vec<&TierAnalysis> = Self::pending_reached_tiers(&analysis.tiers, sign, analysis.current_price).collect();
+        if reached.iter().any(|tier| tier.side == TierSide::StopLoss) {
+            return reached.into_iter().filter(|tier| tier.side == TierSide::StopLoss).collect();
+        }
+        reached
+    }
+
     pub fn format_currency(amount: f64) -> String {
         format!("¥{:.3}", amount)
     }
 
+    pub fn format_near_threshold(threshold: &NearThreshold) -> String {
+        match threshold.kind {
+            NearThresholdKind::Percent => format!("价格的 {:.2}%", threshold.value),
+            NearThresholdKind::Price => format!("价差 ¥{:.3}", threshold.value),
+            NearThresholdKind::Distance => format!("剩余距离的 {:.2}%", threshold.value),
+        }
+    }
+
     pub fn format_percentage(percentage: f64) -> String {
         format!("{:.2}%", percentage)
     }
 
-    pub fn get_risk_level(risk_reward_ratio: f64) -> &'static str {
-        match risk_reward_ratio {
-            ratio if ratio >= 3.0 => "优秀",
-            ratio if ratio >= 2.0 => "良好",
-            ratio if ratio >= 1.5 => "一般",
-            _ => "较差",
+    pub fn format_target_spec(value: f64, kind: TargetKind) -> String {
+        match kind {
+            TargetKind::Amount => Self::format_currency(value),
+            TargetKind::Percent => Self::format_percentage(value),
+            TargetKind::Price => format!("价格 {}", Self::format_currency(value)),
+        }
+    }
+
+    pub fn format_trailing_stop(trailing: &TrailingStop) -> String {
+        let distance = match trailing.kind {
+            TrailingKind::Percent => format!("回撤 {}", Self::format_percentage(trailing.distance)),
+            TrailingKind::Amount => format!("回撤 {}", Self::format_currency(trailing.distance)),
+        };
+
+        match trailing.activation_profit {
+            Some(activation) => format!("{}，盈利 {} 后启用", distance, Self::format_percentage(activation)),
+            None => distance,
         }
     }
 
-    pub fn get_investment_scale_text(scale: &InvestmentScale) -> &'static str {
-        match scale {
-            InvestmentScale::Small => "小额",
-            InvestmentScale::Medium => "中等",
-            InvestmentScale::Large => "大额",
+    pub fn format_corporate_action(kind: &CorporateActionKind) -> String {
+        match *kind {
+            CorporateActionKind::CashDividend { per_share } => format!("每10股派 {:.3} 元", per_share * 10.0),
+            CorporateActionKind::BonusShares { per_share } => format!("每10股送转 {:.2} 股", per_share * 10.0),
+            CorporateActionKind::Split { ratio } => format!("1股拆为 {} 股", ratio),
         }
     }
 
+    pub fn get_risk_level(risk_reward_ratio: f64, config: &RiskGradingConfig) -> &str {
+        config
+            .grades
+            .iter()
+            .find(|grade| risk_reward_ratio >= grade.min_ratio)
+            .or(config.grades.last())
+            .map_or("", |grade| grade.label.as_str())
+    }
+
     pub fn get_status_emoji(status: &StockStatus) -> &'static str {
         match status {
             StockStatus::Profitable => "📈",
@@ -155,6 +961,9 @@ impl StockCalculator {
             StockStatus::AtStopLoss => "⚠️",
             StockStatus::NearTarget => "🎯",
             StockStatus::NearStopLoss => "🚨",
+            StockStatus::AtTakeProfitTier(_) => "💰",
+            StockStatus::AtStopLossTier(_) => "🔻",
+            StockStatus::MaintenanceWarning => "🏦",
         }
     }
 }
@@ -162,8 +971,19 @@ impl StockCalculator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{RiskGrade, ScaleBucket, StatusConfig};
     use crate::models::StockData;
-    use chrono::Utc;
+    use std::collections::HashMap;
+
+    /// 从 2024-01-01 起逐日排列的收盘价序列，供各子模块的测试共用
+    pub(super) fn series(closes: &[f64]) -> Vec<(NaiveDate, f64)> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| (start + chrono::Duration::days(i as i64), *close))
+            .collect()
+    }
 
     #[test]
     fn test_validate_input() {
@@ -182,14 +1002,7 @@ mod tests {
 
     #[test]
     fn test_calculate_analysis() {
-        let stock_data = StockData {
-            code: "000001".to_string(),
-            quantity: 1000.0,
-            avg_price: 15.5,
-            target_profit: 5000.0,
-            max_loss: 2000.0,
-            last_updated: Utc::now(),
-        };
+        let stock_data = StockData::new("000001".to_string(), 1000.0, 15.5, 5000.0, 2000.0);
 
         let analysis = StockCalculator::calculate_analysis(&stock_data, 16.2);
 
@@ -202,17 +1015,487 @@ mod tests {
 
     #[test]
     fn test_classify_investment_scale() {
-        assert!(matches!(
-            StockCalculator::classify_investment_scale(5000.0),
-            InvestmentScale::Small
-        ));
-        assert!(matches!(
-            StockCalculator::classify_investment_scale(50000.0),
-            InvestmentScale::Medium
-        ));
-        assert!(matches!(
-            StockCalculator::classify_investment_scale(150000.0),
-            InvestmentScale::Large
-        ));
-    }
-} 
\ No newline at end of file
+        let config = ScaleConfig::default();
+        assert_eq!(StockCalculator::classify_investment_scale(5000.0, None, &config), "小额");
+        assert_eq!(StockCalculator::classify_investment_scale(50000.0, None, &config), "中等");
+        assert_eq!(StockCalculator::classify_investment_scale(150000.0, None, &config), "大额");
+        // 边界: 10000 属于中等，100000 仍属于中等
+        assert_eq!(StockCalculator::classify_investment_scale(10000.0, None, &config), "中等");
+        assert_eq!(StockCalculator::classify_investment_scale(100000.0, None, &config), "中等");
+        assert_eq!(StockCalculator::classify_investment_scale(100000.01, None, &config), "大额");
+
+        // 按账户权益占比分档
+        let relative = ScaleConfig {
+            relative_to_equity: true,
+            buckets: vec![
+                ScaleBucket { below: Some(10.0), inclusive: false, label: "轻仓".to_string() },
+                ScaleBucket { below: None, inclusive: false, label: "重仓".to_string() },
+            ],
+        };
+        assert_eq!(StockCalculator::classify_investment_scale(50000.0, Some(1_000_000.0), &relative), "轻仓");
+        assert_eq!(StockCalculator::classify_investment_scale(50000.0, Some(200_000.0), &relative), "重仓");
+        assert_eq!(StockCalculator::classify_investment_scale(50000.0, None, &relative), "重仓");
+    }
+
+    #[test]
+    fn test_risk_level() {
+        let config = RiskGradingConfig::default();
+        assert_eq!(StockCalculator::get_risk_level(3.5, &config), "优秀");
+        assert_eq!(StockCalculator::get_risk_level(1.6, &config), "一般");
+        assert_eq!(StockCalculator::get_risk_level(-1.0, &config), "较差");
+
+        let custom = RiskGradingConfig {
+            grades: vec![
+                RiskGrade { min_ratio: 2.0, label: "A".to_string() },
+                RiskGrade { min_ratio: 1.0, label: "B".to_string() },
+            ],
+        };
+        assert_eq!(StockCalculator::get_risk_level(2.5, &custom), "A");
+        assert_eq!(StockCalculator::get_risk_level(0.5, &custom), "B");
+    }
+
+    #[test]
+    fn test_calculate_position_size() {
+        // 10万资金，单笔风险1%，买入10元，止损9.5元 => 风险预算1000元，每股风险0.5元
+        let sizing = StockCalculator::calculate_position_size(100_000.0, 1.0, 10.0, 9.5, 100.0, 2.0).unwrap();
+        assert_eq!(sizing.quantity, 2000.0);
+        assert!((sizing.max_loss - 1000.0).abs() < 0.01);
+        assert!((sizing.target_price - 11.0).abs() < 0.001);
+        assert!((sizing.target_profit - 2000.0).abs() < 0.01);
+
+        // 按手向下取整
+        let sizing = StockCalculator::calculate_position_size(100_000.0, 1.0, 10.0, 9.3, 100.0, 2.0).unwrap();
+        assert_eq!(sizing.quantity, 1400.0);
+
+        // 受可用资金约束
+        let sizing = StockCalculator::calculate_position_size(10_000.0, 10.0, 10.0, 9.99, 100.0, 2.0).unwrap();
+        assert_eq!(sizing.quantity, 1000.0);
+
+        // 止损价格高于买入价格
+        assert!(StockCalculator::calculate_position_size(100_000.0, 1.0, 10.0, 10.5, 100.0, 2.0).is_err());
+
+        // 风险预算不足一手
+        assert!(StockCalculator::calculate_position_size(1_000.0, 1.0, 10.0, 9.0, 100.0, 2.0).is_err());
+    }
+
+    #[test]
+    fn test_simulate_trades() {
+        let stock_data = StockData::new("000001".to_string(), 1000.0, 15.5, 5000.0, 2000.0);
+
+        let trades = vec![
+            SimulatedTrade { quantity: 1000.0, price: 12.5 },
+            SimulatedTrade { quantity: 2000.0, price: 12.0 },
+        ];
+        let simulated = StockCalculator::simulate_trades(&stock_data, &trades).unwrap();
+
+        assert_eq!(simulated.quantity, 4000.0);
+        assert!((simulated.avg_price - 13.0).abs() < 0.001); // (15500 + 12500 + 24000) / 4000
+
+        let analysis = StockCalculator::calculate_analysis(&simulated, 13.0);
+        assert!((analysis.target_price - 14.25).abs() < 0.001); // 13 + 5000/4000
+        assert!((analysis.stop_loss_price - 12.5).abs() < 0.001); // 13 - 2000/4000
+
+        // 原持仓不受影响
+        assert_eq!(stock_data.quantity, 1000.0);
+
+        assert!(StockCalculator::simulate_trades(&stock_data, &[SimulatedTrade { quantity: 0.0, price: 12.0 }]).is_err());
+    }
+
+    #[test]
+    fn test_target_kinds() {
+        // 止盈 +20%，止损价 14.0
+        let stock_data = StockData {
+            target_kind: TargetKind::Percent,
+            stop_kind: TargetKind::Price,
+            ..StockData::new("000001".to_string(), 1000.0, 15.0, 20.0, 14.0)
+        };
+
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 16.0);
+        assert!((analysis.target_price - 18.0).abs() < 0.001);
+        assert!((analysis.stop_loss_price - 14.0).abs() < 0.001);
+        assert!((analysis.target_profit - 3000.0).abs() < 0.01);
+        assert!((analysis.max_loss - 1000.0).abs() < 0.01);
+        assert!((analysis.risk_reward_ratio - 3.0).abs() < 0.001);
+
+        assert!(StockCalculator::validate_target_kinds(PositionDirection::Long, 15.0, 20.0, TargetKind::Percent, 14.0, TargetKind::Price).is_ok());
+        assert!(StockCalculator::validate_target_kinds(PositionDirection::Long, 15.0, 14.0, TargetKind::Price, 1000.0, TargetKind::Amount).is_err());
+        assert!(StockCalculator::validate_target_kinds(PositionDirection::Long, 15.0, 5000.0, TargetKind::Amount, 16.0, TargetKind::Price).is_err());
+        assert!(StockCalculator::validate_target_kinds(PositionDirection::Long, 15.0, 5000.0, TargetKind::Amount, 100.0, TargetKind::Percent).is_err());
+    }
+
+    #[test]
+    fn test_near_thresholds() {
+        // 成本 10，目标 12，止损 9
+        let stock_data = StockData {
+            target_kind: TargetKind::Price,
+            stop_kind: TargetKind::Price,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 12.0, 9.0)
+        };
+
+        // 默认 5%：11.4 以上接近目标，9.45 以下接近止损
+        let default = AppConfig::default();
+        assert!(matches!(StockCalculator::calculate_analysis(&stock_data, 11.5).status, StockStatus::NearTarget));
+        assert!(matches!(StockCalculator::calculate_analysis(&stock_data, 11.3).status, StockStatus::Profitable));
+        assert!(matches!(StockCalculator::calculate_analysis(&stock_data, 9.4).status, StockStatus::NearStopLoss));
+
+        // 全局按价差 0.1 判断
+        let price_config = AppConfig {
+            status: StatusConfig {
+                near_target: NearThreshold { kind: NearThresholdKind::Price, value: 0.1 },
+                near_stop_loss: NearThreshold { kind: NearThresholdKind::Price, value: 0.1 },
+            },
+            ..AppConfig::default()
+        };
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&stock_data, 11.5, &price_config, None).status, StockStatus::Profitable));
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&stock_data, 11.95, &price_config, None).status, StockStatus::NearTarget));
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&stock_data, 9.4, &price_config, None).status, StockStatus::Loss));
+
+        // 单个持仓按剩余距离 50% 判断，优先于全局配置
+        let per_position = StockData {
+            near_target: Some(NearThreshold { kind: NearThresholdKind::Distance, value: 50.0 }),
+            near_stop_loss: Some(NearThreshold { kind: NearThresholdKind::Distance, value: 50.0 }),
+            ..stock_data.clone()
+        };
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&per_position, 11.1, &price_config, None).status, StockStatus::NearTarget));
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&per_position, 10.9, &default, None).status, StockStatus::Profitable));
+        assert!(matches!(StockCalculator::calculate_analysis_with_config(&per_position, 9.5, &default, None).status, StockStatus::NearStopLoss));
+
+        assert_eq!("price:0.05".parse::<NearThreshold>().unwrap(), NearThreshold { kind: NearThresholdKind::Price, value: 0.05 });
+        assert!("percent".parse::<NearThreshold>().is_err());
+        assert!("ratio:3".parse::<NearThreshold>().is_err());
+    }
+
+    #[test]
+    fn test_trailing_stop() {
+        let mut stock_data = StockData {
+            trailing_stop: Some(TrailingStop {
+                kind: TrailingKind::Percent,
+                distance: 10.0,
+                activation_profit: Some(10.0),
+                high_water_mark: None,
+            }),
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 1000.0)
+        };
+
+        // 未达到启用条件，使用固定止损
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 10.5);
+        assert!(analysis.trailing_stop_price.is_none());
+        assert!((analysis.stop_loss_price - 9.0).abs() < 0.001);
+
+        // 最高价 12.0 后回落，止损上移到 10.8
+        assert!(StockCalculator::update_trailing_stop(&mut stock_data, 12.0));
+        assert!(!StockCalculator::update_trailing_stop(&mut stock_data, 11.5));
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 11.5);
+        assert!((analysis.stop_loss_price - 10.8).abs() < 0.001);
+        assert_eq!(analysis.high_water_mark, Some(12.0));
+
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 10.7);
+        assert!(matches!(analysis.status, StockStatus::AtStopLoss));
+
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_ok());
+        stock_data.trailing_stop.as_mut().unwrap().distance = 100.0;
+        let err = StockCalculator::validate_stock_data(&stock_data).unwrap_err();
+        assert!(err.to_string().contains(&ValidationError::InvalidTrailingStop.to_string()));
+    }
+
+    #[test]
+    fn test_short_and_margin() {
+        // 融券卖空：盈利 5000 的目标价 5.0，亏损 1000 的止损价 11.0
+        let mut short = StockData {
+            direction: PositionDirection::Short,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 1000.0)
+        };
+        assert!(StockCalculator::validate_stock_data(&short).is_ok());
+        assert!(StockCalculator::validate_target_kinds(PositionDirection::Short, 10.0, 11.0, TargetKind::Price, 9.0, TargetKind::Price).is_err());
+
+        let analysis = StockCalculator::calculate_analysis(&short, 9.0);
+        assert!((analysis.target_price - 5.0).abs() < 0.001);
+        assert!((analysis.stop_loss_price - 11.0).abs() < 0.001);
+        assert!((analysis.current_profit - 1000.0).abs() < 0.001);
+        assert!(matches!(analysis.status, StockStatus::Profitable));
+        assert!(matches!(StockCalculator::calculate_analysis(&short, 11.2).status, StockStatus::AtStopLoss));
+        assert!(matches!(StockCalculator::calculate_analysis(&short, 4.8).status, StockStatus::AtTarget));
+
+        // 空头移动止损跟随最低价下移
+        short.trailing_stop = Some(TrailingStop {
+            kind: TrailingKind::Percent,
+            distance: 10.0,
+            activation_profit: None,
+            high_water_mark: None,
+        });
+        assert!(StockCalculator::update_trailing_stop(&mut short, 8.0));
+        assert!(!StockCalculator::update_trailing_stop(&mut short, 8.5));
+        let analysis = StockCalculator::calculate_analysis(&short, 8.5);
+        assert!((analysis.stop_loss_price - 8.8).abs() < 0.001);
+        assert!(matches!(StockCalculator::calculate_analysis(&short, 9.0).status, StockStatus::AtStopLoss));
+
+        // 融资买入 5000，年化 7.2%，持有 100 天计息 100
+        let today = chrono::Local::now().date_naive();
+        let margin = StockData {
+            financing: Some(Financing {
+                borrowed_amount: 5000.0,
+                interest_rate: 7.2,
+                start_date: today - chrono::Duration::days(100),
+                maintenance_ratio: 130.0,
+                collateral: 0.0,
+            }),
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 5000.0)
+        };
+        assert!(StockCalculator::validate_stock_data(&margin).is_ok());
+
+        let analysis = StockCalculator::calculate_analysis(&margin, 10.0);
+        let financing = analysis.financing.as_ref().unwrap();
+        assert_eq!(financing.holding_days, 100);
+        assert!((financing.accrued_interest - 100.0).abs() < 0.001);
+        assert!((financing.maintenance_ratio - 10000.0 / 5100.0 * 100.0).abs() < 0.001);
+        assert!((analysis.total_profit + 100.0).abs() < 0.001);
+        assert!(!matches!(analysis.status, StockStatus::MaintenanceWarning));
+
+        // 6000 / 5100 ≈ 117.6%，低于 130%
+        let analysis = StockCalculator::calculate_analysis(&margin, 6.0);
+        assert!(matches!(analysis.status, StockStatus::MaintenanceWarning));
+    }
+
+    #[test]
+    fn test_net_equity() {
+        let today = chrono::Local::now().date_naive();
+        let mut portfolio = Portfolio::new("test", 20000.0);
+
+        // 融券卖出不改变权益，价格下跌后权益增加
+        let short = StockData {
+            direction: PositionDirection::Short,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 1000.0)
+        };
+        portfolio.stocks.insert(short.code.clone(), short.clone());
+        assert!((portfolio.book_equity() - 20000.0).abs() < 1e-9);
+
+        // 融资买入 10000，其中 5000 为融资，自有资金 5000 从现金扣除
+        let margin = StockData {
+            financing: Some(Financing {
+                borrowed_amount: 5000.0,
+                interest_rate: 0.0,
+                start_date: today,
+                maintenance_ratio: 130.0,
+                collateral: 0.0,
+            }),
+            ..StockData::new("600000".to_string(), 1000.0, 10.0, 5000.0, 1000.0)
+        };
+        portfolio.cash -= 5000.0;
+        portfolio.stocks.insert(margin.code.clone(), margin.clone());
+        assert!((portfolio.book_equity() - 20000.0).abs() < 1e-9);
+
+        // 组合汇总、快照与收益表现的权益一致：15000 + 1000 + (11000 - 5000)
+        let prices = HashMap::from([("000001".to_string(), 9.0), ("600000".to_string(), 11.0)]);
+        let holdings = vec![(short, stock_info("000001", 9.0, 0.0)), (margin, stock_info("600000", 11.0, 0.0))];
+        let summary = StockCalculator::calculate_portfolio_summary(&holdings, portfolio.cash, 20000.0, &AppConfig::default());
+        assert!((summary.equity - 22000.0).abs() < 1e-9);
+        assert!((StockCalculator::take_snapshot(&portfolio, &prices, today).equity - 22000.0).abs() < 1e-9);
+        let performance = StockCalculator::calculate_portfolio_performance(&portfolio, &prices, today);
+        assert!((performance.equity - 22000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_merge_monitor_state() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let mut stored = StockData {
+            trailing_stop: Some(TrailingStop {
+                kind: TrailingKind::Percent,
+                distance: 10.0,
+                activation_profit: None,
+                high_water_mark: None,
+            }),
+            take_profit_tiers: vec![ExitTier { fraction: 0.5, value: 20.0, kind: TargetKind::Percent, triggered: false }],
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 1000.0)
+        };
+
+        // 第一次刷新：监控副本记录最高价 11.5 并保存
+        let mut monitored = stored.clone();
+        assert!(StockCalculator::update_trailing_stop(&mut monitored, 11.5));
+        StockCalculator::merge_monitor_state(&mut stored, &monitored, today);
+        assert_eq!(stored.trailing_stop.as_ref().unwrap().high_water_mark, Some(11.5));
+
+        // 两次刷新之间加仓，数据库中的数量和成本已变化
+        stored.quantity = 2000.0;
+        stored.avg_price = 11.0;
+        stored.realized_profit = 300.0;
+
+        // 第二次刷新用的仍是旧副本：只合并最高价和触发标记
+        assert!(StockCalculator::update_trailing_stop(&mut monitored, 12.5));
+        assert!(StockCalculator::mark_triggered_tiers(&mut monitored, 12.5));
+        StockCalculator::merge_monitor_state(&mut stored, &monitored, today);
+        assert_eq!(stored.quantity, 2000.0);
+        assert_eq!(stored.avg_price, 11.0);
+        assert_eq!(stored.realized_profit, 300.0);
+        assert_eq!(stored.trailing_stop.as_ref().unwrap().high_water_mark, Some(12.5));
+        assert!(stored.take_profit_tiers[0].triggered);
+
+        // 旧副本中较低的最高价不会覆盖最新记录
+        stored.trailing_stop.as_mut().unwrap().high_water_mark = Some(13.0);
+        StockCalculator::merge_monitor_state(&mut stored, &monitored, today);
+        assert_eq!(stored.trailing_stop.as_ref().unwrap().high_water_mark, Some(13.0));
+    }
+
+    #[test]
+    fn test_exit_tiers() {
+        let tier = |fraction: f64, value: f64| ExitTier {
+            fraction,
+            value,
+            kind: TargetKind::Percent,
+            triggered: false,
+        };
+        let mut stock_data = StockData {
+            target_kind: TargetKind::Percent,
+            take_profit_tiers: vec![tier(0.25, 10.0), tier(0.25, 20.0), tier(0.5, 30.0)],
+            stop_loss_tiers: vec![tier(0.5, 5.0), tier(0.5, 10.0)],
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 30.0, 1000.0)
+        };
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_ok());
+
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 12.1);
+        assert_eq!(analysis.tiers.len(), 5);
+        assert!((analysis.tiers[1].price - 12.0).abs() < 0.001);
+        assert!((analysis.tiers[1].expected_profit - 500.0).abs() < 0.01);
+        assert!(matches!(analysis.status, StockStatus::AtTakeProfitTier(2)));
+        // 一次越过两档时两档都要提醒
+        let reached: Vec<usize> = StockCalculator::newly_reached_tiers(&analysis).iter().map(|t| t.tier).collect();
+        assert_eq!(reached, vec![1, 2]);
+
+        // 触发后不再重复提醒
+        assert!(StockCalculator::mark_triggered_tiers(&mut stock_data, 12.1));
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 12.1);
+        assert!(matches!(analysis.status, StockStatus::Profitable));
+
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 9.4);
+        assert!(matches!(analysis.status, StockStatus::AtStopLossTier(1)));
+
+        stock_data.stop_loss_tiers.push(tier(0.5, 15.0));
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_err());
+
+        // 多头止损档 100% 以上或价格不低于成本价无效
+        stock_data.stop_loss_tiers = vec![tier(0.5, 100.0)];
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_err());
+        stock_data.stop_loss_tiers = vec![ExitTier { kind: TargetKind::Price, ..tier(0.5, 10.0) }];
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_err());
+        stock_data.stop_loss_tiers = vec![ExitTier { kind: TargetKind::Price, ..tier(0.5, 9.5) }];
+        assert!(StockCalculator::validate_stock_data(&stock_data).is_ok());
+    }
+
+    #[test]
+    fn test_corporate_actions() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let mut stock_data = StockData {
+            target_kind: TargetKind::Price,
+            corporate_actions: vec![
+                // 10送5，派息先于送转处理
+                CorporateAction {
+                    ex_date: date("2024-06-03"),
+                    kind: CorporateActionKind::BonusShares { per_share: 0.5 },
+                    applied: false,
+                    cash_received: 0.0,
+                },
+                CorporateAction {
+                    ex_date: date("2024-06-03"),
+                    kind: CorporateActionKind::CashDividend { per_share: 0.3 },
+                    applied: false,
+                    cash_received: 0.0,
+                },
+                CorporateAction {
+                    ex_date: date("2024-12-01"),
+                    kind: CorporateActionKind::Split { ratio: 2.0 },
+                    applied: false,
+                    cash_received: 0.0,
+                },
+            ],
+            ..StockData::new("000001".to_string(), 1000.0, 15.0, 18.3, 1500.0)
+        };
+
+        let applied = StockCalculator::apply_due_corporate_actions(&mut stock_data, date("2024-06-03"));
+        assert_eq!(applied.len(), 2);
+        assert!((applied[0].cash_received - 300.0).abs() < 0.01);
+        assert_eq!(stock_data.quantity, 1500.0);
+        assert!((stock_data.avg_price - 10.0).abs() < 0.001);
+        assert!((stock_data.realized_profit - 300.0).abs() < 0.01);
+        assert!((stock_data.target_profit - 12.0).abs() < 0.001); // (18.3 - 0.3) / 1.5
+        assert!(!stock_data.corporate_actions[2].applied);
+
+        // 除权后价格 (15.3 - 0.3) / 1.5 = 10.0，不应显示虚假亏损
+        let analysis = StockCalculator::calculate_analysis(&stock_data, 10.0);
+        assert!(analysis.current_profit.abs() < 0.01);
+        assert!((analysis.total_profit - 300.0).abs() < 0.01);
+
+        assert!(StockCalculator::apply_due_corporate_actions(&mut stock_data, date("2024-06-04")).is_empty());
+
+        // 百分比止损 10%：除息前 9.2 未触发，每股派 1 元除息到 8.2 后仍不应触发
+        let mut stock_data = StockData {
+            stop_kind: TargetKind::Percent,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 10.0)
+        };
+        let before = StockCalculator::calculate_analysis(&stock_data, 9.2);
+        StockCalculator::apply_corporate_action(&mut stock_data, &CorporateActionKind::CashDividend { per_share: 1.0 });
+        let after = StockCalculator::calculate_analysis(&stock_data, 8.2);
+        assert!(!matches!(after.status, StockStatus::AtStopLoss));
+        assert_eq!(std::mem::discriminant(&before.status), std::mem::discriminant(&after.status));
+        assert!((after.stop_loss_price - 8.0).abs() < 1e-9);
+        assert!((after.total_profit - before.total_profit).abs() < 0.01);
+        assert!((stock_data.realized_profit - 1000.0).abs() < 0.01);
+    }
+
+    fn stock_info(code: &str, current_price: f64, change_amount: f64) -> StockInfo {
+        StockInfo {
+            name: code.to_string(),
+            code: code.to_string(),
+            current_price,
+            yesterday_close: current_price - change_amount,
+            open_price: current_price,
+            volume: 0,
+            turnover: 0.0,
+            high_price: current_price,
+            low_price: current_price,
+            change_amount,
+            change_percent: change_amount / (current_price - change_amount) * 100.0,
+        }
+    }
+
+    #[test]
+    fn test_portfolio_summary() {
+        let holdings = vec![
+            (StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0), stock_info("000001", 12.0, 0.5)),
+            (StockData::new("600000".to_string(), 500.0, 20.0, 5000.0, 1000.0), stock_info("600000", 16.0, -1.0)),
+        ];
+
+        let summary = StockCalculator::calculate_portfolio_summary(&holdings, 0.0, 0.0, &AppConfig::default());
+        assert!((summary.market_value - 20000.0).abs() < 0.01);
+        assert!((summary.cost - 20000.0).abs() < 0.01);
+        assert!(summary.unrealized_profit.abs() < 0.01);
+        assert!((summary.day_change - 0.0).abs() < 0.01); // 500 - 500
+        assert!((summary.positions[0].weight - 60.0).abs() < 0.01);
+        // (12 - 8) * 1000 + (16 - 18 已跌破止损, 计 0)
+        assert!((summary.risk_to_stop - 4000.0).abs() < 0.01);
+        assert!((summary.risk_percent - 20.0).abs() < 0.01);
+
+        // 5000 现金，净入金 24000
+        let summary = StockCalculator::calculate_portfolio_summary(&holdings, 5000.0, 24000.0, &AppConfig::default());
+        assert!((summary.equity - 25000.0).abs() < 0.01);
+        assert!((summary.invested_percent - 80.0).abs() < 0.01);
+        assert!((summary.positions[0].weight - 48.0).abs() < 0.01);
+        assert!((summary.total_return - 1000.0).abs() < 0.01);
+        assert!((summary.total_return_percent - 4.1667).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_buy_and_sell() {
+        let mut stock_data = StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0);
+
+        let cash = StockCalculator::apply_buy(&mut stock_data, 1000.0, 12.0, 10.0).unwrap();
+        assert!((cash + 12010.0).abs() < 0.01);
+        assert_eq!(stock_data.quantity, 2000.0);
+        assert!((stock_data.avg_price - 11.005).abs() < 0.0001);
+
+        let cash = StockCalculator::apply_sell(&mut stock_data, 500.0, 13.0, 5.0).unwrap();
+        assert!((cash - 6495.0).abs() < 0.01);
+        assert_eq!(stock_data.quantity, 1500.0);
+        assert!((stock_data.realized_profit - 992.5).abs() < 0.01); // (13 - 11.005) * 500 - 5
+
+        assert!(StockCalculator::apply_sell(&mut stock_data, 2000.0, 13.0, 0.0).is_err());
+    }
+}
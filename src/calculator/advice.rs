@@ -0,0 +1,243 @@
+use super::StockCalculator;
+use crate::error::{Result, StockCalcError, ValidationError};
+use crate::models::{Advice, AdviceCondition, AdviceOp, AdviceRule, AdviceRuleSet, StockAnalysis, StockInfo};
+
+/// 来自分析结果的字段，波动和概率字段只有附加了对应分析时才有值
+const ANALYSIS_FIELDS: &[&str] = &[
+    "current_price",
+    "avg_price",
+    "quantity",
+    "target_price",
+    "stop_loss_price",
+    "current_profit",
+    "total_profit",
+    "profit_ratio",
+    "distance_to_target",
+    "distance_to_stop_loss",
+    "risk_reward_ratio",
+    "investment_amount",
+    "atr_percent",
+    "daily_volatility",
+    "stop_distance_atr",
+    "target_probability",
+    "stop_probability",
+];
+
+/// 来自实时行情的字段，需要额外获取行情
+const QUOTE_FIELDS: &[&str] = &[
+    "change_percent",
+    "change_amount",
+    "open_price",
+    "yesterday_close",
+    "high_price",
+    "low_price",
+    "volume",
+    "turnover",
+];
+
+impl StockCalculator {
+    /// 检查规则中的字段名是否有效，错误信息中带上规则名和字段名
+    pub fn validate_advice_rules(rules: &AdviceRuleSet) -> Result<()> {
+        for rule in &rules.rules {
+            let unknown = rule
+                .when
+                .iter()
+                .find(|c| !ANALYSIS_FIELDS.contains(&c.field.as_str()) && !QUOTE_FIELDS.contains(&c.field.as_str()));
+
+            if let Some(condition) = unknown {
+                return Err(StockCalcError::ValidationError(format!(
+                    "{}: 规则「{}」使用了未知字段 {}",
+                    ValidationError::InvalidAdviceRule, rule.name, condition.field
+                )));
+            }
+
+            if rule.advice.trim().is_empty() {
+                return Err(StockCalcError::ValidationError(format!(
+                    "{}: 规则「{}」缺少建议内容",
+                    ValidationError::InvalidAdviceRule, rule.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 规则是否用到实时行情字段，决定是否需要额外获取行情
+    pub fn advice_needs_quote(rules: &AdviceRuleSet) -> bool {
+        rules
+            .rules
+            .iter()
+            .flat_map(|rule| &rule.when)
+            .any(|c| QUOTE_FIELDS.contains(&c.field.as_str()))
+    }
+
+    /// 按顺序匹配规则，字段缺失的条件视为不满足；没有普通规则命中时使用 fallback 规则
+    pub fn evaluate_advice(rules: &AdviceRuleSet, analysis: &StockAnalysis, info: Option<&StockInfo>) -> Vec<Advice> {
+        let matches = |conditions: &[AdviceCondition]| {
+            conditions.iter().all(|c| {
+                Self::advice_field(&c.field, analysis, info).is_some_and(|value| match c.op {
+                    AdviceOp::Gt => value > c.value,
+                    AdviceOp::Ge => value >= c.value,
+                    AdviceOp::Lt => value < c.value,
+                    AdviceOp::Le => value <= c.value,
+                })
+            })
+        };
+        let to_advice = |rule: &AdviceRule| Advice {
+            rule: rule.name.clone(),
+            text: rule.advice.clone(),
+            severity: rule.severity,
+        };
+
+        let mut advice = Vec::new();
+        for rule in rules.rules.iter().filter(|rule| !rule.fallback) {
+            if matches(&rule.when) {
+                advice.push(to_advice(rule));
+                if rule.stop {
+                    break;
+                }
+            }
+        }
+
+        if advice.is_empty() {
+            advice.extend(
+                rules
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.fallback && matches(&rule.when))
+                    .take(1)
+                    .map(to_advice),
+            );
+        }
+
+        advice
+    }
+
+    fn advice_field(field: &str, analysis: &StockAnalysis, info: Option<&StockInfo>) -> Option<f64> {
+        let volatility = analysis.volatility.as_ref();
+        let probability = analysis.probability.as_ref();
+
+        match field {
+            "current_price" => Some(analysis.current_price),
+            "avg_price" => Some(analysis.avg_price),
+            "quantity" => Some(analysis.quantity),
+            "target_price" => Some(analysis.target_price),
+            "stop_loss_price" => Some(analysis.stop_loss_price),
+            "current_profit" => Some(analysis.current_profit),
+            "total_profit" => Some(analysis.total_profit),
+            "profit_ratio" => Some(analysis.profit_ratio),
+            "distance_to_target" => Some(analysis.distance_to_target),
+            "distance_to_stop_loss" => Some(analysis.distance_to_stop_loss),
+            "risk_reward_ratio" => Some(analysis.risk_reward_ratio),
+            "investment_amount" => Some(analysis.investment_amount),
+            "atr_percent" => volatility.map(|v| v.atr_percent),
+            "daily_volatility" => volatility.map(|v| v.daily_volatility),
+            "stop_distance_atr" => volatility.map(|v| v.stop_distance_atr),
+            "target_probability" => probability.map(|p| p.target_probability),
+            "stop_probability" => probability.map(|p| p.stop_probability),
+            "change_percent" => info.map(|i| i.change_percent),
+            "change_amount" => info.map(|i| i.change_amount),
+            "open_price" => info.map(|i| i.open_price),
+            "yesterday_close" => info.map(|i| i.yesterday_close),
+            "high_price" => info.map(|i| i.high_price),
+            "low_price" => info.map(|i| i.low_price),
+            "volume" => info.map(|i| i.volume as f64),
+            "turnover" => info.map(|i| i.turnover),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AdviceSeverity, StockData};
+
+    fn info(change_percent: f64) -> StockInfo {
+        StockInfo {
+            name: "平安银行".to_string(),
+            code: "000001".to_string(),
+            current_price: 12.0,
+            yesterday_close: 12.0,
+            open_price: 12.0,
+            volume: 0,
+            turnover: 0.0,
+            high_price: 12.0,
+            low_price: 12.0,
+            change_amount: 0.0,
+            change_percent,
+        }
+    }
+
+    #[test]
+    fn test_default_advice_rules() {
+        let rules = AdviceRuleSet::default();
+        assert!(StockCalculator::validate_advice_rules(&rules).is_ok());
+        assert!(!StockCalculator::advice_needs_quote(&rules));
+
+        let stock = StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0);
+        let texts = |price: f64| -> Vec<String> {
+            let analysis = StockCalculator::calculate_analysis(&stock, price);
+            StockCalculator::evaluate_advice(&rules, &analysis, None)
+                .into_iter()
+                .map(|a| a.text)
+                .collect()
+        };
+
+        assert_eq!(texts(12.5), vec!["考虑分批卖出"]);
+        assert_eq!(texts(8.5), vec!["考虑止损或补仓"]);
+        assert_eq!(texts(10.5), vec!["继续持有观察"]);
+    }
+
+    #[test]
+    fn test_custom_advice_rules() {
+        let rules: AdviceRuleSet = toml::from_str(r#"
+            [[rules]]
+            name = "急跌"
+            when = [
+                { field = "change_percent", op = "<=", value = -5.0 },
+                { field = "distance_to_stop_loss", op = "<", value = 10.0 },
+            ]
+            advice = "急跌逼近止损，准备离场"
+            severity = "critical"
+            stop = true
+
+            [[rules]]
+            name = "浮亏"
+            when = [{ field = "profit_ratio", op = "<", value = 0.0 }]
+            advice = "浮亏中，不要加仓"
+            severity = "warning"
+        "#).unwrap();
+        assert!(StockCalculator::validate_advice_rules(&rules).is_ok());
+        assert!(StockCalculator::advice_needs_quote(&rules));
+
+        // 止损价 8，当前价 8.5 距止损约 5.9%
+        let stock = StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0);
+        let analysis = StockCalculator::calculate_analysis(&stock, 8.5);
+
+        let advice = StockCalculator::evaluate_advice(&rules, &analysis, Some(&info(-6.0)));
+        assert_eq!(advice.len(), 1);
+        assert_eq!(advice[0].severity, AdviceSeverity::Critical);
+
+        // 没有行情时行情条件不满足
+        let advice = StockCalculator::evaluate_advice(&rules, &analysis, None);
+        assert_eq!(advice.len(), 1);
+        assert_eq!(advice[0].rule, "浮亏");
+
+        // 盈利且没有 fallback 规则时无建议
+        let analysis = StockCalculator::calculate_analysis(&stock, 11.0);
+        assert!(StockCalculator::evaluate_advice(&rules, &analysis, None).is_empty());
+
+        let invalid = AdviceRuleSet {
+            rules: vec![AdviceRule {
+                name: "拼写错误".to_string(),
+                when: vec![AdviceCondition { field: "profit".to_string(), op: AdviceOp::Gt, value: 0.0 }],
+                advice: "-".to_string(),
+                severity: AdviceSeverity::Info,
+                fallback: false,
+                stop: false,
+            }],
+        };
+        assert!(StockCalculator::validate_advice_rules(&invalid).is_err());
+    }
+}
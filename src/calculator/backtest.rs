@@ -0,0 +1,220 @@
+use super::StockCalculator;
+use crate::error::{Result, StockCalcError, ValidationError};
+use crate::models::{
+    BacktestExitKind, BacktestFill, BacktestResult, Candle, StockData, StockStatus, TierSide,
+};
+use chrono::NaiveDate;
+
+impl StockCalculator {
+    /// 从买入日的下一根K线开始逐日回放止盈/止损规则，状态判断直接使用 calculate_analysis，
+    /// 与实时提醒保持一致。每根K线先用最低价检验止损、再用最高价检验止盈（保守假设），
+    /// 跳空越过触发价时按开盘价成交
+    pub fn backtest(stock_data: &StockData, entry_date: NaiveDate, candles: &[Candle]) -> Result<BacktestResult> {
+        Self::validate_stock_data(stock_data)?;
+
+        let replay: Vec<&Candle> = candles.iter().filter(|c| c.date > entry_date).collect();
+        if replay.is_empty() {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InsufficientHistory.to_string()
+            ));
+        }
+
+        let mut stock = stock_data.clone();
+        let entry_price = stock.avg_price;
+        let initial = Self::calculate_analysis(&stock, entry_price);
+        let fixed_stop_price = Self::resolve_stop_loss_price(&stock);
+
+        let mut remaining = stock.quantity;
+        let mut fills = Vec::new();
+        let mut trading_days = 0;
+        let (mut high, mut low) = (entry_price, entry_price);
+
+        'days: for candle in &replay {
+            trading_days += 1;
+            high = high.max(candle.high);
+            low = low.min(candle.low);
+
+            for price in [candle.low, candle.high] {
+                loop {
+                    let analysis = Self::calculate_analysis(&stock, price);
+                    let (kind, level, quantity) = match analysis.status {
+                        StockStatus::AtStopLoss => {
+                            let kind = match analysis.trailing_stop_price {
+                                Some(trailing) if trailing > fixed_stop_price => BacktestExitKind::TrailingStop,
+                                _ => BacktestExitKind::StopLoss,
+                            };
+                            (kind, analysis.stop_loss_price, remaining)
+                        }
+                        StockStatus::AtTarget => (BacktestExitKind::Target, analysis.target_price, remaining),
+                        StockStatus::AtTakeProfitTier(tier) | StockStatus::AtStopLossTier(tier) => {
+                            let side = match analysis.status {
+                                StockStatus::AtTakeProfitTier(_) => TierSide::TakeProfit,
+                                _ => TierSide::StopLoss,
+                            };
+                            let tier_analysis = analysis
+                                .tiers
+                                .iter()
+                                .find(|t| t.side == side && t.tier == tier)
+                                .expect("tier status refers to an existing tier");
+
+                            let (tiers, kind) = match side {
+                                TierSide::TakeProfit => (&mut stock.take_profit_tiers, BacktestExitKind::TakeProfitTier(tier)),
+                                TierSide::StopLoss => (&mut stock.stop_loss_tiers, BacktestExitKind::StopLossTier(tier)),
+                            };
+                            tiers[tier - 1].triggered = true;
+
+                            (kind, tier_analysis.price, tier_analysis.quantity.min(remaining))
+                        }
+                        _ => break,
+                    };
+
+                    let downward = matches!(
+                        kind,
+                        BacktestExitKind::StopLoss | BacktestExitKind::TrailingStop | BacktestExitKind::StopLossTier(_)
+                    );
+                    let fill_price = match downward {
+                        true if candle.open <= level => candle.open,
+                        false if candle.open >= level => candle.open,
+                        _ => level,
+                    };
+
+                    fills.push(BacktestFill { date: candle.date, price: fill_price, quantity, kind });
+                    remaining -= quantity;
+
+                    if remaining <= 1e-9 {
+                        break 'days;
+                    }
+                }
+            }
+
+            // 收盘后才用当日最高价更新移动止损，避免使用未来数据
+            Self::update_trailing_stop(&mut stock, candle.high);
+        }
+
+        let last = replay[trading_days - 1];
+        if remaining > 1e-9 {
+            fills.push(BacktestFill {
+                date: last.date,
+                price: last.close,
+                quantity: remaining,
+                kind: BacktestExitKind::EndOfData,
+            });
+        }
+
+        let profit = fills.iter().fold(0.0, |acc, f| acc + (f.price - entry_price) * f.quantity);
+        let exit_date = last.date;
+
+        Ok(BacktestResult {
+            code: stock.code.clone(),
+            entry_date,
+            entry_price,
+            quantity: stock.quantity,
+            target_price: initial.target_price,
+            stop_loss_price: initial.stop_loss_price,
+            fills,
+            exit_date,
+            holding_days: (exit_date - entry_date).num_days(),
+            trading_days,
+            profit,
+            profit_ratio: profit / (entry_price * stock.quantity) * 100.0,
+            max_gain: (high / entry_price - 1.0) * 100.0,
+            max_drawdown: (low / entry_price - 1.0) * 100.0,
+        })
+    }
+
+    pub fn format_backtest_exit(kind: BacktestExitKind) -> String {
+        match kind {
+            BacktestExitKind::Target => "达到目标".to_string(),
+            BacktestExitKind::StopLoss => "触发止损".to_string(),
+            BacktestExitKind::TrailingStop => "触发移动止损".to_string(),
+            BacktestExitKind::TakeProfitTier(tier) => format!("第{}档止盈", tier),
+            BacktestExitKind::StopLossTier(tier) => format!("第{}档止损", tier),
+            BacktestExitKind::EndOfData => "未触发（按最后收盘价估值）".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ExitTier, TargetKind, TrailingKind, TrailingStop};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn candle(day: u32, open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { date: date(day), open, close, high, low, volume: 0.0 }
+    }
+
+    fn stock() -> StockData {
+        StockData {
+            target_kind: TargetKind::Percent,
+            stop_kind: TargetKind::Percent,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 10.0, 5.0)
+        }
+    }
+
+    #[test]
+    fn test_backtest_fixed_rules() {
+        let candles = vec![
+            candle(1, 10.0, 10.0, 10.0, 10.0),
+            candle(2, 10.1, 10.5, 9.8, 10.2),
+            candle(3, 10.3, 11.2, 10.1, 11.1),
+        ];
+        let result = StockCalculator::backtest(&stock(), date(1), &candles).unwrap();
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(result.fills[0].kind, BacktestExitKind::Target);
+        assert!((result.fills[0].price - 11.0).abs() < 1e-9);
+        assert_eq!(result.trading_days, 2);
+        assert_eq!(result.holding_days, 2);
+        assert!((result.profit - 1000.0).abs() < 1e-6);
+
+        // 跳空低开越过止损价，按开盘价成交
+        let candles = vec![candle(2, 9.0, 9.2, 8.9, 9.1)];
+        let result = StockCalculator::backtest(&stock(), date(1), &candles).unwrap();
+        assert_eq!(result.fills[0].kind, BacktestExitKind::StopLoss);
+        assert!((result.fills[0].price - 9.0).abs() < 1e-9);
+
+        // 未触发时按最后收盘价估值
+        let candles = vec![candle(2, 10.0, 10.3, 9.9, 10.2)];
+        let result = StockCalculator::backtest(&stock(), date(1), &candles).unwrap();
+        assert_eq!(result.fills[0].kind, BacktestExitKind::EndOfData);
+        assert!((result.profit - 200.0).abs() < 1e-6);
+
+        assert!(StockCalculator::backtest(&stock(), date(5), &candles).is_err());
+    }
+
+    #[test]
+    fn test_backtest_trailing_and_tiers() {
+        let mut trailing = stock();
+        trailing.target_profit = 50.0;
+        trailing.trailing_stop = Some(TrailingStop {
+            kind: TrailingKind::Percent,
+            distance: 5.0,
+            activation_profit: None,
+            high_water_mark: None,
+        });
+        let candles = vec![
+            candle(2, 10.5, 12.0, 10.4, 11.8),
+            candle(3, 11.8, 11.9, 11.3, 11.5),
+        ];
+        let result = StockCalculator::backtest(&trailing, date(1), &candles).unwrap();
+        assert_eq!(result.fills[0].kind, BacktestExitKind::TrailingStop);
+        assert_eq!(result.fills[0].date, date(3));
+        assert!((result.fills[0].price - 11.4).abs() < 1e-9);
+
+        let mut tiered = stock();
+        tiered.take_profit_tiers = vec![ExitTier { fraction: 0.5, value: 5.0, kind: TargetKind::Percent, triggered: false }];
+        let candles = vec![
+            candle(2, 10.2, 10.6, 10.1, 10.4),
+            candle(3, 10.5, 11.1, 10.4, 11.0),
+        ];
+        let result = StockCalculator::backtest(&tiered, date(1), &candles).unwrap();
+        assert_eq!(result.fills.len(), 2);
+        assert_eq!(result.fills[0].kind, BacktestExitKind::TakeProfitTier(1));
+        assert!((result.fills[0].quantity - 500.0).abs() < 1e-9);
+        assert_eq!(result.fills[1].kind, BacktestExitKind::Target);
+        assert!((result.profit - (0.5 * 500.0 + 1.0 * 500.0)).abs() < 1e-6);
+    }
+}
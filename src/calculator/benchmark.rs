@@ -0,0 +1,125 @@
+use super::StockCalculator;
+use crate::models::{BenchmarkComparison, Candle};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+
+/// 年化使用的交易日数
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+impl StockCalculator {
+    pub fn candle_closes(candles: &[Candle]) -> Vec<(NaiveDate, f64)> {
+        candles.iter().map(|candle| (candle.date, candle.close)).collect()
+    }
+
+    /// 按当前持仓数量合成组合市值序列，只保留所有持仓都有收盘价的交易日
+    pub fn combine_value_series(holdings: &[(f64, Vec<(NaiveDate, f64)>)]) -> Vec<(NaiveDate, f64)> {
+        let mut values: BTreeMap<NaiveDate, (usize, f64)> = BTreeMap::new();
+
+        for (quantity, series) in holdings {
+            for (date, close) in series {
+                let entry = values.entry(*date).or_insert((0, 0.0));
+                entry.0 += 1;
+                entry.1 += quantity * close;
+            }
+        }
+
+        values
+            .into_iter()
+            .filter(|(_, (count, _))| *count == holdings.len())
+            .map(|(date, (_, value))| (date, value))
+            .collect()
+    }
+
+    /// 在共同交易日上比较序列与基准的区间收益、beta 和年化 alpha
+    pub fn calculate_benchmark_comparison(
+        series: &[(NaiveDate, f64)],
+        benchmark: &[(NaiveDate, f64)],
+    ) -> Option<BenchmarkComparison> {
+        let benchmark: HashMap<NaiveDate, f64> = benchmark.iter().copied().collect();
+        let aligned: Vec<(NaiveDate, f64, f64)> = series
+            .iter()
+            .filter(|(_, value)| *value > 0.0)
+            .filter_map(|(date, value)| benchmark.get(date).map(|b| (*date, *value, *b)))
+            .filter(|(_, _, b)| *b > 0.0)
+            .collect();
+
+        if aligned.len() < 2 {
+            return None;
+        }
+
+        let returns: Vec<(f64, f64)> = aligned
+            .windows(2)
+            .map(|pair| (pair[1].1 / pair[0].1 - 1.0, pair[1].2 / pair[0].2 - 1.0))
+            .collect();
+
+        let n = returns.len() as f64;
+        let mean = returns.iter().fold(0.0, |acc, (r, _)| acc + r) / n;
+        let mean_benchmark = returns.iter().fold(0.0, |acc, (_, b)| acc + b) / n;
+        let covariance = returns
+            .iter()
+            .fold(0.0, |acc, (r, b)| acc + (r - mean) * (b - mean_benchmark)) / n;
+        let variance = returns
+            .iter()
+            .fold(0.0, |acc, (_, b)| acc + (b - mean_benchmark).powi(2)) / n;
+        let beta = if variance > 0.0 { covariance / variance } else { 0.0 };
+
+        let (first, last) = (aligned[0], aligned[aligned.len() - 1]);
+        let period_return = (last.1 / first.1 - 1.0) * 100.0;
+        let benchmark_return = (last.2 / first.2 - 1.0) * 100.0;
+
+        Some(BenchmarkComparison {
+            start: first.0,
+            end: last.0,
+            trading_days: returns.len(),
+            period_return,
+            benchmark_return,
+            excess_return: period_return - benchmark_return,
+            beta,
+            alpha: (mean - beta * mean_benchmark) * TRADING_DAYS_PER_YEAR * 100.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::tests::series;
+
+    #[test]
+    fn test_benchmark_comparison() {
+        let benchmark = series(&[100.0, 101.0, 99.0, 102.0, 103.0]);
+
+        // 与基准完全同步：beta=1, alpha=0
+        let same = StockCalculator::calculate_benchmark_comparison(&benchmark, &benchmark).unwrap();
+        assert!((same.beta - 1.0).abs() < 1e-9);
+        assert!(same.alpha.abs() < 1e-9);
+        assert!(same.excess_return.abs() < 1e-9);
+        assert_eq!(same.trading_days, 4);
+
+        // 两倍杠杆的日收益：beta=2
+        let mut value = 10.0;
+        let mut leveraged = vec![(benchmark[0].0, value)];
+        for pair in benchmark.windows(2) {
+            value *= 1.0 + 2.0 * (pair[1].1 / pair[0].1 - 1.0);
+            leveraged.push((pair[1].0, value));
+        }
+        let comparison = StockCalculator::calculate_benchmark_comparison(&leveraged, &benchmark).unwrap();
+        assert!((comparison.beta - 2.0).abs() < 1e-9);
+        assert!((comparison.benchmark_return - 3.0).abs() < 1e-9);
+
+        // 没有共同交易日
+        assert!(StockCalculator::calculate_benchmark_comparison(&series(&[1.0]), &benchmark).is_none());
+    }
+
+    #[test]
+    fn test_combine_value_series() {
+        let a = series(&[10.0, 11.0, 12.0]);
+        let mut b = series(&[5.0, 6.0, 7.0]);
+        b.remove(1);
+
+        let combined = StockCalculator::combine_value_series(&[(100.0, a), (200.0, b)]);
+        assert_eq!(combined.len(), 2);
+        assert!((combined[0].1 - 2000.0).abs() < 1e-9);
+        assert!((combined[1].1 - 2600.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,149 @@
+use super::StockCalculator;
+use crate::config::CorrelationConfig;
+use crate::models::{CorrelatedPair, CorrelationMatrix, PositionDirection};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// 参与相关性计算的持仓：代码、方向和收盘价序列
+type HoldingSeries = (String, PositionDirection, Vec<(NaiveDate, f64)>);
+
+impl StockCalculator {
+    /// 两个收盘价序列在共同交易日上的日收益率相关系数，返回相关系数和收益率个数。
+    /// 任一序列收益率没有波动时无法计算
+    pub fn return_correlation(first: &[(NaiveDate, f64)], second: &[(NaiveDate, f64)]) -> Option<(f64, usize)> {
+        let second: HashMap<NaiveDate, f64> = second.iter().copied().collect();
+        let aligned: Vec<(f64, f64)> = first
+            .iter()
+            .filter(|(_, close)| *close > 0.0)
+            .filter_map(|(date, close)| second.get(date).map(|other| (*close, *other)))
+            .filter(|(_, other)| *other > 0.0)
+            .collect();
+
+        if aligned.len() < 3 {
+            return None;
+        }
+
+        let returns: Vec<(f64, f64)> = aligned
+            .windows(2)
+            .map(|pair| (pair[1].0 / pair[0].0 - 1.0, pair[1].1 / pair[0].1 - 1.0))
+            .collect();
+
+        let n = returns.len() as f64;
+        let mean_first = returns.iter().fold(0.0, |acc, (a, _)| acc + a) / n;
+        let mean_second = returns.iter().fold(0.0, |acc, (_, b)| acc + b) / n;
+        let (covariance, variance_first, variance_second) = returns.iter().fold((0.0, 0.0, 0.0), |acc, (a, b)| {
+            let (da, db) = (a - mean_first, b - mean_second);
+            (acc.0 + da * db, acc.1 + da * da, acc.2 + db * db)
+        });
+
+        if variance_first <= 0.0 || variance_second <= 0.0 {
+            return None;
+        }
+
+        Some((covariance / (variance_first * variance_second).sqrt(), returns.len()))
+    }
+
+    /// 所有持仓两两之间的相关系数矩阵，并找出高度相关的组合。
+    /// 融券持仓的收益与价格反向，与多头持仓的相关系数取反
+    pub fn calculate_correlation_matrix(
+        series: &[HoldingSeries],
+        config: &CorrelationConfig,
+    ) -> CorrelationMatrix {
+        let n = series.len();
+        let mut values = vec![vec![None; n]; n];
+        let mut high_pairs = Vec::new();
+
+        for i in 0..n {
+            values[i][i] = Some(1.0);
+            for j in (i + 1)..n {
+                let sign = series[i].1.sign() * series[j].1.sign();
+                let correlation = Self::return_correlation(&series[i].2, &series[j].2)
+                    .filter(|(_, observations)| *observations >= config.min_observations)
+                    .map(|(correlation, observations)| (sign * correlation, observations));
+
+                if let Some((correlation, observations)) = correlation {
+                    values[i][j] = Some(correlation);
+                    values[j][i] = Some(correlation);
+
+                    if correlation >= config.high_threshold {
+                        high_pairs.push(CorrelatedPair {
+                            first: series[i].0.clone(),
+                            second: series[j].0.clone(),
+                            correlation,
+                            observations,
+                        });
+                    }
+                }
+            }
+        }
+        high_pairs.sort_by(|a, b| b.correlation.total_cmp(&a.correlation));
+
+        CorrelationMatrix {
+            codes: series.iter().map(|(code, _, _)| code.clone()).collect(),
+            values,
+            threshold: config.high_threshold,
+            high_pairs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::tests::series;
+
+    #[test]
+    fn test_return_correlation() {
+        let base = series(&[10.0, 10.5, 10.2, 10.8, 10.6, 11.0]);
+        // 每日涨跌幅相同的序列完全正相关
+        let scaled = series(&[20.0, 21.0, 20.4, 21.6, 21.2, 22.0]);
+        let (correlation, observations) = StockCalculator::return_correlation(&base, &scaled).unwrap();
+        assert!((correlation - 1.0).abs() < 1e-9);
+        assert_eq!(observations, 5);
+
+        // 涨跌方向相反的序列负相关
+        let opposite = series(&[10.0, 9.5, 9.8, 9.2, 9.4, 9.0]);
+        let (correlation, _) = StockCalculator::return_correlation(&base, &opposite).unwrap();
+        assert!(correlation < -0.9);
+
+        // 只用共同交易日，价格不变的序列无法计算
+        assert!(StockCalculator::return_correlation(&base, &series(&[5.0; 6])).is_none());
+        assert!(StockCalculator::return_correlation(&base, &base[4..]).is_none());
+    }
+
+    #[test]
+    fn test_correlation_matrix() {
+        let config = CorrelationConfig {
+            min_observations: 3,
+            ..CorrelationConfig::default()
+        };
+        let long = PositionDirection::Long;
+        let mut holdings = vec![
+            ("000001".to_string(), long, series(&[10.0, 10.5, 10.2, 10.8, 10.6, 11.0])),
+            ("600036".to_string(), long, series(&[20.0, 21.0, 20.4, 21.6, 21.2, 22.0])),
+            ("sh513500".to_string(), long, series(&[2.0, 1.98, 2.03, 2.01, 2.05, 2.04])),
+            ("600519".to_string(), long, series(&[100.0, 101.0])),
+        ];
+
+        let matrix = StockCalculator::calculate_correlation_matrix(&holdings, &config);
+        assert_eq!(matrix.codes.len(), 4);
+        assert_eq!(matrix.values[2][2], Some(1.0));
+        assert_eq!(matrix.values[0][1], matrix.values[1][0]);
+        assert!(matrix.values[0][3].is_none());
+
+        assert_eq!(matrix.high_pairs.len(), 1);
+        assert_eq!(matrix.high_pairs[0].first, "000001");
+        assert_eq!(matrix.high_pairs[0].second, "600036");
+
+        // 共同收益率不足 min_observations 时不计算
+        let strict = CorrelationConfig { min_observations: 10, ..config.clone() };
+        let matrix = StockCalculator::calculate_correlation_matrix(&holdings, &strict);
+        assert!(matrix.values[0][1].is_none() && matrix.high_pairs.is_empty());
+
+        // 融券 600036 对冲 000001，相关系数为负，不算作高度相关
+        holdings[1].1 = PositionDirection::Short;
+        let matrix = StockCalculator::calculate_correlation_matrix(&holdings, &config);
+        assert!((matrix.values[0][1].unwrap() + 1.0).abs() < 1e-9);
+        assert!(!matrix.high_pairs.iter().any(|pair| pair.first == "000001" && pair.second == "600036"));
+    }
+}
@@ -0,0 +1,237 @@
+use super::StockCalculator;
+use crate::error::{Result, StockCalcError, ValidationError};
+use crate::models::{Candle, DcaFrequency, DcaPlan, DcaProjection, DcaPurchase};
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+
+impl StockCalculator {
+    pub fn validate_dca_plan(plan: &DcaPlan) -> Result<()> {
+        if plan.amount <= 0.0 || plan.lot_size <= 0.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidDcaPlan.to_string()
+            ));
+        }
+
+        if plan.target_percent <= 0.0 || plan.stop_percent <= 0.0 || plan.stop_percent >= 100.0 {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidNumber.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 从开始日期起的计划日期，按日定投只在工作日，按月定投遇到月末自动顺延到当月最后一天
+    pub fn dca_dates(plan: &DcaPlan) -> impl Iterator<Item = NaiveDate> {
+        let start = plan.start_date;
+        let frequency = plan.frequency;
+
+        (0u32..)
+            .map_while(move |k| match frequency {
+                DcaFrequency::Daily => start.checked_add_signed(Duration::days(k as i64)),
+                DcaFrequency::Weekly => start.checked_add_signed(Duration::weeks(k as i64)),
+                DcaFrequency::Biweekly => start.checked_add_signed(Duration::weeks(2 * k as i64)),
+                DcaFrequency::Monthly => start.checked_add_months(Months::new(k)),
+            })
+            .filter(move |date| frequency != DcaFrequency::Daily || !Self::is_weekend(*date))
+    }
+
+    /// 截至 today 最近一个尚未执行的计划日期，不考虑周末
+    pub fn pending_dca_date(plan: &DcaPlan, today: NaiveDate) -> Option<NaiveDate> {
+        Self::dca_dates(plan)
+            .take_while(|date| *date <= today)
+            .last()
+            .filter(|date| plan.last_executed.map_or(true, |executed| *date > executed))
+    }
+
+    /// 需要提醒执行的计划日期，周末不提醒，顺延到下一个工作日
+    pub fn due_dca_date(plan: &DcaPlan, today: NaiveDate) -> Option<NaiveDate> {
+        if Self::is_weekend(today) {
+            return None;
+        }
+        Self::pending_dca_date(plan, today)
+    }
+
+    /// 最近一次执行之后、待执行的一期之前被跳过的期数。
+    /// 只提醒最近一期，更早的计划日期不会补买
+    pub fn missed_dca_periods(plan: &DcaPlan, today: NaiveDate) -> usize {
+        let pending = match Self::pending_dca_date(plan, today) {
+            Some(pending) => pending,
+            None => return 0,
+        };
+
+        Self::dca_dates(plan)
+            .take_while(|date| *date < pending)
+            .filter(|date| plan.last_executed.map_or(true, |executed| *date > executed))
+            .count()
+    }
+
+    pub fn next_dca_date(plan: &DcaPlan, after: NaiveDate) -> Option<NaiveDate> {
+        Self::dca_dates(plan).find(|date| *date > after)
+    }
+
+    /// 按每手股数向下取整的买入数量
+    pub fn dca_quantity(amount: f64, price: f64, lot_size: f64) -> f64 {
+        if price <= 0.0 || lot_size <= 0.0 {
+            return 0.0;
+        }
+        (amount / price / lot_size).floor() * lot_size
+    }
+
+    /// 按历史收盘价模拟定投：每个计划日期在当天或之后第一个交易日买入，不计手续费
+    pub fn project_dca(plan: &DcaPlan, candles: &[Candle]) -> Result<DcaProjection> {
+        Self::validate_dca_plan(plan)?;
+
+        let last = candles
+            .iter()
+            .rfind(|c| c.date >= plan.start_date)
+            .ok_or_else(|| StockCalcError::ValidationError(
+                ValidationError::InsufficientHistory.to_string()
+            ))?;
+
+        let mut purchases: Vec<DcaPurchase> = Vec::new();
+        for scheduled_date in Self::dca_dates(plan).take_while(|date| *date <= last.date) {
+            let candle = match candles.iter().find(|c| c.date >= scheduled_date) {
+                Some(candle) => candle,
+                None => break,
+            };
+
+            // 节假日顺延后与下一期落在同一交易日时只买一次
+            if purchases.last().is_some_and(|p| p.date == candle.date) {
+                continue;
+            }
+
+            let quantity = Self::dca_quantity(plan.amount, candle.close, plan.lot_size);
+            if quantity > 0.0 {
+                purchases.push(DcaPurchase {
+                    scheduled_date,
+                    date: candle.date,
+                    price: candle.close,
+                    quantity,
+                    amount: quantity * candle.close,
+                });
+            }
+        }
+
+        if purchases.is_empty() {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InsufficientHistory.to_string()
+            ));
+        }
+
+        let total_invested = purchases.iter().fold(0.0, |acc, p| acc + p.amount);
+        let total_quantity = purchases.iter().fold(0.0, |acc, p| acc + p.quantity);
+        let market_value = total_quantity * last.close;
+        let profit = market_value - total_invested;
+
+        let mut flows: Vec<(NaiveDate, f64)> = purchases.iter().map(|p| (p.date, -p.amount)).collect();
+        flows.push((last.date, market_value));
+
+        Ok(DcaProjection {
+            purchases,
+            total_invested,
+            total_quantity,
+            avg_cost: total_invested / total_quantity,
+            final_date: last.date,
+            final_price: last.close,
+            market_value,
+            profit,
+            profit_ratio: profit / total_invested * 100.0,
+            xirr: Self::xirr(&flows),
+        })
+    }
+
+    pub fn format_dca_frequency(frequency: DcaFrequency) -> &'static str {
+        match frequency {
+            DcaFrequency::Daily => "每个交易日",
+            DcaFrequency::Weekly => "每周",
+            DcaFrequency::Biweekly => "每两周",
+            DcaFrequency::Monthly => "每月",
+        }
+    }
+
+    fn is_weekend(date: NaiveDate) -> bool {
+        matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn plan(frequency: DcaFrequency, start_date: NaiveDate) -> DcaPlan {
+        DcaPlan {
+            code: "sh510300".to_string(),
+            amount: 1000.0,
+            frequency,
+            start_date,
+            lot_size: 100.0,
+            target_percent: 20.0,
+            stop_percent: 10.0,
+            last_executed: None,
+        }
+    }
+
+    #[test]
+    fn test_dca_schedule() {
+        let monthly = plan(DcaFrequency::Monthly, date(1, 31));
+        let dates: Vec<NaiveDate> = StockCalculator::dca_dates(&monthly).take(3).collect();
+        assert_eq!(dates, vec![date(1, 31), date(2, 29), date(3, 31)]);
+
+        // 2024-01-05 是周五，按日定投跳过周末
+        let daily = plan(DcaFrequency::Daily, date(1, 5));
+        let dates: Vec<NaiveDate> = StockCalculator::dca_dates(&daily).take(2).collect();
+        assert_eq!(dates, vec![date(1, 5), date(1, 8)]);
+
+        let mut weekly = plan(DcaFrequency::Weekly, date(1, 6));
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(1, 5)), None);
+        // 计划日是周六，周一提醒
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(1, 6)), None);
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(1, 8)), Some(date(1, 6)));
+        weekly.last_executed = Some(date(1, 6));
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(1, 9)), None);
+        assert_eq!(StockCalculator::next_dca_date(&weekly, date(1, 9)), Some(date(1, 13)));
+
+        // 1/13、1/20、1/27 三期未执行，只提醒最近一期，前两期算作错过
+        assert_eq!(StockCalculator::missed_dca_periods(&weekly, date(1, 9)), 0);
+        assert_eq!(StockCalculator::missed_dca_periods(&weekly, date(1, 15)), 0);
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(1, 29)), Some(date(1, 27)));
+        assert_eq!(StockCalculator::missed_dca_periods(&weekly, date(1, 29)), 2);
+
+        // 2/3 是周六，不提醒，但这一期仍待执行，之前三期算作错过
+        assert_eq!(StockCalculator::due_dca_date(&weekly, date(2, 3)), None);
+        assert_eq!(StockCalculator::pending_dca_date(&weekly, date(2, 3)), Some(date(2, 3)));
+        assert_eq!(StockCalculator::pending_dca_date(&weekly, date(2, 4)), Some(date(2, 3)));
+        assert_eq!(StockCalculator::missed_dca_periods(&weekly, date(2, 4)), 3);
+    }
+
+    #[test]
+    fn test_project_dca() {
+        let candles: Vec<Candle> = [(1, 2, 1.0), (1, 3, 1.2), (2, 1, 0.8), (3, 1, 1.0), (3, 4, 1.1)]
+            .iter()
+            .map(|(month, day, close)| Candle {
+                date: date(*month, *day),
+                open: *close,
+                close: *close,
+                high: *close,
+                low: *close,
+                volume: 0.0,
+            })
+            .collect();
+
+        let projection = StockCalculator::project_dca(&plan(DcaFrequency::Monthly, date(1, 2)), &candles).unwrap();
+        // 1/2 @1.0 买 1000 股，2/2 顺延到 3/1 @1.0 买 1000 股，3/2 与 3/1 不同日，顺延到 3/4 @1.1 买 900 股
+        assert_eq!(projection.purchases.len(), 3);
+        assert_eq!(projection.purchases[1].date, date(3, 1));
+        assert!((projection.purchases[2].quantity - 900.0).abs() < 1e-9);
+        assert!((projection.total_invested - 2990.0).abs() < 1e-9);
+        assert!((projection.market_value - 2900.0 * 1.1).abs() < 1e-9);
+        assert!(projection.profit > 0.0);
+        assert!(projection.xirr.is_some());
+
+        assert!(StockCalculator::project_dca(&plan(DcaFrequency::Monthly, date(4, 1)), &candles).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use super::StockCalculator;
+use crate::config::ConcentrationConfig;
+use crate::models::{
+    ConcentrationKind, ConcentrationWarning, ExposureReport, PortfolioSummary, PositionExposure, SectorExposure, SectorMap,
+};
+use std::collections::HashMap;
+
+/// 映射文件和行情接口都没有分类时使用的行业名称
+pub const UNCLASSIFIED_SECTOR: &str = "未分类";
+
+impl StockCalculator {
+    /// 在映射中查找股票所属行业，先按原代码查找，再按去掉市场前缀的代码查找
+    pub fn lookup_sector<'a>(map: &'a SectorMap, code: &str) -> Option<&'a str> {
+        let lower = code.to_lowercase();
+        let bare = ["sh", "sz", "bj"]
+            .iter()
+            .find_map(|prefix| lower.strip_prefix(prefix))
+            .unwrap_or(&lower);
+
+        map.sectors
+            .get(code)
+            .or_else(|| map.sectors.get(bare))
+            .map(String::as_str)
+    }
+
+    /// 按行业汇总权重，统计前N大持仓的合计权重，并检查单只股票和单个行业的上限。
+    /// 融券持仓的权重为负，集中度按权重绝对值计算
+    pub fn calculate_exposure(
+        summary: &PortfolioSummary,
+        sectors: &HashMap<String, String>,
+        config: &ConcentrationConfig,
+    ) -> ExposureReport {
+        let mut positions: Vec<PositionExposure> = summary
+            .positions
+            .iter()
+            .map(|position| PositionExposure {
+                code: position.analysis.code.clone(),
+                name: position.name.clone(),
+                sector: sectors
+                    .get(&position.analysis.code)
+                    .cloned()
+                    .unwrap_or_else(|| UNCLASSIFIED_SECTOR.to_string()),
+                market_value: position.market_value,
+                weight: position.weight,
+            })
+            .collect();
+        positions.sort_by(|a, b| b.weight.abs().total_cmp(&a.weight.abs()));
+
+        let mut by_sector: Vec<SectorExposure> = Vec::new();
+        for position in &positions {
+            match by_sector.iter_mut().find(|s| s.sector == position.sector) {
+                Some(sector) => {
+                    sector.market_value += position.market_value;
+                    sector.weight += position.weight;
+                    sector.codes.push(position.code.clone());
+                }
+                None => by_sector.push(SectorExposure {
+                    sector: position.sector.clone(),
+                    market_value: position.market_value,
+                    weight: position.weight,
+                    codes: vec![position.code.clone()],
+                }),
+            }
+        }
+        by_sector.sort_by(|a, b| b.weight.abs().total_cmp(&a.weight.abs()));
+
+        let top_n_weight = positions
+            .iter()
+            .take(config.top_n)
+            .fold(0.0, |acc, p| acc + p.weight.abs());
+
+        let stock_warnings = positions
+            .iter()
+            .filter(|p| p.weight.abs() > config.max_stock_weight)
+            .map(|p| ConcentrationWarning {
+                kind: ConcentrationKind::Stock,
+                name: format!("{} {}", p.code, p.name),
+                weight: p.weight,
+                limit: config.max_stock_weight,
+            });
+        let sector_warnings = by_sector
+            .iter()
+            .filter(|s| s.sector != UNCLASSIFIED_SECTOR && s.weight.abs() > config.max_sector_weight)
+            .map(|s| ConcentrationWarning {
+                kind: ConcentrationKind::Sector,
+                name: s.sector.clone(),
+                weight: s.weight,
+                limit: config.max_sector_weight,
+            });
+        let warnings = stock_warnings.chain(sector_warnings).collect();
+
+        ExposureReport {
+            positions,
+            sectors: by_sector,
+            equity: summary.equity,
+            cash_weight: Self::percent_of(summary.cash, summary.equity),
+            top_n: config.top_n,
+            top_n_weight,
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::{StockData, StockInfo};
+
+    fn holding(code: &str, quantity: f64, price: f64) -> (StockData, StockInfo) {
+        let info = StockInfo {
+            name: code.to_string(),
+            code: code.to_string(),
+            current_price: price,
+            yesterday_close: price,
+            open_price: price,
+            volume: 0,
+            turnover: 0.0,
+            high_price: price,
+            low_price: price,
+            change_amount: 0.0,
+            change_percent: 0.0,
+        };
+        (StockData::new(code.to_string(), quantity, price, 1000.0, 500.0), info)
+    }
+
+    #[test]
+    fn test_lookup_sector() {
+        let map: SectorMap = toml::from_str(r#"
+            [sectors]
+            "000001" = "银行"
+            "sh513500" = "海外指数"
+        "#).unwrap();
+
+        assert_eq!(StockCalculator::lookup_sector(&map, "000001"), Some("银行"));
+        assert_eq!(StockCalculator::lookup_sector(&map, "sz000001"), Some("银行"));
+        assert_eq!(StockCalculator::lookup_sector(&map, "sh513500"), Some("海外指数"));
+        assert_eq!(StockCalculator::lookup_sector(&map, "513500"), None);
+    }
+
+    #[test]
+    fn test_calculate_exposure() {
+        // 权益 100000：两只银行股 30% + 15%，一只白酒 25%，一只未分类 10%，现金 20%
+        let holdings = vec![
+            holding("000001", 3000.0, 10.0),
+            holding("600036", 500.0, 30.0),
+            holding("600519", 100.0, 250.0),
+            holding("sh513500", 5000.0, 2.0),
+        ];
+        let config = AppConfig::default();
+        let summary = StockCalculator::calculate_portfolio_summary(&holdings, 20000.0, 100000.0, &config);
+        let sectors: HashMap<String, String> = [("000001", "银行"), ("600036", "银行"), ("600519", "白酒")]
+            .iter()
+            .map(|(code, sector)| (code.to_string(), sector.to_string()))
+            .collect();
+
+        let report = StockCalculator::calculate_exposure(&summary, &sectors, &config.concentration);
+        assert!((report.cash_weight - 20.0).abs() < 1e-9);
+        assert_eq!(report.positions[0].code, "000001");
+        assert_eq!(report.positions[3].sector, UNCLASSIFIED_SECTOR);
+
+        assert_eq!(report.sectors[0].sector, "银行");
+        assert!((report.sectors[0].weight - 45.0).abs() < 1e-9);
+        assert_eq!(report.sectors[0].codes, vec!["000001", "600036"]);
+
+        let top3 = ConcentrationConfig { top_n: 3, ..config.concentration.clone() };
+        let report = StockCalculator::calculate_exposure(&summary, &sectors, &top3);
+        assert!((report.top_n_weight - 70.0).abs() < 1e-9);
+
+        // 默认上限: 单只 20%、单个行业 40%
+        assert_eq!(report.warnings.len(), 3);
+        assert_eq!(report.warnings[0].kind, ConcentrationKind::Stock);
+        assert!(report.warnings[0].name.starts_with("000001"));
+        assert_eq!(report.warnings[1].kind, ConcentrationKind::Stock);
+        assert_eq!(report.warnings[2].kind, ConcentrationKind::Sector);
+        assert_eq!(report.warnings[2].name, "银行");
+    }
+}
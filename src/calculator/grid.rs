@@ -0,0 +1,167 @@
+use super::StockCalculator;
+use crate::error::{Result, StockCalcError, ValidationError};
+use crate::models::{GridAnalysis, GridCrossing, GridLevel, GridPlan, GridSpacing};
+
+impl StockCalculator {
+    pub fn validate_grid_plan(plan: &GridPlan) -> Result<()> {
+        if plan.lower_price <= 0.0
+            || plan.upper_price <= plan.lower_price
+            || plan.grid_count == 0
+            || plan.quantity_per_grid <= 0.0
+            || plan.fee_rate < 0.0
+            || plan.min_fee < 0.0
+        {
+            return Err(StockCalcError::ValidationError(
+                ValidationError::InvalidGrid.to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 从下限到上限的 grid_count + 1 条网格线
+    pub fn grid_lines(plan: &GridPlan) -> Vec<f64> {
+        let n = plan.grid_count as f64;
+        (0..=plan.grid_count)
+            .map(|i| match plan.spacing {
+                GridSpacing::Arithmetic => plan.lower_price + (plan.upper_price - plan.lower_price) * i as f64 / n,
+                GridSpacing::Geometric => plan.lower_price * (plan.upper_price / plan.lower_price).powf(i as f64 / n),
+            })
+            .collect()
+    }
+
+    /// 单笔交易手续费，不低于最低手续费
+    pub fn trade_fee(amount: f64, fee_rate: f64, min_fee: f64) -> f64 {
+        (amount * fee_rate / 100.0).max(min_fee)
+    }
+
+    /// 生成每格买卖价、扣费后的每格收益，以及买满所有格所需的资金
+    pub fn calculate_grid(plan: &GridPlan) -> Result<GridAnalysis> {
+        Self::validate_grid_plan(plan)?;
+
+        let lines = Self::grid_lines(plan);
+        let quantity = plan.quantity_per_grid;
+        let levels: Vec<GridLevel> = lines
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let (buy_price, sell_price) = (pair[0], pair[1]);
+                let cost = buy_price * quantity;
+                let fees = Self::trade_fee(cost, plan.fee_rate, plan.min_fee)
+                    + Self::trade_fee(sell_price * quantity, plan.fee_rate, plan.min_fee);
+                let profit = (sell_price - buy_price) * quantity - fees;
+
+                GridLevel {
+                    index: i + 1,
+                    buy_price,
+                    sell_price,
+                    quantity,
+                    profit,
+                    profit_percent: profit / cost * 100.0,
+                }
+            })
+            .collect();
+
+        let capital_required = levels.iter().fold(0.0, |acc, level| {
+            let cost = level.buy_price * level.quantity;
+            acc + cost + Self::trade_fee(cost, plan.fee_rate, plan.min_fee)
+        });
+        let min_grid_profit = levels.iter().map(|l| l.profit).fold(f64::MAX, f64::min);
+        let max_grid_profit = levels.iter().map(|l| l.profit).fold(f64::MIN, f64::max);
+
+        Ok(GridAnalysis {
+            lines,
+            levels,
+            capital_required,
+            min_grid_profit,
+            max_grid_profit,
+        })
+    }
+
+    /// 价格从 previous 变到 current 时穿越的网格线，按穿越顺序返回
+    pub fn detect_grid_crossings(lines: &[f64], previous: f64, current: f64) -> Vec<GridCrossing> {
+        let top = lines.len().saturating_sub(1);
+
+        if current < previous {
+            // 向下穿越：最上方的一条线不对应买入
+            lines
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(i, line)| *i < top && previous > **line && current <= **line)
+                .map(|(i, line)| GridCrossing { line_price: *line, upward: false, level: i + 1 })
+                .collect()
+        } else {
+            // 向上穿越：最下方的一条线不对应卖出
+            lines
+                .iter()
+                .enumerate()
+                .filter(|(i, line)| *i > 0 && previous < **line && current >= **line)
+                .map(|(i, line)| GridCrossing { line_price: *line, upward: true, level: i })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(spacing: GridSpacing) -> GridPlan {
+        GridPlan {
+            code: "sh513500".to_string(),
+            lower_price: 2.0,
+            upper_price: 2.4,
+            grid_count: 4,
+            spacing,
+            quantity_per_grid: 1000.0,
+            fee_rate: 0.01,
+            min_fee: 0.1,
+        }
+    }
+
+    #[test]
+    fn test_calculate_grid() {
+        let analysis = StockCalculator::calculate_grid(&plan(GridSpacing::Arithmetic)).unwrap();
+        assert_eq!(analysis.lines.len(), 5);
+        assert!((analysis.lines[1] - 2.1).abs() < 1e-9);
+        assert_eq!(analysis.levels.len(), 4);
+
+        // 第一格: 2.0 买入 2.1 卖出，手续费 max(0.2, 0.1) + max(0.21, 0.1)
+        let first = &analysis.levels[0];
+        assert!((first.profit - (100.0 - 0.41)).abs() < 1e-9);
+        assert!((analysis.capital_required - (2.0 + 2.1 + 2.2 + 2.3) * 1000.0 - 0.86).abs() < 1e-6);
+        assert!(analysis.max_grid_profit > analysis.min_grid_profit);
+
+        let geometric = StockCalculator::calculate_grid(&plan(GridSpacing::Geometric)).unwrap();
+        let ratio = geometric.lines[1] / geometric.lines[0];
+        assert!((geometric.lines[4] / geometric.lines[3] - ratio).abs() < 1e-9);
+        assert!((geometric.lines[4] - 2.4).abs() < 1e-9);
+
+        let mut invalid = plan(GridSpacing::Arithmetic);
+        invalid.upper_price = 1.9;
+        assert!(StockCalculator::calculate_grid(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_detect_grid_crossings() {
+        let lines = StockCalculator::grid_lines(&plan(GridSpacing::Arithmetic));
+
+        // 2.25 跌到 2.05，依次穿越 2.2 和 2.1，对应第3、2格买入
+        let down = StockCalculator::detect_grid_crossings(&lines, 2.25, 2.05);
+        assert_eq!(down.len(), 2);
+        assert!(!down[0].upward);
+        assert_eq!(down[0].level, 3);
+        assert_eq!(down[1].level, 2);
+
+        // 2.05 涨到 2.1，第1格卖出
+        let up = StockCalculator::detect_grid_crossings(&lines, 2.05, 2.1);
+        assert_eq!(up.len(), 1);
+        assert!(up[0].upward);
+        assert_eq!(up[0].level, 1);
+
+        assert!(StockCalculator::detect_grid_crossings(&lines, 2.11, 2.15).is_empty());
+        // 跌破上限线不提示买入
+        assert!(StockCalculator::detect_grid_crossings(&lines, 2.45, 2.35).is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use super::StockCalculator;
+use crate::config::HoldingCostConfig;
+use crate::models::{CashTransaction, HoldingCostAnalysis, PositionDirection, StockData, TransactionKind};
+use chrono::NaiveDate;
+
+impl StockCalculator {
+    /// 按流水日期推算当前持仓的建仓日：持仓清零后重新买入时重新计算
+    pub fn holding_since(ledger: &[CashTransaction], code: &str) -> Option<NaiveDate> {
+        let mut trades: Vec<&CashTransaction> = ledger
+            .iter()
+            .filter(|t| t.code.as_deref() == Some(code))
+            .collect();
+        trades.sort_by_key(|t| t.date);
+
+        let mut quantity = 0.0;
+        let mut since = None;
+        for trade in trades {
+            match trade.kind {
+                TransactionKind::Buy => {
+                    if quantity <= f64::EPSILON {
+                        since = Some(trade.date);
+                    }
+                    quantity += trade.quantity;
+                }
+                TransactionKind::Sell => {
+                    quantity -= trade.quantity;
+                    if quantity <= f64::EPSILON {
+                        since = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        since
+    }
+
+    /// 盈亏平衡价包含平仓费用和融资融券利息（买入手续费已计入成本价）。
+    /// 持有起始日未知时使用融资融券开始日期；融资部分不计机会成本，融券按卖出金额计资金占用。
+    /// 持仓已全部卖出时没有盈亏平衡价，返回 None
+    pub fn analyze_holding_cost(
+        stock_data: &StockData,
+        since: Option<NaiveDate>,
+        config: &HoldingCostConfig,
+        today: NaiveDate,
+    ) -> Option<HoldingCostAnalysis> {
+        let sign = stock_data.direction.sign();
+        let quantity = stock_data.quantity;
+        if quantity <= 0.0 {
+            return None;
+        }
+        let cost = quantity * stock_data.avg_price;
+        let accrued_interest = stock_data
+            .financing
+            .as_ref()
+            .map_or(0.0, |f| f.accrued_interest(today).1);
+
+        // 多头: 价格 × 数量 - 平仓费 = 成本 + 利息；空头: 价格 × 数量 + 平仓费 = 成本 - 利息
+        let fee_rate = config.exit_fee_rate / 100.0;
+        let mut break_even_price = (cost + sign * accrued_interest) / (quantity * (1.0 - sign * fee_rate));
+        let mut exit_fee = break_even_price * quantity * fee_rate;
+        if exit_fee < config.min_exit_fee {
+            exit_fee = config.min_exit_fee;
+            break_even_price = (cost + sign * (accrued_interest + exit_fee)) / quantity;
+        }
+
+        let holding_since = since.or(stock_data.financing.as_ref().map(|f| f.start_date));
+        let holding_days = holding_since.map(|date| (today - date).num_days().max(0));
+        let own_capital = match stock_data.direction {
+            PositionDirection::Long => (cost - stock_data.financing.as_ref().map_or(0.0, |f| f.borrowed_amount)).max(0.0),
+            PositionDirection::Short => cost,
+        };
+        let opportunity_cost = holding_days.map(|days| own_capital * config.risk_free_rate / 100.0 * days as f64 / 365.0);
+
+        Some(HoldingCostAnalysis {
+            break_even_price,
+            exit_fee,
+            accrued_interest,
+            holding_since,
+            holding_days,
+            opportunity_cost,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Financing;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_holding_since() {
+        let trade = |d: &str, kind: TransactionKind, code: &str, quantity: f64| {
+            CashTransaction::trade(date(d), kind, code, quantity, 10.0, 0.0, 0.0)
+        };
+        let mut ledger = vec![
+            CashTransaction::new(date("2026-01-02"), TransactionKind::Deposit, 100000.0),
+            trade("2026-01-05", TransactionKind::Buy, "000001", 1000.0),
+            trade("2026-02-01", TransactionKind::Sell, "000001", 1000.0),
+            trade("2026-03-10", TransactionKind::Buy, "000001", 500.0),
+            trade("2026-01-08", TransactionKind::Buy, "600000", 100.0),
+            trade("2026-04-01", TransactionKind::Buy, "000001", 500.0),
+            trade("2026-05-01", TransactionKind::Sell, "000001", 300.0),
+        ];
+
+        assert_eq!(StockCalculator::holding_since(&ledger, "000001"), Some(date("2026-03-10")));
+        assert_eq!(StockCalculator::holding_since(&ledger, "600000"), Some(date("2026-01-08")));
+        assert_eq!(StockCalculator::holding_since(&ledger, "000002"), None);
+
+        ledger.push(trade("2026-06-01", TransactionKind::Sell, "000001", 700.0));
+        assert_eq!(StockCalculator::holding_since(&ledger, "000001"), None);
+    }
+
+    #[test]
+    fn test_analyze_holding_cost() {
+        let config = HoldingCostConfig::default();
+        let today = date("2026-10-18");
+
+        // 卖出 10007.5 的 0.075% 为 7.5，高于最低费用
+        let stock = StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0);
+        let cost = StockCalculator::analyze_holding_cost(&stock, Some(date("2026-07-20")), &config, today).unwrap();
+        assert!((cost.break_even_price * 1000.0 - cost.exit_fee - 10000.0).abs() < 1e-6);
+        assert!((cost.exit_fee - cost.break_even_price * 1000.0 * 0.00075).abs() < 1e-9);
+        assert_eq!(cost.holding_days, Some(90));
+        assert!((cost.opportunity_cost.unwrap() - 10000.0 * 0.02 * 90.0 / 365.0).abs() < 1e-9);
+
+        // 空头小额持仓按最低费用 5 元计算，平衡价低于成本价
+        let short = StockData {
+            direction: PositionDirection::Short,
+            ..StockData::new("000001".to_string(), 100.0, 10.0, 500.0, 200.0)
+        };
+        let cost = StockCalculator::analyze_holding_cost(&short, None, &config, today).unwrap();
+        assert!((cost.break_even_price - 9.95).abs() < 1e-9);
+        assert!(cost.holding_days.is_none() && cost.opportunity_cost.is_none());
+
+        // 融资 5000，年化 7.2% 持有 73 天利息 73；只有自有资金 5000 计机会成本
+        let margin = StockData {
+            financing: Some(Financing {
+                borrowed_amount: 5000.0,
+                interest_rate: 7.2,
+                start_date: date("2026-08-06"),
+                maintenance_ratio: 130.0,
+                collateral: 0.0,
+            }),
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0)
+        };
+        let cost = StockCalculator::analyze_holding_cost(&margin, None, &config, today).unwrap();
+        assert!((cost.accrued_interest - 73.0).abs() < 1e-9);
+        assert!((cost.break_even_price * 1000.0 - cost.exit_fee - 10073.0).abs() < 1e-6);
+        assert_eq!(cost.holding_since, Some(date("2026-08-06")));
+        assert!((cost.opportunity_cost.unwrap() - 5000.0 * 0.02 * 73.0 / 365.0).abs() < 1e-9);
+
+        // 全部卖出后仍保留的记录没有盈亏平衡价
+        let sold = StockData::new("000001".to_string(), 0.0, 10.0, 5000.0, 2000.0);
+        assert!(StockCalculator::analyze_holding_cost(&sold, Some(date("2026-07-20")), &config, today).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use super::StockCalculator;
+use crate::models::{BollingerBands, Candle, IndicatorSummary, Kdj, Macd};
+
+impl StockCalculator {
+    /// 简单移动平均，数据不足 period 时为 None
+    pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+        let mut result = Vec::with_capacity(values.len());
+        let mut sum = 0.0;
+
+        for (i, value) in values.iter().enumerate() {
+            sum += value;
+            if i >= period {
+                sum -= values[i - period];
+            }
+            result.push(if period > 0 && i + 1 >= period { Some(sum / period as f64) } else { None });
+        }
+
+        result
+    }
+
+    /// 指数移动平均，与国内行情软件一致以第一个值为初始值
+    pub fn ema(values: &[f64], period: usize) -> Vec<f64> {
+        let alpha = 2.0 / (period as f64 + 1.0);
+        let mut result = Vec::with_capacity(values.len());
+
+        for value in values {
+            let next = match result.last() {
+                Some(prev) => alpha * value + (1.0 - alpha) * prev,
+                None => *value,
+            };
+            result.push(next);
+        }
+
+        result
+    }
+
+    /// MACD (DIF/DEA/柱)，柱状值按国内习惯取 2 × (DIF - DEA)
+    pub fn macd(closes: &[f64], fast: usize, slow: usize, signal: usize) -> Vec<Macd> {
+        let fast = Self::ema(closes, fast);
+        let slow = Self::ema(closes, slow);
+        let dif: Vec<f64> = fast.iter().zip(&slow).map(|(f, s)| f - s).collect();
+        let dea = Self::ema(&dif, signal);
+
+        dif.iter()
+            .zip(&dea)
+            .map(|(dif, dea)| Macd {
+                dif: *dif,
+                dea: *dea,
+                histogram: 2.0 * (dif - dea),
+            })
+            .collect()
+    }
+
+    /// RSI，使用 Wilder 平滑，数据不足 period 时为 None
+    pub fn rsi(closes: &[f64], period: usize) -> Vec<Option<f64>> {
+        let mut result = vec![None; closes.len()];
+        if period == 0 || closes.len() <= period {
+            return result;
+        }
+
+        let changes: Vec<f64> = closes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let mut avg_gain = changes[..period].iter().map(|c| c.max(0.0)).sum::<f64>() / period as f64;
+        let mut avg_loss = changes[..period].iter().map(|c| (-c).max(0.0)).sum::<f64>() / period as f64;
+
+        let value = |gain: f64, loss: f64| -> f64 {
+            if gain + loss == 0.0 { 50.0 } else { gain / (gain + loss) * 100.0 }
+        };
+
+        result[period] = Some(value(avg_gain, avg_loss));
+        for (i, change) in changes.iter().enumerate().skip(period) {
+            avg_gain = (avg_gain * (period - 1) as f64 + change.max(0.0)) / period as f64;
+            avg_loss = (avg_loss * (period - 1) as f64 + (-change).max(0.0)) / period as f64;
+            result[i + 1] = Some(value(avg_gain, avg_loss));
+        }
+
+        result
+    }
+
+    /// KDJ，K/D 初始值为50，数据不足 period 时使用已有数据
+    pub fn kdj(candles: &[Candle], period: usize, k_smooth: usize, d_smooth: usize) -> Vec<Kdj> {
+        let mut result = Vec::with_capacity(candles.len());
+        let (mut k, mut d) = (50.0, 50.0);
+
+        for i in 0..candles.len() {
+            let window = &candles[(i + 1).saturating_sub(period)..=i];
+            let high = window.iter().fold(f64::MIN, |acc, c| acc.max(c.high));
+            let low = window.iter().fold(f64::MAX, |acc, c| acc.min(c.low));
+            let rsv = if high > low { (candles[i].close - low) / (high - low) * 100.0 } else { 50.0 };
+
+            k = ((k_smooth - 1) as f64 * k + rsv) / k_smooth as f64;
+            d = ((d_smooth - 1) as f64 * d + k) / d_smooth as f64;
+            result.push(Kdj { k, d, j: 3.0 * k - 2.0 * d });
+        }
+
+        result
+    }
+
+    /// 布林带，中轨为 period 日均线，上下轨为中轨 ± multiplier 倍标准差
+    pub fn bollinger(closes: &[f64], period: usize, multiplier: f64) -> Vec<Option<BollingerBands>> {
+        Self::sma(closes, period)
+            .into_iter()
+            .enumerate()
+            .map(|(i, middle)| {
+                let middle = middle?;
+                let window = &closes[i + 1 - period..=i];
+                let variance = window.iter().map(|c| (c - middle).powi(2)).sum::<f64>() / period as f64;
+                let width = multiplier * variance.sqrt();
+
+                Some(BollingerBands {
+                    upper: middle + width,
+                    middle,
+                    lower: middle - width,
+                })
+            })
+            .collect()
+    }
+
+    /// 平均真实波幅 (ATR)，使用 Wilder 平滑，数据不足 period 时为 None
+    pub fn atr(candles: &[Candle], period: usize) -> Vec<Option<f64>> {
+        let mut result = vec![None; candles.len()];
+        if period == 0 || candles.len() < period {
+            return result;
+        }
+
+        let true_ranges: Vec<f64> = candles
+            .iter()
+            .enumerate()
+            .map(|(i, candle)| match i.checked_sub(1).map(|prev| candles[prev].close) {
+                Some(prev_close) => (candle.high - candle.low)
+                    .max((candle.high - prev_close).abs())
+                    .max((candle.low - prev_close).abs()),
+                None => candle.high - candle.low,
+            })
+            .collect();
+
+        let mut atr = true_ranges[..period].iter().sum::<f64>() / period as f64;
+        result[period - 1] = Some(atr);
+        for (i, true_range) in true_ranges.iter().enumerate().skip(period) {
+            atr = (atr * (period - 1) as f64 + true_range) / period as f64;
+            result[i] = Some(atr);
+        }
+
+        result
+    }
+
+    /// 计算最新一根K线的常用指标和信号
+    pub fn calculate_indicator_summary(candles: &[Candle]) -> Option<IndicatorSummary> {
+        let last = candles.last()?;
+        let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+        let latest = |series: Vec<Option<f64>>| series.last().copied().flatten();
+
+        let macd = Self::macd(&closes, 12, 26, 9);
+        let kdj = Self::kdj(candles, 9, 3, 3);
+        let mut summary = IndicatorSummary {
+            date: last.date,
+            close: last.close,
+            ma5: latest(Self::sma(&closes, 5)),
+            ma10: latest(Self::sma(&closes, 10)),
+            ma20: latest(Self::sma(&closes, 20)),
+            ma60: latest(Self::sma(&closes, 60)),
+            ema12: Self::ema(&closes, 12).last().copied(),
+            ema26: Self::ema(&closes, 26).last().copied(),
+            macd: macd.last().cloned(),
+            rsi6: latest(Self::rsi(&closes, 6)),
+            rsi12: latest(Self::rsi(&closes, 12)),
+            rsi24: latest(Self::rsi(&closes, 24)),
+            kdj: kdj.last().cloned(),
+            bollinger: Self::bollinger(&closes, 20, 2.0).last().cloned().flatten(),
+            atr: latest(Self::atr(candles, 14)),
+            signals: Vec::new(),
+        };
+
+        let previous_macd = macd.len().checked_sub(2).map(|i| &macd[i]);
+        summary.signals = Self::indicator_signals(&summary, previous_macd);
+        Some(summary)
+    }
+
+    fn indicator_signals(summary: &IndicatorSummary, previous_macd: Option<&Macd>) -> Vec<String> {
+        let mut signals = Vec::new();
+
+        if let (Some(macd), Some(previous)) = (&summary.macd, previous_macd) {
+            if previous.dif <= previous.dea && macd.dif > macd.dea {
+                signals.push("MACD 金叉".to_string());
+            } else if previous.dif >= previous.dea && macd.dif < macd.dea {
+                signals.push("MACD 死叉".to_string());
+            }
+        }
+
+        if let Some(rsi) = summary.rsi6 {
+            if rsi >= 80.0 {
+                signals.push(format!("RSI6 超买 ({:.1})", rsi));
+            } else if rsi <= 20.0 {
+                signals.push(format!("RSI6 超卖 ({:.1})", rsi));
+            }
+        }
+
+        if let Some(kdj) = &summary.kdj {
+            if kdj.j > 100.0 {
+                signals.push(format!("KDJ J值超买 ({:.1})", kdj.j));
+            } else if kdj.j < 0.0 {
+                signals.push(format!("KDJ J值超卖 ({:.1})", kdj.j));
+            }
+        }
+
+        if let Some(bands) = &summary.bollinger {
+            if summary.close > bands.upper {
+                signals.push("收盘价突破布林上轨".to_string());
+            } else if summary.close < bands.lower {
+                signals.push("收盘价跌破布林下轨".to_string());
+            }
+        }
+
+        if let (Some(ma5), Some(ma20)) = (summary.ma5, summary.ma20) {
+            if summary.close < ma20 && ma5 < ma20 {
+                signals.push("价格和MA5位于MA20下方，短期趋势转弱".to_string());
+            }
+        }
+
+        signals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn candles(closes: &[f64], range: f64) -> Vec<Candle> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Candle {
+                date: start + chrono::Duration::days(i as i64),
+                open: *close,
+                close: *close,
+                high: close + range / 2.0,
+                low: close - range / 2.0,
+                volume: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_moving_averages() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let sma = StockCalculator::sma(&values, 3);
+        assert_eq!(sma[1], None);
+        assert_eq!(sma[2], Some(2.0));
+        assert_eq!(sma[4], Some(4.0));
+
+        let ema = StockCalculator::ema(&values, 3);
+        assert_eq!(ema[0], 1.0);
+        assert!((ema[1] - 1.5).abs() < 1e-9);
+
+        // 价格不变时 MACD 为 0
+        let macd = StockCalculator::macd(&[10.0; 40], 12, 26, 9);
+        assert!(macd.last().unwrap().histogram.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_oscillators() {
+        let rising: Vec<f64> = (1..=30).map(|i| i as f64).collect();
+        let rsi = StockCalculator::rsi(&rising, 14);
+        assert_eq!(rsi[13], None);
+        assert_eq!(rsi[14], Some(100.0));
+
+        let kdj = StockCalculator::kdj(&candles(&rising, 1.0), 9, 3, 3);
+        let last = kdj.last().unwrap();
+        assert!(last.k > 80.0 && last.k > last.d);
+
+        let bands = StockCalculator::bollinger(&[10.0; 20], 20, 2.0);
+        let last = bands.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.upper, last.lower);
+        assert!(bands[18].is_none());
+    }
+
+    #[test]
+    fn test_atr_and_summary() {
+        let atr = StockCalculator::atr(&candles(&[10.0; 20], 0.4), 14);
+        assert_eq!(atr[12], None);
+        assert!((atr[13].unwrap() - 0.4).abs() < 1e-9);
+        assert!((atr[19].unwrap() - 0.4).abs() < 1e-9);
+
+        let closes: Vec<f64> = (0..80).map(|i| 10.0 + (i as f64 * 0.3).sin()).collect();
+        let summary = StockCalculator::calculate_indicator_summary(&candles(&closes, 0.2)).unwrap();
+        assert!(summary.ma60.is_some());
+        assert!(summary.atr.is_some());
+        assert!(summary.bollinger.is_some());
+        assert!(StockCalculator::calculate_indicator_summary(&[]).is_none());
+    }
+}
@@ -0,0 +1,646 @@
+use super::StockCalculator;
+use crate::models::{
+    CashTransaction, CorporateActionKind, DailySnapshot, EquityCurve, EquityCurvePoint, Portfolio,
+    PortfolioPerformance, PositionDirection, PositionPerformance, PositionSnapshot, StockData, TransactionKind,
+    ValuationPoint,
+};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+impl StockCalculator {
+    /// 年化内部收益率 (XIRR)，返回百分比；现金流需同时包含流入和流出
+    pub fn xirr(flows: &[(NaiveDate, f64)]) -> Option<f64> {
+        let has_inflow = flows.iter().any(|(_, amount)| *amount > 0.0);
+        let has_outflow = flows.iter().any(|(_, amount)| *amount < 0.0);
+        let start = flows.iter().map(|(date, _)| *date).min()?;
+
+        if !has_inflow || !has_outflow {
+            return None;
+        }
+
+        let flows: Vec<(f64, f64)> = flows
+            .iter()
+            .map(|(date, amount)| ((*date - start).num_days() as f64 / 365.0, *amount))
+            .collect();
+
+        if flows.iter().all(|(years, _)| *years == 0.0) {
+            return None;
+        }
+
+        let npv = |rate: f64| -> f64 {
+            flows.iter().map(|(years, amount)| amount / (1.0 + rate).powf(*years)).sum()
+        };
+
+        // 先用牛顿法，不收敛时退回二分法
+        let mut rate = 0.1;
+        for _ in 0..100 {
+            let value = npv(rate);
+            let derivative: f64 = flows
+                .iter()
+                .map(|(years, amount)| -years * amount / (1.0 + rate).powf(years + 1.0))
+                .sum();
+
+            if derivative.abs() < 1e-12 {
+                break;
+            }
+
+            let next = rate - value / derivative;
+            if !next.is_finite() || next <= -0.999_999 {
+                break;
+            }
+
+            if (next - rate).abs() < 1e-10 {
+                return Some(next * 100.0);
+            }
+            rate = next;
+        }
+
+        let (mut low, mut high) = (-0.999_999, 1.0);
+        while npv(low).signum() == npv(high).signum() {
+            high *= 2.0;
+            if high > 1e6 {
+                return None;
+            }
+        }
+
+        for _ in 0..300 {
+            let mid = (low + high) / 2.0;
+            if npv(mid).signum() == npv(low).signum() {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        Some((low + high) / 2.0 * 100.0)
+    }
+
+    /// 时间加权收益率，返回百分比；期初市值为0的区间不计入
+    pub fn time_weighted_return(points: &[ValuationPoint]) -> Option<f64> {
+        if points.len() < 2 {
+            return None;
+        }
+
+        let growth = points
+            .windows(2)
+            .filter(|pair| pair[0].value > 0.0)
+            .fold(1.0, |growth, pair| growth * (pair[1].value - pair[1].flow) / pair[0].value);
+
+        Some((growth - 1.0) * 100.0)
+    }
+
+    /// 将区间收益率换算为年化收益率 (%)
+    pub fn annualize_return(total_return: f64, start: NaiveDate, end: NaiveDate) -> Option<f64> {
+        let days = (end - start).num_days();
+        if days < 1 {
+            return None;
+        }
+
+        Some(((1.0 + total_return / 100.0).powf(365.0 / days as f64) - 1.0) * 100.0)
+    }
+
+    /// 单只股票的投资者现金流：买入为负，卖出和分红为正，期末市值视为流入
+    pub fn position_cash_flows(
+        code: &str,
+        stock: Option<&StockData>,
+        ledger: &[CashTransaction],
+        current_price: f64,
+        today: NaiveDate,
+    ) -> Vec<(NaiveDate, f64)> {
+        let mut flows: Vec<(NaiveDate, f64)> = Self::position_transactions(code, ledger)
+            .map(|t| (t.date, t.amount))
+            .collect();
+
+        if let Some(stock) = stock {
+            // 流水之外建仓的部分（如 calculate --save 后再用流水加仓）作为初始投入
+            if let Some((seed_date, quantity)) = Self::pre_ledger_position(code, stock, ledger) {
+                let cost = stock.direction.sign() * quantity * stock.avg_price;
+                flows.insert(0, (seed_date, -cost));
+            }
+
+            flows.push((today, stock.signed_value(current_price)));
+        }
+
+        flows
+    }
+
+    /// 根据成交价格重建单只股票在每笔交易时的估值点，分红并入下一个估值点的收益
+    pub fn position_valuation_points(
+        code: &str,
+        stock: Option<&StockData>,
+        ledger: &[CashTransaction],
+        current_price: f64,
+        today: NaiveDate,
+    ) -> Vec<ValuationPoint> {
+        // 融券持仓的市值为负，不计算时间加权收益率
+        if stock.is_some_and(|stock| stock.direction == PositionDirection::Short) {
+            return Vec::new();
+        }
+
+        enum Event<'a> {
+            ShareChange(f64),
+            Dividend(f64),
+            Trade(&'a CashTransaction),
+        }
+
+        let mut events: Vec<(NaiveDate, u8, Event)> = Vec::new();
+
+        if let Some(stock) = stock {
+            for action in stock.corporate_actions.iter().filter(|a| a.applied) {
+                match action.kind {
+                    CorporateActionKind::BonusShares { per_share } => {
+                        events.push((action.ex_date, 0, Event::ShareChange(1.0 + per_share)));
+                    }
+                    CorporateActionKind::Split { ratio } => {
+                        events.push((action.ex_date, 0, Event::ShareChange(ratio)));
+                    }
+                    CorporateActionKind::CashDividend { .. } => {}
+                }
+            }
+        }
+
+        for transaction in Self::position_transactions(code, ledger) {
+            match transaction.kind {
+                TransactionKind::Dividend => events.push((transaction.date, 1, Event::Dividend(transaction.amount))),
+                TransactionKind::Buy | TransactionKind::Sell => events.push((transaction.date, 2, Event::Trade(transaction))),
+                _ => {}
+            }
+        }
+
+        // 同一天先除权再成交
+        events.sort_by_key(|(date, order, _)| (*date, *order));
+
+        let mut points = Vec::new();
+        let mut shares = 0.0;
+        let mut pending_income = 0.0;
+
+        if let Some((seed_date, quantity)) = stock.and_then(|stock| Self::pre_ledger_position(code, stock, ledger)) {
+            let cost = quantity * stock.map_or(0.0, |stock| stock.avg_price);
+            shares = quantity;
+            points.push(ValuationPoint {
+                date: seed_date,
+                value: cost,
+                flow: cost,
+            });
+        }
+
+        for (date, _, event) in events {
+            match event {
+                Event::ShareChange(factor) => shares *= factor,
+                Event::Dividend(amount) => pending_income += amount,
+                Event::Trade(transaction) => {
+                    match transaction.kind {
+                        TransactionKind::Buy => shares += transaction.quantity,
+                        _ => shares -= transaction.quantity,
+                    }
+
+                    points.push(ValuationPoint {
+                        date,
+                        value: shares * transaction.price,
+                        flow: -transaction.amount - pending_income,
+                    });
+                    pending_income = 0.0;
+                }
+            }
+        }
+
+        let final_value = stock.map_or(0.0, |stock| stock.signed_value(current_price));
+        if stock.is_some() || pending_income != 0.0 {
+            points.push(ValuationPoint {
+                date: today,
+                value: final_value,
+                flow: -pending_income,
+            });
+        }
+
+        points
+    }
+
+    /// 计算组合及其中每只股票（含已清仓）的 XIRR 和时间加权收益率
+    pub fn calculate_portfolio_performance(
+        portfolio: &Portfolio,
+        prices: &HashMap<String, f64>,
+        today: NaiveDate,
+    ) -> PortfolioPerformance {
+        let mut codes: Vec<String> = portfolio
+            .stocks
+            .keys()
+            .filter(|code| prices.contains_key(*code))
+            .cloned()
+            .collect();
+        for transaction in &portfolio.ledger {
+            if let Some(code) = &transaction.code {
+                let open = portfolio.stocks.get(code).is_some_and(|stock| !stock.is_closed());
+                if !open && !codes.contains(code) {
+                    codes.push(code.clone());
+                }
+            }
+        }
+
+        let positions: Vec<PositionPerformance> = codes
+            .iter()
+            .map(|code| {
+                // 已清仓的持仓只按流水计算
+                let stock = portfolio.stocks.get(code).filter(|stock| !stock.is_closed());
+                let price = prices.get(code).copied().unwrap_or_default();
+                let flows = Self::position_cash_flows(code, stock, &portfolio.ledger, price, today);
+                let points = Self::position_valuation_points(code, stock, &portfolio.ledger, price, today);
+                let first_date = flows.iter().map(|(date, _)| *date).min().unwrap_or(today);
+                let twr = Self::time_weighted_return(&points);
+
+                PositionPerformance {
+                    code: code.clone(),
+                    closed: stock.is_none(),
+                    first_date,
+                    market_value: stock.map_or(0.0, |stock| stock.signed_value(price)),
+                    total_profit: flows.iter().map(|(_, amount)| amount).sum(),
+                    xirr: Self::xirr(&flows),
+                    twr,
+                    annualized_twr: twr.and_then(|twr| Self::annualize_return(twr, first_date, today)),
+                }
+            })
+            .collect();
+
+        let equity = portfolio.equity_at(prices, today);
+
+        // 组合层面以入金/出金为外部现金流
+        let mut flows: Vec<(NaiveDate, f64)> = portfolio
+            .ledger
+            .iter()
+            .filter(|t| matches!(t.kind, TransactionKind::Deposit | TransactionKind::Withdrawal))
+            .map(|t| (t.date, -t.amount))
+            .collect();
+        let first_date = flows.iter().map(|(date, _)| *date).min();
+        flows.push((today, equity));
+
+        let points = Self::portfolio_valuation_points(portfolio, equity, today);
+        let twr = points.as_deref().and_then(Self::time_weighted_return);
+        let twr_start = points.as_deref().and_then(|points| points.first()).map(|point| point.date);
+
+        PortfolioPerformance {
+            positions,
+            equity,
+            net_deposits: portfolio.net_deposits(),
+            xirr: Self::xirr(&flows),
+            twr,
+            annualized_twr: twr
+                .zip(twr_start.or(first_date))
+                .and_then(|(twr, start)| Self::annualize_return(twr, start, today)),
+        }
+    }
+
+    /// 组合的估值点：有每日快照时以快照为准（从第一个快照开始计算），
+    /// 否则只有所有入金发生在同一天时才能计算
+    fn portfolio_valuation_points(portfolio: &Portfolio, equity: f64, today: NaiveDate) -> Option<Vec<ValuationPoint>> {
+        if let Some(last) = portfolio.snapshots.last() {
+            let mut points: Vec<ValuationPoint> = portfolio
+                .snapshots
+                .iter()
+                .map(|snapshot| ValuationPoint {
+                    date: snapshot.date,
+                    value: snapshot.equity,
+                    flow: snapshot.net_flow,
+                })
+                .collect();
+
+            if last.date < today {
+                points.push(ValuationPoint {
+                    date: today,
+                    value: equity,
+                    flow: Self::external_flows_between(&portfolio.ledger, Some(last.date), today),
+                });
+            }
+
+            return Some(points);
+        }
+
+        let mut external = portfolio
+            .ledger
+            .iter()
+            .filter(|t| matches!(t.kind, TransactionKind::Deposit | TransactionKind::Withdrawal));
+
+        let first = external.next()?;
+        let initial = portfolio
+            .ledger
+            .iter()
+            .filter(|t| t.date == first.date && matches!(t.kind, TransactionKind::Deposit | TransactionKind::Withdrawal))
+            .map(|t| t.amount)
+            .sum::<f64>();
+
+        if external.any(|t| t.date != first.date) {
+            return None;
+        }
+
+        Some(vec![
+            ValuationPoint { date: first.date, value: initial, flow: initial },
+            ValuationPoint { date: today, value: equity, flow: 0.0 },
+        ])
+    }
+
+    /// 按当前价格生成组合快照，没有价格的持仓按成本计价
+    pub fn take_snapshot(portfolio: &Portfolio, prices: &HashMap<String, f64>, date: NaiveDate) -> DailySnapshot {
+        let mut positions: Vec<PositionSnapshot> = portfolio
+            .stocks
+            .values()
+            .filter(|stock| !stock.is_closed())
+            .map(|stock| {
+                let price = prices.get(&stock.code).copied().unwrap_or(stock.avg_price);
+                PositionSnapshot {
+                    code: stock.code.clone(),
+                    quantity: stock.quantity,
+                    price,
+                    market_value: stock.signed_value(price),
+                }
+            })
+            .collect();
+        positions.sort_by(|a, b| a.code.cmp(&b.code));
+
+        let market_value = positions.iter().fold(0.0, |acc, p| acc + p.market_value);
+        let previous = portfolio.snapshots.iter().rev().find(|s| s.date < date).map(|s| s.date);
+
+        DailySnapshot {
+            date,
+            positions,
+            cash: portfolio.cash,
+            market_value,
+            equity: portfolio.equity_at(prices, date),
+            net_flow: Self::external_flows_between(&portfolio.ledger, previous, date),
+        }
+    }
+
+    /// 根据快照计算净值曲线、最大回撤和最好/最差的一天，日收益剔除入金/出金
+    pub fn calculate_equity_curve(snapshots: &[DailySnapshot]) -> EquityCurve {
+        let mut points = Vec::with_capacity(snapshots.len());
+        let mut index = 1.0;
+        let mut peak = 1.0;
+        let mut peak_date = snapshots.first().map(|s| s.date);
+        let mut max_drawdown = 0.0;
+        let mut max_drawdown_start = None;
+        let mut max_drawdown_end = None;
+
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            let daily_return = match i.checked_sub(1).map(|prev| &snapshots[prev]) {
+                Some(prev) if prev.equity > 0.0 => {
+                    Some(((snapshot.equity - snapshot.net_flow) / prev.equity - 1.0) * 100.0)
+                }
+                _ => None,
+            };
+
+            if let Some(daily_return) = daily_return {
+                index *= 1.0 + daily_return / 100.0;
+            }
+            if index > peak {
+                peak = index;
+                peak_date = Some(snapshot.date);
+            }
+
+            let drawdown = (index / peak - 1.0) * 100.0;
+            if drawdown < max_drawdown {
+                max_drawdown = drawdown;
+                max_drawdown_start = peak_date;
+                max_drawdown_end = Some(snapshot.date);
+            }
+
+            points.push(EquityCurvePoint {
+                date: snapshot.date,
+                equity: snapshot.equity,
+                daily_return,
+                drawdown,
+            });
+        }
+
+        let daily_returns = points.iter().filter_map(|p| p.daily_return.map(|r| (p.date, r)));
+        let best_day = daily_returns.clone().max_by(|a, b| a.1.total_cmp(&b.1));
+        let worst_day = daily_returns.min_by(|a, b| a.1.total_cmp(&b.1));
+
+        EquityCurve {
+            points,
+            total_return: (index - 1.0) * 100.0,
+            max_drawdown,
+            max_drawdown_start,
+            max_drawdown_end,
+            best_day,
+            worst_day,
+        }
+    }
+
+    /// (after, until] 区间内的入金/出金合计，after 为空时从头开始
+    fn external_flows_between(ledger: &[CashTransaction], after: Option<NaiveDate>, until: NaiveDate) -> f64 {
+        ledger
+            .iter()
+            .filter(|t| matches!(t.kind, TransactionKind::Deposit | TransactionKind::Withdrawal))
+            .filter(|t| t.date <= until && after.map_or(true, |after| t.date > after))
+            .fold(0.0, |acc, t| acc + t.amount)
+    }
+
+    /// 流水之外建仓的初始持仓：当前数量扣除流水中的买卖（按送转/拆股折算）后剩余的部分，
+    /// 日期取该股票最早的流水或除权日，没有流水时取最后更新日期；成本按当前成本价估算
+    fn pre_ledger_position(code: &str, stock: &StockData, ledger: &[CashTransaction]) -> Option<(NaiveDate, f64)> {
+        let mut events: Vec<(NaiveDate, u8, f64)> = stock
+            .corporate_actions
+            .iter()
+            .filter(|a| a.applied)
+            .filter_map(|action| match action.kind {
+                CorporateActionKind::BonusShares { per_share } => Some((action.ex_date, 0, 1.0 + per_share)),
+                CorporateActionKind::Split { ratio } => Some((action.ex_date, 0, ratio)),
+                CorporateActionKind::CashDividend { .. } => None,
+            })
+            .collect();
+        let trades: Vec<&CashTransaction> = Self::position_transactions(code, ledger).collect();
+        events.sort_by_key(|(date, order, _)| (*date, *order));
+
+        // 初始数量 x 经过所有事件后为 x × 总倍数 + 流水带来的股数
+        let (mut ledger_shares, mut factor) = (0.0, 1.0);
+        let mut trade_iter = trades.iter().filter(|t| t.kind != TransactionKind::Dividend).peekable();
+        for (date, _, ratio) in &events {
+            while let Some(trade) = trade_iter.next_if(|t| t.date < *date) {
+                ledger_shares += Self::trade_shares(trade);
+            }
+            ledger_shares *= ratio;
+            factor *= ratio;
+        }
+        ledger_shares += trade_iter.map(|t| Self::trade_shares(t)).sum::<f64>();
+
+        let quantity = (stock.quantity - ledger_shares) / factor;
+        if quantity <= 1e-6 {
+            return None;
+        }
+
+        let seed_date = trades
+            .iter()
+            .map(|t| t.date)
+            .chain(events.iter().map(|(date, _, _)| *date))
+            .min()
+            .unwrap_or_else(|| stock.last_updated.date_naive());
+        Some((seed_date, quantity))
+    }
+
+    fn trade_shares(transaction: &CashTransaction) -> f64 {
+        match transaction.kind {
+            TransactionKind::Buy => transaction.quantity,
+            TransactionKind::Sell => -transaction.quantity,
+            _ => 0.0,
+        }
+    }
+
+    fn position_transactions<'a>(code: &'a str, ledger: &'a [CashTransaction]) -> impl Iterator<Item = &'a CashTransaction> {
+        ledger.iter().filter(move |t| {
+            t.code.as_deref() == Some(code)
+                && matches!(t.kind, TransactionKind::Buy | TransactionKind::Sell | TransactionKind::Dividend)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_xirr() {
+        let flows = vec![(date("2023-01-01"), -1000.0), (date("2024-01-01"), 1100.0)];
+        let rate = StockCalculator::xirr(&flows).unwrap();
+        assert!((rate - 10.0).abs() < 0.01);
+
+        // 追加投入后亏损
+        let flows = vec![
+            (date("2023-01-01"), -1000.0),
+            (date("2023-07-01"), -1000.0),
+            (date("2024-01-01"), 1800.0),
+        ];
+        assert!(StockCalculator::xirr(&flows).unwrap() < 0.0);
+
+        // 只有流出无法计算
+        assert!(StockCalculator::xirr(&[(date("2023-01-01"), -1000.0)]).is_none());
+    }
+
+    #[test]
+    fn test_position_performance() {
+        let mut portfolio = Portfolio::new("test", 0.0);
+        portfolio.record_transaction(CashTransaction::new(date("2023-01-01"), TransactionKind::Deposit, 30000.0));
+        portfolio.record_transaction(CashTransaction::trade(date("2023-01-01"), TransactionKind::Buy, "000001", 1000.0, 10.0, 0.0, -10000.0));
+        portfolio.record_transaction(CashTransaction::trade(date("2023-07-01"), TransactionKind::Buy, "000001", 1000.0, 12.0, 0.0, -12000.0));
+
+        let mut stock = StockData::new("000001".to_string(), 2000.0, 11.0, 5000.0, 2000.0);
+        stock.last_updated = chrono::Utc::now();
+        portfolio.stocks.insert("000001".to_string(), stock);
+
+        let prices = HashMap::from([("000001".to_string(), 11.0)]);
+        let performance = StockCalculator::calculate_portfolio_performance(&portfolio, &prices, date("2024-01-01"));
+
+        // 时间加权: 12/10 * 11/12 - 1 = 10%，与追加投入的时点无关
+        let position = &performance.positions[0];
+        assert!((position.twr.unwrap() - 10.0).abs() < 0.001);
+        assert!((position.total_profit - 0.0).abs() < 0.01);
+        // 资金加权: 总盈亏为0
+        assert!(position.xirr.unwrap().abs() < 0.01);
+
+        assert!((performance.equity - 30000.0).abs() < 0.01);
+        assert!(performance.xirr.unwrap().abs() < 0.01);
+        assert!(performance.twr.unwrap().abs() < 0.001);
+    }
+
+    #[test]
+    fn test_closed_position_kept() {
+        // 全部卖出后记录保留为 0 股，收益按流水计算，已实现收益计入组合
+        let mut portfolio = Portfolio::new("test", 0.0);
+        portfolio.record_transaction(CashTransaction::new(date("2023-01-01"), TransactionKind::Deposit, 10000.0));
+        portfolio.record_transaction(CashTransaction::trade(date("2023-01-01"), TransactionKind::Buy, "000001", 1000.0, 10.0, 0.0, -10000.0));
+        portfolio.record_transaction(CashTransaction::trade(date("2023-07-01"), TransactionKind::Sell, "000001", 1000.0, 12.0, 0.0, 12000.0));
+
+        let mut stock = StockData::new("000001".to_string(), 1000.0, 10.0, 5000.0, 2000.0);
+        StockCalculator::apply_sell(&mut stock, 1000.0, 12.0, 0.0).unwrap();
+        assert!(stock.is_closed());
+        portfolio.stocks.insert("000001".to_string(), stock);
+        assert!((portfolio.closed_realized_profit() - 2000.0).abs() < 0.01);
+
+        let performance = StockCalculator::calculate_portfolio_performance(&portfolio, &HashMap::new(), date("2024-01-01"));
+        let position = &performance.positions[0];
+        assert!(position.closed);
+        assert!((position.total_profit - 2000.0).abs() < 0.01);
+        assert!((performance.equity - 12000.0).abs() < 0.01);
+
+        let snapshot = StockCalculator::take_snapshot(&portfolio, &HashMap::new(), date("2024-01-01"));
+        assert!(snapshot.positions.is_empty());
+    }
+
+    #[test]
+    fn test_pre_ledger_position() {
+        // calculate --save 建仓 1000 股后，再用流水加仓 1000 股，成本价 11
+        let mut portfolio = Portfolio::new("test", 0.0);
+        portfolio.record_transaction(CashTransaction::trade(date("2023-07-01"), TransactionKind::Buy, "000001", 1000.0, 12.0, 0.0, -12000.0));
+        let mut stock = StockData::new("000001".to_string(), 2000.0, 11.0, 5000.0, 2000.0);
+        stock.last_updated = chrono::Utc::now();
+        portfolio.stocks.insert("000001".to_string(), stock);
+
+        let prices = HashMap::from([("000001".to_string(), 11.0)]);
+        let performance = StockCalculator::calculate_portfolio_performance(&portfolio, &prices, date("2024-01-01"));
+
+        // 流水之外的 1000 股按成本价计入初始投入: 22000 - 11000 - 12000
+        let position = &performance.positions[0];
+        assert!((position.total_profit + 1000.0).abs() < 0.01);
+        assert!(position.xirr.unwrap() < 0.0);
+        assert!(position.twr.unwrap().abs() < 0.001);
+    }
+
+    fn snapshot(day: &str, equity: f64, net_flow: f64) -> DailySnapshot {
+        DailySnapshot {
+            date: date(day),
+            positions: Vec::new(),
+            cash: equity,
+            market_value: 0.0,
+            equity,
+            net_flow,
+        }
+    }
+
+    #[test]
+    fn test_equity_curve() {
+        let snapshots = vec![
+            snapshot("2024-01-02", 100.0, 100.0),
+            snapshot("2024-01-03", 110.0, 0.0),
+            snapshot("2024-01-04", 88.0, 0.0),
+            // 入金20，剔除后当日收益 100/88
+            snapshot("2024-01-05", 120.0, 20.0),
+        ];
+
+        let curve = StockCalculator::calculate_equity_curve(&snapshots);
+        assert_eq!(curve.points.len(), 4);
+        assert!(curve.points[0].daily_return.is_none());
+        assert!((curve.max_drawdown + 20.0).abs() < 0.001);
+        assert_eq!(curve.max_drawdown_start, Some(date("2024-01-03")));
+        assert_eq!(curve.max_drawdown_end, Some(date("2024-01-04")));
+
+        let (best_date, best) = curve.best_day.unwrap();
+        assert_eq!(best_date, date("2024-01-05"));
+        assert!((best - (100.0 / 88.0 - 1.0) * 100.0).abs() < 0.001);
+        assert_eq!(curve.worst_day.unwrap().0, date("2024-01-04"));
+        assert!((curve.total_return - 0.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_take_snapshot() {
+        let mut portfolio = Portfolio::new("test", 0.0);
+        portfolio.record_transaction(CashTransaction::new(date("2024-01-02"), TransactionKind::Deposit, 10000.0));
+        portfolio.stocks.insert(
+            "000001".to_string(),
+            StockData::new("000001".to_string(), 100.0, 10.0, 500.0, 200.0),
+        );
+
+        let prices = HashMap::from([("000001".to_string(), 12.0)]);
+        let first = StockCalculator::take_snapshot(&portfolio, &prices, date("2024-01-02"));
+        assert!((first.equity - 11200.0).abs() < 0.001);
+        assert!((first.net_flow - 10000.0).abs() < 0.001);
+        portfolio.record_snapshot(first);
+
+        portfolio.record_transaction(CashTransaction::new(date("2024-01-03"), TransactionKind::Withdrawal, -1000.0));
+        let second = StockCalculator::take_snapshot(&portfolio, &prices, date("2024-01-03"));
+        assert!((second.net_flow + 1000.0).abs() < 0.001);
+        portfolio.record_snapshot(second.clone());
+        portfolio.record_snapshot(second);
+        assert_eq!(portfolio.snapshots.len(), 2);
+    }
+}
@@ -0,0 +1,97 @@
+use super::StockCalculator;
+use crate::models::{ExitProbability, StockAnalysis};
+use rand::Rng;
+
+impl StockCalculator {
+    /// 以零漂移几何布朗运动模拟未来 days 个交易日的收盘价，统计先到达目标价或止损价的概率。
+    /// daily_volatility 为日波动率（小数），只用收盘价判断，盘中触及不计；空头的目标价在下方
+    pub fn simulate_exit_probability<R: Rng>(
+        analysis: &StockAnalysis,
+        daily_volatility: f64,
+        days: usize,
+        simulations: usize,
+        rng: &mut R,
+    ) -> ExitProbability {
+        let (mut target_hits, mut stop_hits) = (0usize, 0usize);
+        let mut total_profit = 0.0;
+        let drift = -0.5 * daily_volatility * daily_volatility;
+        let sign = analysis.direction.sign();
+
+        for _ in 0..simulations {
+            let mut price = analysis.current_price;
+            let mut exit_price = None;
+
+            for _ in 0..days {
+                price *= (drift + daily_volatility * Self::standard_normal(rng)).exp();
+
+                if sign * (price - analysis.target_price) >= 0.0 {
+                    target_hits += 1;
+                    exit_price = Some(analysis.target_price);
+                    break;
+                }
+                if sign * (price - analysis.stop_loss_price) <= 0.0 {
+                    stop_hits += 1;
+                    exit_price = Some(analysis.stop_loss_price);
+                    break;
+                }
+            }
+
+            total_profit += sign * (exit_price.unwrap_or(price) - analysis.avg_price) * analysis.quantity;
+        }
+
+        let runs = simulations.max(1) as f64;
+        let target_probability = target_hits as f64 / runs * 100.0;
+        let stop_probability = stop_hits as f64 / runs * 100.0;
+
+        ExitProbability {
+            days,
+            simulations,
+            daily_volatility: daily_volatility * 100.0,
+            target_probability,
+            stop_probability,
+            neither_probability: 100.0 - target_probability - stop_probability,
+            expected_profit: total_profit / runs,
+        }
+    }
+
+    /// Box-Muller 变换生成标准正态分布随机数
+    fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen::<f64>();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StockData;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_simulate_exit_probability() {
+        // 目标 +10%，止损 -10%，当前价位于中间
+        let stock = StockData::new("000001".to_string(), 1000.0, 10.0, 1000.0, 1000.0);
+        let analysis = StockCalculator::calculate_analysis(&stock, 10.0);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let result = StockCalculator::simulate_exit_probability(&analysis, 0.02, 250, 4000, &mut rng);
+        let total = result.target_probability + result.stop_probability + result.neither_probability;
+        assert!((total - 100.0).abs() < 1e-9);
+        // 对称区间、零漂移时两边概率接近
+        assert!((result.target_probability - result.stop_probability).abs() < 8.0);
+        assert!(result.neither_probability < 5.0);
+        // 价格是鞅，期望盈亏接近当前浮盈 0
+        assert!(result.expected_profit.abs() < 50.0);
+
+        // 波动为0时两者都不会触及
+        let flat = StockCalculator::simulate_exit_probability(&analysis, 0.0, 20, 100, &mut rng);
+        assert_eq!(flat.neither_probability, 100.0);
+
+        // 当前价接近目标时，到达目标的概率更高
+        let near_target = StockCalculator::calculate_analysis(&stock, 10.8);
+        let result = StockCalculator::simulate_exit_probability(&near_target, 0.02, 60, 4000, &mut rng);
+        assert!(result.target_probability > result.stop_probability * 2.0);
+    }
+}
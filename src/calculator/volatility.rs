@@ -0,0 +1,107 @@
+use super::StockCalculator;
+use crate::config::VolatilityConfig;
+use crate::models::{Candle, PositionDirection, VolatilityStop};
+
+impl StockCalculator {
+    /// 最近 lookback 个交易日对数收益率的标准差（小数）
+    pub fn daily_volatility(candles: &[Candle], lookback: usize) -> Option<f64> {
+        let start = candles.len().saturating_sub(lookback + 1);
+        let returns: Vec<f64> = candles[start..]
+            .windows(2)
+            .filter(|pair| pair[0].close > 0.0 && pair[1].close > 0.0)
+            .map(|pair| (pair[1].close / pair[0].close).ln())
+            .collect();
+
+        if returns.len() < 2 {
+            return None;
+        }
+
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        Some(variance.sqrt())
+    }
+
+    /// 对比止损距离与 ATR / 日波动率，给出建议止损价和被正常波动触及的预计天数。
+    /// 空头的止损在当前价上方
+    pub fn calculate_volatility_stop(
+        direction: PositionDirection,
+        stop_loss_price: f64,
+        current_price: f64,
+        candles: &[Candle],
+        config: &VolatilityConfig,
+    ) -> Option<VolatilityStop> {
+        let atr = Self::atr(candles, config.atr_period).last().copied().flatten()?;
+        if atr <= 0.0 || current_price <= 0.0 {
+            return None;
+        }
+
+        let volatility = Self::daily_volatility(candles, config.lookback_days);
+        let distance = direction.sign() * (current_price - stop_loss_price);
+        let stop_distance_atr = distance / atr;
+
+        // 无漂移随机游走到达距离 d 的典型时间约为 (d / 日波动)^2
+        let expected_days_to_stop = volatility.filter(|v| *v > 0.0).map(|v| {
+            if distance <= 0.0 { 0.0 } else { (distance / (v * current_price)).powi(2) }
+        });
+
+        Some(VolatilityStop {
+            atr,
+            atr_percent: atr / current_price * 100.0,
+            daily_volatility: volatility.unwrap_or_default() * 100.0,
+            suggested_atr_multiple: config.suggested_atr_multiple,
+            suggested_stop_price: Self::stop_price_from_atr(direction, current_price, atr, config.suggested_atr_multiple),
+            stop_distance_atr,
+            too_tight: stop_distance_atr < config.min_stop_atr_multiple,
+            expected_days_to_stop,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_volatility_stop() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        // 收盘价在 9.9 和 10.1 之间交替，真实波幅约 0.3
+        let candles: Vec<Candle> = (0..40)
+            .map(|i| {
+                let close = if i % 2 == 0 { 9.9 } else { 10.1 };
+                Candle {
+                    date: start + chrono::Duration::days(i),
+                    open: close,
+                    close,
+                    high: close + 0.1,
+                    low: close - 0.1,
+                    volume: 0.0,
+                }
+            })
+            .collect();
+        let config = VolatilityConfig::default();
+
+        let tight = StockCalculator::calculate_volatility_stop(PositionDirection::Long, 9.8, 10.0, &candles, &config).unwrap();
+        assert!((tight.atr - 0.3).abs() < 0.01);
+        assert!(tight.too_tight);
+        assert!((tight.suggested_stop_price - 9.4).abs() < 0.02);
+        let tight_days = tight.expected_days_to_stop.unwrap();
+
+        let loose = StockCalculator::calculate_volatility_stop(PositionDirection::Long, 9.0, 10.0, &candles, &config).unwrap();
+        assert!(!loose.too_tight);
+        assert!((loose.stop_distance_atr - 3.33).abs() < 0.05);
+        // 距离扩大5倍，预计天数扩大25倍
+        assert!((loose.expected_days_to_stop.unwrap() / tight_days - 25.0).abs() < 1e-6);
+
+        assert!(StockCalculator::calculate_volatility_stop(PositionDirection::Long, 9.0, 10.0, &candles[..5], &config).is_none());
+
+        // 空头止损在上方：10.2 只有约 0.67 倍 ATR，建议止损 10.6
+        let short = StockCalculator::calculate_volatility_stop(PositionDirection::Short, 10.2, 10.0, &candles, &config).unwrap();
+        assert!(short.too_tight);
+        assert!(short.stop_distance_atr > 0.0);
+        assert!((short.suggested_stop_price - 10.6).abs() < 0.02);
+        let short = StockCalculator::calculate_volatility_stop(PositionDirection::Short, 11.0, 10.0, &candles, &config).unwrap();
+        assert!(!short.too_tight);
+        assert!((short.stop_distance_atr - 3.33).abs() < 0.05);
+    }
+}
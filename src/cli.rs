@@ -1,8 +1,10 @@
-use clap::{Parser, Subcommand};
-use crate::error::Result;
-use crate::models::StockData;
+use clap::{Args, Parser, Subcommand};
+use crate::error::{Result, StockCalcError};
+use crate::models::{
+    StockData, SimulatedTrade, TargetKind, TrailingKind, TrailingStop, ExitTier, CorporateAction, CorporateActionKind,
+    GridSpacing, DcaPlan, DcaFrequency, NearThreshold, PositionDirection, Financing, DEFAULT_PORTFOLIO,
+};
 use crate::config::AppConfig;
-use chrono::Utc;
 
 /// 解析逗号分隔的股票代码字符串为向量
 pub fn parse_stock_codes(code_str: &str) -> Vec<String> {
@@ -13,6 +15,226 @@ pub fn parse_stock_codes(code_str: &str) -> Vec<String> {
         .collect()
 }
 
+/// 解析 "数量@价格" 格式的模拟交易，例如 1000@12.5
+pub fn parse_trade(trade_str: &str) -> Result<SimulatedTrade> {
+    let (quantity, price) = trade_str
+        .split_once('@')
+        .ok_or_else(|| StockCalcError::ParseError(format!("交易格式应为 数量@价格: {}", trade_str)))?;
+
+    let quantity = quantity
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| StockCalcError::ParseError(format!("无效的交易数量: {}", trade_str)))?;
+    let price = price
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| StockCalcError::ParseError(format!("无效的交易价格: {}", trade_str)))?;
+
+    Ok(SimulatedTrade { quantity, price })
+}
+
+/// 解析 "比例@值" 格式的分批档位，值以 % 结尾表示相对成本的百分比，否则为价格，
+/// 例如 1/3@10% 或 0.5@12.8
+pub fn parse_tier(tier_str: &str) -> Result<ExitTier> {
+    let (fraction, value) = tier_str
+        .split_once('@')
+        .ok_or_else(|| StockCalcError::ParseError(format!("分批格式应为 比例@值: {}", tier_str)))?;
+
+    let fraction = match fraction.trim().split_once('/') {
+        Some((numerator, denominator)) => numerator.trim().parse::<f64>().ok()
+            .zip(denominator.trim().parse::<f64>().ok())
+            .map(|(n, d)| n / d),
+        None => fraction.trim().parse::<f64>().ok(),
+    }
+    .ok_or_else(|| StockCalcError::ParseError(format!("无效的分批比例: {}", tier_str)))?;
+
+    let value = value.trim();
+    let (value, kind) = match value.strip_suffix('%') {
+        Some(percent) => (percent, TargetKind::Percent),
+        None => (value, TargetKind::Price),
+    };
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| StockCalcError::ParseError(format!("无效的分批价格: {}", tier_str)))?;
+
+    Ok(ExitTier { fraction, value, kind, triggered: false })
+}
+
+/// 解析公司行为，数值沿用A股习惯：cash 为每10股派息（元），bonus 为每10股送转股数，
+/// split 为1股拆为几股
+pub fn parse_corporate_action(ex_date: &str, kind: &str, value: f64) -> Result<CorporateAction> {
+    let ex_date = chrono::NaiveDate::parse_from_str(ex_date.trim(), "%Y-%m-%d")
+        .map_err(|_| StockCalcError::ParseError(format!("无效的除权除息日(应为 YYYY-MM-DD): {}", ex_date)))?;
+
+    let kind = match kind.trim().to_lowercase().as_str() {
+        "cash" => CorporateActionKind::CashDividend { per_share: value / 10.0 },
+        "bonus" => CorporateActionKind::BonusShares { per_share: value / 10.0 },
+        "split" => CorporateActionKind::Split { ratio: value },
+        other => {
+            return Err(StockCalcError::ParseError(format!("无效的公司行为类型: {}（可选 cash/bonus/split）", other)));
+        }
+    };
+
+    Ok(CorporateAction { ex_date, kind, applied: false, cash_received: 0.0 })
+}
+
+/// 解析导入文件中的一行 "代码,除权除息日,类型,数值"，空行和注释返回 None
+pub fn parse_corporate_action_line(line: &str) -> Result<Option<(String, CorporateAction)>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with("code") {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+    if fields.len() != 4 {
+        return Err(StockCalcError::ParseError(format!("导入格式应为 代码,日期,类型,数值: {}", line)));
+    }
+
+    let value = fields[3]
+        .parse::<f64>()
+        .map_err(|_| StockCalcError::ParseError(format!("无效的数值: {}", line)))?;
+
+    let action = parse_corporate_action(fields[1], fields[2], value)?;
+    Ok(Some((fields[0].to_string(), action)))
+}
+
+/// 止盈/止损规则参数，calculate 和 backtest 共用
+#[derive(Args, Clone)]
+pub struct ExitRuleArgs {
+    /// 预期收益（金额、百分比或价格，见 --target-kind）
+    #[arg(short, long)]
+    pub target_profit: f64,
+
+    /// 最大亏损（金额、百分比或价格，见 --stop-kind）
+    #[arg(short, long)]
+    pub max_loss: f64,
+
+    /// 预期收益的设定方式: amount/percent/price
+    #[arg(long, default_value = "amount")]
+    pub target_kind: TargetKind,
+
+    /// 最大亏损的设定方式: amount/percent/price
+    #[arg(long, default_value = "amount")]
+    pub stop_kind: TargetKind,
+
+    /// 移动止损距离（百分比或价差，见 --trailing-kind）
+    #[arg(long)]
+    pub trailing_stop: Option<f64>,
+
+    /// 移动止损方式: percent/amount
+    #[arg(long, default_value = "percent")]
+    pub trailing_kind: TrailingKind,
+
+    /// 盈利达到该百分比后才启用移动止损
+    #[arg(long)]
+    pub trailing_activation: Option<f64>,
+
+    /// 分批止盈，格式为 比例@值（如 1/3@10% 或 0.5@12.8，可重复）
+    #[arg(long)]
+    pub take_profit_tier: Vec<String>,
+
+    /// 分批止损，格式同 --take-profit-tier（可重复）
+    #[arg(long)]
+    pub stop_loss_tier: Vec<String>,
+
+    /// 接近目标价的提示阈值，格式为 方式:值（percent:3、price:0.05 或 distance:20），默认使用全局配置
+    #[arg(long)]
+    pub near_target: Option<NearThreshold>,
+
+    /// 接近止损价的提示阈值，格式同 --near-target
+    #[arg(long)]
+    pub near_stop: Option<NearThreshold>,
+}
+
+impl ExitRuleArgs {
+    /// 按规则参数生成持仓数据
+    pub fn to_stock_data(&self, code: &str, quantity: f64, avg_price: f64) -> Result<StockData> {
+        Ok(StockData {
+            target_kind: self.target_kind,
+            stop_kind: self.stop_kind,
+            trailing_stop: self.trailing_stop.map(|distance| TrailingStop {
+                kind: self.trailing_kind,
+                distance,
+                activation_profit: self.trailing_activation,
+                high_water_mark: None,
+            }),
+            take_profit_tiers: self
+                .take_profit_tier
+                .iter()
+                .map(|t| parse_tier(t))
+                .collect::<Result<Vec<_>>>()?,
+            stop_loss_tiers: self
+                .stop_loss_tier
+                .iter()
+                .map(|t| parse_tier(t))
+                .collect::<Result<Vec<_>>>()?,
+            near_target: self.near_target,
+            near_stop_loss: self.near_stop,
+            ..StockData::new(code.to_string(), quantity, avg_price, self.target_profit, self.max_loss)
+        })
+    }
+}
+
+/// 持仓方向和融资融券参数
+#[derive(Args)]
+pub struct MarginArgs {
+    /// 持仓方向: long/short（short 为融券卖出）
+    #[arg(long, default_value = "long")]
+    pub direction: PositionDirection,
+
+    /// 融资金额，或融券卖出借入证券的市值（融券默认为持仓成本）
+    #[arg(long)]
+    pub borrowed: Option<f64>,
+
+    /// 融资利率/融券费率（年化 %，按 360 天计息）
+    #[arg(long, default_value = "0")]
+    pub interest_rate: f64,
+
+    /// 最低维持担保比例 (%)
+    #[arg(long, default_value = "130")]
+    pub maintenance_ratio: f64,
+
+    /// 本持仓之外的担保物价值（现金和证券）
+    #[arg(long)]
+    pub collateral: Option<f64>,
+
+    /// 融资/融券开始日期 (YYYY-MM-DD)，默认为今天
+    #[arg(long)]
+    pub financing_date: Option<String>,
+}
+
+impl MarginArgs {
+    /// 设置持仓方向；指定融资金额或融券卖出时记录负债。
+    /// 融券卖出所得已计入担保资产，担保物只计用户指定的部分
+    pub fn apply(&self, stock_data: StockData) -> Result<StockData> {
+        let proceeds = stock_data.quantity * stock_data.avg_price;
+        let borrowed = match self.direction {
+            PositionDirection::Long => self.borrowed,
+            PositionDirection::Short => Some(self.borrowed.unwrap_or(proceeds)),
+        };
+        let collateral = self.collateral.unwrap_or(0.0);
+
+        let start_date = match &self.financing_date {
+            Some(date) => chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| StockCalcError::ParseError(format!("无效的融资融券日期(应为 YYYY-MM-DD): {}", date)))?,
+            None => chrono::Local::now().date_naive(),
+        };
+
+        Ok(StockData {
+            direction: self.direction,
+            financing: borrowed.map(|borrowed_amount| Financing {
+                borrowed_amount,
+                interest_rate: self.interest_rate,
+                start_date,
+                maintenance_ratio: self.maintenance_ratio,
+                collateral,
+            }),
+            ..stock_data
+        })
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "stock-calc")]
 #[command(about = "股票收益计算器 - 命令行工具")]
@@ -38,17 +260,31 @@ pub enum Commands {
         #[arg(short, long)]
         avg_price: f64,
 
-        /// 预期收益
-        #[arg(short, long)]
-        target_profit: f64,
+        #[command(flatten)]
+        rules: ExitRuleArgs,
 
-        /// 最大亏损
-        #[arg(short, long)]
-        max_loss: f64,
+        #[command(flatten)]
+        margin: MarginArgs,
+
+        /// 蒙特卡洛估算先到达目标价或止损价的概率
+        #[arg(long, default_value = "false")]
+        probability: bool,
+
+        /// 模拟使用的日波动率 (%)，默认按历史K线计算
+        #[arg(long, requires = "probability")]
+        volatility: Option<f64>,
 
         /// 是否保存到数据库
         #[arg(short, long, default_value = "false")]
         save: bool,
+
+        /// 保存时将持仓记为一笔买入并扣减组合现金
+        #[arg(long, default_value = "false", requires = "save")]
+        record_buy: bool,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
     },
 
     /// 实时监控股票
@@ -64,6 +300,10 @@ pub enum Commands {
         /// 最大重试次数
         #[arg(short, long, default_value = "3")]
         retry: u32,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
     },
 
     /// 查看历史数据
@@ -71,6 +311,76 @@ pub enum Commands {
         /// 显示详细信息
         #[arg(short, long, default_value = "false")]
         detailed: bool,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 投资组合汇总
+    Portfolio {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+
+        /// 与比较基准对比的交易日数，默认使用配置
+        #[arg(long)]
+        period: Option<usize>,
+    },
+
+    /// 持仓集中度和行业分布
+    Exposure {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+
+        /// 统计前N大持仓的合计权重，默认使用配置
+        #[arg(long)]
+        top: Option<usize>,
+    },
+
+    /// 持仓日收益率相关性矩阵
+    Correlation {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+
+        /// 计算使用的交易日数，默认使用配置
+        #[arg(short, long)]
+        days: Option<usize>,
+
+        /// 高度相关的阈值，默认使用配置
+        #[arg(long)]
+        threshold: Option<f64>,
+    },
+
+    /// 收益表现 (XIRR / 时间加权收益率)
+    Performance {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+
+        /// 与比较基准对比的交易日数，默认使用配置
+        #[arg(long)]
+        period: Option<usize>,
+    },
+
+    /// 记录组合当日估值快照
+    Snapshot {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 查看净值曲线、最大回撤和最好/最差的一天
+    EquityCurve {
+        /// 只显示最近N天
+        #[arg(short, long)]
+        days: Option<usize>,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
     },
 
     /// 删除股票数据
@@ -78,6 +388,90 @@ pub enum Commands {
         /// 股票代码（支持多个，用逗号分隔）
         #[arg(short, long)]
         code: String,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 管理投资组合（账户）
+    Account {
+        #[command(subcommand)]
+        subcommand: AccountSubcommand,
+    },
+
+    /// 模拟补仓/加仓（不修改数据库）
+    Simulate {
+        /// 已保存的股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 模拟交易，格式为 数量@价格（可重复）
+        #[arg(short, long, required = true)]
+        trade: Vec<String>,
+
+        /// 当前价格（不填则获取实时价格）
+        #[arg(short, long)]
+        price: Option<f64>,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 根据风险预算计算仓位
+    Size {
+        /// 账户总资金
+        #[arg(short, long)]
+        equity: f64,
+
+        /// 单笔风险比例（%）
+        #[arg(short, long)]
+        risk_percent: f64,
+
+        /// 买入价格
+        #[arg(long)]
+        entry: f64,
+
+        /// 止损价格（与 --atr 二选一）
+        #[arg(long, required_unless_present = "atr", conflicts_with = "atr")]
+        stop: Option<f64>,
+
+        /// ATR 数值，按 ATR 倍数推算止损价格
+        #[arg(long)]
+        atr: Option<f64>,
+
+        /// ATR 倍数
+        #[arg(long, default_value = "2.0")]
+        atr_multiple: f64,
+
+        /// 每手股数
+        #[arg(long, default_value = "100")]
+        lot_size: f64,
+
+        /// 期望盈亏比
+        #[arg(long, default_value = "2.0")]
+        reward_ratio: f64,
+    },
+
+    /// 公司行为（分红、送转、拆股）
+    Action {
+        #[command(subcommand)]
+        subcommand: ActionSubcommand,
+
+        /// 投资组合名称
+        #[arg(long, global = true, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 定投计划
+    Dca {
+        #[command(subcommand)]
+        subcommand: DcaSubcommand,
+
+        /// 投资组合名称
+        #[arg(long, global = true, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
     },
 
     /// 交互式模式
@@ -89,6 +483,82 @@ pub enum Commands {
         subcommand: ConfigSubcommand,
     },
 
+    /// 网格交易计划（可实时监控穿越网格线）
+    Grid {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 网格下限价格
+        #[arg(long)]
+        lower: f64,
+
+        /// 网格上限价格
+        #[arg(long)]
+        upper: f64,
+
+        /// 网格数量
+        #[arg(short, long)]
+        grids: usize,
+
+        /// 每格买卖数量
+        #[arg(short, long)]
+        quantity: f64,
+
+        /// 网格方式: arithmetic（等差）/geometric（等比）
+        #[arg(long, default_value = "arithmetic")]
+        spacing: GridSpacing,
+
+        /// 手续费率 (%)
+        #[arg(long, default_value = "0.025")]
+        fee_rate: f64,
+
+        /// 单笔最低手续费
+        #[arg(long, default_value = "5")]
+        min_fee: f64,
+
+        /// 生成计划后实时监控，价格穿越网格线时提醒
+        #[arg(long, default_value = "false")]
+        monitor: bool,
+
+        /// 监控间隔（秒）
+        #[arg(short, long, default_value = "60")]
+        interval: u64,
+    },
+
+    /// 用历史日K线回测止盈/止损规则
+    Backtest {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 买入日期 (YYYY-MM-DD)，从下一个交易日开始回放
+        #[arg(short, long)]
+        entry_date: String,
+
+        /// 买入价格，默认使用买入日收盘价
+        #[arg(long)]
+        entry_price: Option<f64>,
+
+        /// 买入数量
+        #[arg(short, long)]
+        quantity: f64,
+
+        #[command(flatten)]
+        rules: ExitRuleArgs,
+    },
+
+    /// 技术指标 (MA/EMA/MACD/RSI/KDJ/布林带/ATR)
+    Indicators {
+        /// 股票代码（支持多个，用逗号分隔）
+        #[arg(short, long)]
+        code: String,
+
+        /// 获取的日K线数量
+        #[arg(short, long, default_value = "250")]
+        days: usize,
+    },
+
     /// 校验股票代码
     Test {
         /// 股票代码（支持多个，用逗号分隔）
@@ -109,6 +579,246 @@ pub enum ConfigSubcommand {
     Edit,
 }
 
+#[derive(Subcommand)]
+pub enum AccountSubcommand {
+    /// 创建投资组合
+    Create {
+        /// 组合名称
+        #[arg(short, long)]
+        name: String,
+
+        /// 初始现金
+        #[arg(short, long, default_value = "0")]
+        cash: f64,
+    },
+
+    /// 查看所有投资组合
+    List,
+
+    /// 入金
+    Deposit {
+        /// 金额
+        #[arg(short, long)]
+        amount: f64,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 出金
+    Withdraw {
+        /// 金额
+        #[arg(short, long)]
+        amount: f64,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 买入已保存的股票（加仓），扣减现金
+    Buy {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 数量
+        #[arg(short, long)]
+        quantity: f64,
+
+        /// 成交价格
+        #[arg(short, long)]
+        price: f64,
+
+        /// 手续费
+        #[arg(short, long, default_value = "0")]
+        fee: f64,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 卖出已保存的股票，收入计入现金
+    Sell {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 数量
+        #[arg(short, long)]
+        quantity: f64,
+
+        /// 成交价格
+        #[arg(short, long)]
+        price: f64,
+
+        /// 手续费
+        #[arg(short, long, default_value = "0")]
+        fee: f64,
+
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 查看现金流水
+    Ledger {
+        /// 投资组合名称
+        #[arg(long, default_value = DEFAULT_PORTFOLIO)]
+        portfolio: String,
+    },
+
+    /// 删除投资组合及其持仓
+    Delete {
+        /// 组合名称
+        #[arg(short, long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ActionSubcommand {
+    /// 手动录入公司行为
+    Add {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 除权除息日 (YYYY-MM-DD)
+        #[arg(short, long)]
+        ex_date: String,
+
+        /// 每10股派息（元）
+        #[arg(long)]
+        cash: Option<f64>,
+
+        /// 每10股送转股数
+        #[arg(long)]
+        bonus: Option<f64>,
+
+        /// 1股拆为几股
+        #[arg(long)]
+        split: Option<f64>,
+    },
+
+    /// 从 CSV 文件导入，每行格式为 代码,除权除息日,类型(cash/bonus/split),数值
+    Import {
+        /// 文件路径
+        #[arg(short, long)]
+        file: String,
+    },
+
+    /// 查看公司行为记录
+    List {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+    },
+}
+
+/// 定投计划参数，add 和 project 共用
+#[derive(Args, Clone)]
+pub struct DcaPlanArgs {
+    /// 股票代码
+    #[arg(short, long)]
+    pub code: String,
+
+    /// 每期投入金额
+    #[arg(short, long)]
+    pub amount: f64,
+
+    /// 定投频率: daily/weekly/biweekly/monthly
+    #[arg(short, long, default_value = "weekly")]
+    pub frequency: DcaFrequency,
+
+    /// 开始日期 (YYYY-MM-DD)
+    #[arg(short, long)]
+    pub start_date: String,
+
+    /// 每手股数
+    #[arg(long, default_value = "100")]
+    pub lot_size: f64,
+
+    /// 首次建仓时的止盈百分比
+    #[arg(long, default_value = "20")]
+    pub target_percent: f64,
+
+    /// 首次建仓时的止损百分比
+    #[arg(long, default_value = "10")]
+    pub stop_percent: f64,
+}
+
+impl DcaPlanArgs {
+    pub fn to_plan(&self) -> Result<DcaPlan> {
+        let start_date = chrono::NaiveDate::parse_from_str(self.start_date.trim(), "%Y-%m-%d")
+            .map_err(|_| StockCalcError::ParseError(format!("无效的开始日期(应为 YYYY-MM-DD): {}", self.start_date)))?;
+
+        Ok(DcaPlan {
+            code: self.code.clone(),
+            amount: self.amount,
+            frequency: self.frequency,
+            start_date,
+            lot_size: self.lot_size,
+            target_percent: self.target_percent,
+            stop_percent: self.stop_percent,
+            last_executed: None,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+pub enum DcaSubcommand {
+    /// 添加或替换定投计划
+    Add {
+        #[command(flatten)]
+        plan: DcaPlanArgs,
+    },
+
+    /// 查看定投计划
+    List,
+
+    /// 删除定投计划
+    Remove {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+    },
+
+    /// 按历史K线模拟定投的成本和收益
+    Project {
+        #[command(flatten)]
+        plan: DcaPlanArgs,
+    },
+
+    /// 执行到期的定投，按当前价格（或指定价格）记录买入
+    Execute {
+        /// 股票代码
+        #[arg(short, long)]
+        code: String,
+
+        /// 成交价格，默认使用当前价格
+        #[arg(long)]
+        price: Option<f64>,
+
+        /// 手续费
+        #[arg(long, default_value = "0")]
+        fee: f64,
+    },
+
+    /// 监控定投计划，到期时提醒
+    Monitor {
+        /// 检查间隔（秒）
+        #[arg(short, long, default_value = "600")]
+        interval: u64,
+
+        /// 到期时自动按当前价格记录买入
+        #[arg(long, default_value = "false")]
+        auto_record: bool,
+    },
+}
+
 pub struct InteractiveMode;
 
 impl InteractiveMode {
@@ -122,14 +832,7 @@ impl InteractiveMode {
         let target_profit = Self::prompt_number("请输入预期收益")?;
         let max_loss = Self::prompt_number("请输入最大亏损")?;
 
-        let stock_data = StockData {
-            code,
-            quantity,
-            avg_price,
-            target_profit,
-            max_loss,
-            last_updated: Utc::now(),
-        };
+        let stock_data = StockData::new(code, quantity, avg_price, target_profit, max_loss);
 
         // 验证输入
         crate::calculator::StockCalculator::validate_input(
@@ -146,7 +849,14 @@ impl InteractiveMode {
         let current_price = api.fetch_stock_price(&stock_data.code).await?;
 
         // 计算分析
-        let analysis = crate::calculator::StockCalculator::calculate_analysis(&stock_data, current_price);
+        let mut analysis = crate::calculator::StockCalculator::calculate_analysis_with_config(&stock_data, current_price, &config, None);
+        let advice_rules = config.load_advice_rules()?;
+        let info = if crate::calculator::StockCalculator::advice_needs_quote(&advice_rules) {
+            api.fetch_stock_info(&stock_data.code).await.ok()
+        } else {
+            None
+        };
+        analysis.advice = crate::calculator::StockCalculator::evaluate_advice(&advice_rules, &analysis, info.as_ref());
 
         // 显示结果
         let notifier = crate::notification::Notifier::new(config.clone());
@@ -156,7 +866,7 @@ impl InteractiveMode {
         let save = Self::prompt_yes_no("是否保存到数据库")?;
         if save {
             let storage = crate::storage::Storage::new(config);
-            storage.add_stock(stock_data)?;
+            storage.add_stock(DEFAULT_PORTFOLIO, stock_data)?;
             println!("✅ 数据已保存");
         }
 
@@ -207,7 +917,19 @@ pub fn print_help() {
     println!("  calculate    计算股票收益");
     println!("  monitor      实时监控股票");
     println!("  list         查看历史数据");
+    println!("  portfolio    投资组合汇总");
+    println!("  performance  收益表现 (XIRR / 时间加权收益率)");
+    println!("  snapshot     记录组合当日估值快照");
+    println!("  equity-curve 净值曲线与最大回撤");
     println!("  remove       删除股票数据");
+    println!("  account      管理投资组合（账户）");
+    println!("  simulate     模拟补仓/加仓");
+    println!("  action       公司行为（分红、送转、拆股）");
+    println!("  size         根据风险预算计算仓位");
+    println!("  grid         网格交易计划与监控");
+    println!("  dca          定投计划、模拟与提醒");
+    println!("  backtest     回测止盈/止损规则");
+    println!("  indicators   技术指标");
     println!("  test         校验股票代码");
     println!("  interactive  交互式模式");
     println!("  config       配置管理");
@@ -219,6 +941,12 @@ pub fn print_help() {
     println!("  # 实时监控");
     println!("  stock-calc monitor --code 000001 --interval 60");
     println!();
+    println!("  # 模拟补仓");
+    println!("  stock-calc simulate --code 000001 --trade 1000@12.5");
+    println!();
+    println!("  # 仓位计算");
+    println!("  stock-calc size --equity 100000 --risk-percent 1 --entry 10 --stop 9.5");
+    println!();
     println!("  # 校验股票代码");
     println!("  stock-calc test --code 000001");
     println!();
@@ -227,4 +955,60 @@ pub fn print_help() {
     println!();
     println!("  # 查看帮助");
     println!("  stock-calc --help");
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::StockCalculator;
+    use crate::models::StockStatus;
+
+    #[test]
+    fn test_size_stop_or_atr() {
+        let parse = |extra: &[&str]| {
+            let args = ["stock-calc", "size", "-e", "100000", "-r", "1", "--entry", "10"];
+            Cli::try_parse_from(args.iter().chain(extra))
+        };
+
+        assert!(parse(&["--stop", "9.5"]).is_ok());
+        assert!(parse(&["--atr", "0.3"]).is_ok());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--stop", "9.5", "--atr", "0.3"]).is_err());
+    }
+
+    #[test]
+    fn test_short_collateral() {
+        let margin = MarginArgs {
+            direction: PositionDirection::Short,
+            borrowed: None,
+            interest_rate: 0.0,
+            maintenance_ratio: 130.0,
+            collateral: None,
+            financing_date: None,
+        };
+        let stock_data = StockData {
+            target_kind: TargetKind::Price,
+            stop_kind: TargetKind::Price,
+            ..StockData::new("000001".to_string(), 1000.0, 10.0, 8.0, 11.0)
+        };
+        // 未指定担保物时只计卖出所得 10000，维持担保比例 100% 低于 130%，需要提示
+        let short = margin.apply(stock_data.clone()).unwrap();
+        let analysis = StockCalculator::calculate_analysis(&short, 10.0);
+        assert!((analysis.financing.unwrap().maintenance_ratio - 100.0).abs() < 1e-9);
+        assert!(matches!(analysis.status, StockStatus::MaintenanceWarning));
+
+        // 卖出所得 + 担保物 20000，买回市值 10000，维持担保比例 200%
+        let margin = MarginArgs { collateral: Some(10000.0), ..margin };
+        let short = margin.apply(stock_data).unwrap();
+        let analysis = StockCalculator::calculate_analysis(&short, 10.0);
+        let financing = analysis.financing.as_ref().unwrap();
+        assert!((financing.maintenance_ratio - 200.0).abs() < 1e-9);
+        assert!(!matches!(analysis.status, StockStatus::MaintenanceWarning));
+        assert!(matches!(StockCalculator::calculate_analysis(&short, 7.9).status, StockStatus::AtTarget));
+
+        // 上涨约 54% 后跌破 130%
+        assert!(matches!(StockCalculator::calculate_analysis(&short, 15.5).status, StockStatus::AtStopLoss));
+        let financing = StockCalculator::calculate_analysis(&short, 15.5).financing.unwrap();
+        assert!(financing.maintenance_ratio < 130.0);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::Result;
 use config::{Config, Environment, File};
+use crate::models::{AdviceRuleSet, NearThreshold, SectorMap};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -8,6 +9,26 @@ pub struct AppConfig {
     pub general: GeneralConfig,
     pub api: ApiConfig,
     pub display: DisplayConfig,
+    #[serde(default)]
+    pub benchmark: BenchmarkConfig,
+    #[serde(default)]
+    pub volatility: VolatilityConfig,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+    #[serde(default)]
+    pub status: StatusConfig,
+    #[serde(default)]
+    pub scale: ScaleConfig,
+    #[serde(default)]
+    pub risk_grading: RiskGradingConfig,
+    #[serde(default)]
+    pub advice: AdviceConfig,
+    #[serde(default)]
+    pub holding_cost: HoldingCostConfig,
+    #[serde(default)]
+    pub concentration: ConcentrationConfig,
+    #[serde(default)]
+    pub correlation: CorrelationConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +52,205 @@ pub struct DisplayConfig {
     pub show_progress: bool,
 }
 
+/// 业绩比较基准
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkConfig {
+    pub code: String,
+    pub name: String,
+    /// 默认比较的交易日数
+    pub period_days: usize,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            code: "sh000300".to_string(),
+            name: "沪深300".to_string(),
+            period_days: 250,
+        }
+    }
+}
+
+/// 基于 ATR 和历史波动率的止损参考
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolatilityConfig {
+    pub atr_period: usize,
+    /// 计算日波动率使用的交易日数
+    pub lookback_days: usize,
+    /// 建议止损距离为 ATR 的倍数
+    pub suggested_atr_multiple: f64,
+    /// 止损距离小于该 ATR 倍数时提示过紧
+    pub min_stop_atr_multiple: f64,
+}
+
+impl Default for VolatilityConfig {
+    fn default() -> Self {
+        Self {
+            atr_period: 14,
+            lookback_days: 60,
+            suggested_atr_multiple: 2.0,
+            min_stop_atr_multiple: 1.0,
+        }
+    }
+}
+
+/// 蒙特卡洛模拟参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// 模拟的交易日数
+    pub days: usize,
+    /// 模拟路径数
+    pub simulations: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            days: 20,
+            simulations: 5000,
+        }
+    }
+}
+
+/// 持仓状态判断，单个持仓设置的阈值优先
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatusConfig {
+    pub near_target: NearThreshold,
+    pub near_stop_loss: NearThreshold,
+}
+
+/// 投资规模分档，加载时按 below 从小到大排序，取第一个满足 投入 < below（inclusive 时为 <=）的档位，最后一档不设上限
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleConfig {
+    /// 为 true 时 below 表示占账户权益的百分比，否则为金额。
+    /// 权益按成本计算（现金 + 已保存持仓的成本 - 融资负债），不请求行情；未保存的持仓按加入前的权益计算占比
+    pub relative_to_equity: bool,
+    pub buckets: Vec<ScaleBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleBucket {
+    pub below: Option<f64>,
+    /// 为 true 时恰好等于 below 也属于该档
+    #[serde(default)]
+    pub inclusive: bool,
+    pub label: String,
+}
+
+impl Default for ScaleConfig {
+    fn default() -> Self {
+        Self {
+            relative_to_equity: false,
+            buckets: vec![
+                ScaleBucket { below: Some(10_000.0), inclusive: false, label: "小额".to_string() },
+                ScaleBucket { below: Some(100_000.0), inclusive: true, label: "中等".to_string() },
+                ScaleBucket { below: None, inclusive: false, label: "大额".to_string() },
+            ],
+        }
+    }
+}
+
+/// 风险收益比评级，加载时按 min_ratio 从大到小排序，取第一个满足 比值 >= min_ratio 的档位，都不满足时取最后一档
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskGradingConfig {
+    pub grades: Vec<RiskGrade>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskGrade {
+    pub min_ratio: f64,
+    pub label: String,
+}
+
+impl Default for RiskGradingConfig {
+    fn default() -> Self {
+        Self {
+            grades: vec![
+                RiskGrade { min_ratio: 3.0, label: "优秀".to_string() },
+                RiskGrade { min_ratio: 2.0, label: "良好".to_string() },
+                RiskGrade { min_ratio: 1.5, label: "一般".to_string() },
+                RiskGrade { min_ratio: 0.0, label: "较差".to_string() },
+            ],
+        }
+    }
+}
+
+/// 操作建议规则
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdviceConfig {
+    /// 规则文件路径，未设置时使用配置目录下的 advice.toml，不存在则使用内置规则
+    pub rules_file: Option<PathBuf>,
+}
+
+/// 盈亏平衡和持有成本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldingCostConfig {
+    /// 无风险年化利率 (%)，用于计算资金占用的机会成本
+    pub risk_free_rate: f64,
+    /// 平仓费率 (%)，含佣金和印花税
+    pub exit_fee_rate: f64,
+    /// 单笔最低平仓费用
+    pub min_exit_fee: f64,
+}
+
+impl Default for HoldingCostConfig {
+    fn default() -> Self {
+        Self {
+            risk_free_rate: 2.0,
+            exit_fee_rate: 0.075,
+            min_exit_fee: 5.0,
+        }
+    }
+}
+
+/// 持仓集中度和行业分布
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcentrationConfig {
+    /// 行业映射文件，未设置时使用配置目录下的 sectors.toml，文件中的分类优先于行情接口
+    pub sector_file: Option<PathBuf>,
+    /// 映射文件中没有的股票是否从行情接口获取所属行业
+    pub fetch_sector: bool,
+    /// 统计前N大持仓的合计权重
+    pub top_n: usize,
+    /// 单只股票占权益的上限 (%)
+    pub max_stock_weight: f64,
+    /// 单个行业占权益的上限 (%)
+    pub max_sector_weight: f64,
+}
+
+impl Default for ConcentrationConfig {
+    fn default() -> Self {
+        Self {
+            sector_file: None,
+            fetch_sector: true,
+            top_n: 5,
+            max_stock_weight: 20.0,
+            max_sector_weight: 40.0,
+        }
+    }
+}
+
+/// 持仓相关性
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationConfig {
+    /// 计算日收益率使用的交易日数
+    pub lookback_days: usize,
+    /// 相关系数不低于该值时视为高度相关
+    pub high_threshold: f64,
+    /// 共同交易日收益率少于该数量时不计算
+    pub min_observations: usize,
+}
+
+impl Default for CorrelationConfig {
+    fn default() -> Self {
+        Self {
+            lookback_days: 120,
+            high_threshold: 0.8,
+            min_observations: 20,
+        }
+    }
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -49,6 +269,16 @@ impl Default for AppConfig {
                 color_output: true,
                 show_progress: true,
             },
+            benchmark: BenchmarkConfig::default(),
+            volatility: VolatilityConfig::default(),
+            simulation: SimulationConfig::default(),
+            status: StatusConfig::default(),
+            scale: ScaleConfig::default(),
+            risk_grading: RiskGradingConfig::default(),
+            advice: AdviceConfig::default(),
+            holding_cost: HoldingCostConfig::default(),
+            concentration: ConcentrationConfig::default(),
+            correlation: CorrelationConfig::default(),
         }
     }
 }
@@ -62,10 +292,33 @@ impl AppConfig {
             .add_source(Environment::with_prefix("STOCK_CALC"))
             .build()?;
 
-        let app_config: AppConfig = config.try_deserialize()?;
+        let mut app_config: AppConfig = config.try_deserialize()?;
+        app_config.normalize()?;
         Ok(app_config)
     }
 
+    /// 按阈值排序分档，使查找结果与配置文件中的书写顺序无关：
+    /// 规模分档按 below 从小到大（不设 below 的一档在最后），评级按 min_ratio 从大到小。
+    /// 分档列表为空时报错
+    pub fn normalize(&mut self) -> Result<()> {
+        if self.scale.buckets.is_empty() {
+            return Err(config::ConfigError::Message("scale.buckets 不能为空".to_string()).into());
+        }
+        if self.risk_grading.grades.is_empty() {
+            return Err(config::ConfigError::Message("risk_grading.grades 不能为空".to_string()).into());
+        }
+
+        self.scale.buckets.sort_by(|a, b| match (a.below, b.below) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        self.risk_grading.grades.sort_by(|a, b| b.min_ratio.total_cmp(&a.min_ratio));
+
+        Ok(())
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::get_config_path()?;
         
@@ -99,8 +352,72 @@ impl AppConfig {
         Ok(home_dir.join(".config").join("stock-calc").join("data.json"))
     }
 
+    /// 加载并校验建议规则，显式指定的规则文件不存在时报错
+    pub fn load_advice_rules(&self) -> Result<AdviceRuleSet> {
+        let path = match &self.advice.rules_file {
+            Some(path) => path.clone(),
+            None => {
+                let path = Self::get_config_path()?.with_file_name("advice.toml");
+                if !path.exists() {
+                    return Ok(AdviceRuleSet::default());
+                }
+                path
+            }
+        };
+
+        let content = std::fs::read_to_string(&path)?;
+        let rules: AdviceRuleSet = toml::from_str(&content)
+            .map_err(|e| crate::error::StockCalcError::ParseError(format!("{}: {}", path.display(), e)))?;
+        crate::calculator::StockCalculator::validate_advice_rules(&rules)?;
+
+        Ok(rules)
+    }
+
+    /// 加载行业映射，显式指定的映射文件不存在时报错
+    pub fn load_sector_map(&self) -> Result<SectorMap> {
+        let path = match &self.concentration.sector_file {
+            Some(path) => path.clone(),
+            None => {
+                let path = Self::get_config_path()?.with_file_name("sectors.toml");
+                if !path.exists() {
+                    return Ok(SectorMap::default());
+                }
+                path
+            }
+        };
+
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content)
+            .map_err(|e| crate::error::StockCalcError::ParseError(format!("{}: {}", path.display(), e)))
+    }
+
     pub fn create_default_config() -> Result<()> {
         let config = AppConfig::default();
         config.save()
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let mut config: AppConfig = toml::from_str(&toml::to_string(&AppConfig::default()).unwrap()).unwrap();
+        config.scale.buckets.reverse();
+        config.risk_grading.grades.reverse();
+        config.normalize().unwrap();
+
+        let below: Vec<Option<f64>> = config.scale.buckets.iter().map(|b| b.below).collect();
+        assert_eq!(below, vec![Some(10_000.0), Some(100_000.0), None]);
+        let ratios: Vec<f64> = config.risk_grading.grades.iter().map(|g| g.min_ratio).collect();
+        assert_eq!(ratios, vec![3.0, 2.0, 1.5, 0.0]);
+        assert_eq!(crate::calculator::StockCalculator::get_risk_level(2.5, &config.risk_grading), "良好");
+
+        config.risk_grading.grades.clear();
+        assert!(matches!(config.normalize(), Err(crate::error::StockCalcError::ConfigError(_))));
+        let mut config = AppConfig::default();
+        config.scale.buckets.clear();
+        assert!(config.normalize().is_err());
+    }
+}
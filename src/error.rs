@@ -52,6 +52,51 @@ pub enum ValidationError {
 
     #[error("最大亏损必须为正数")]
     InvalidMaxLoss,
+
+    #[error("风险比例必须在0到100之间")]
+    InvalidRiskPercent,
+
+    #[error("止损价格必须为正数且低于买入价格")]
+    InvalidStopPrice,
+
+    #[error("移动止损距离必须为正数，按百分比回撤时须小于100")]
+    InvalidTrailingStop,
+
+    #[error("风险预算不足以买入一手")]
+    InsufficientRiskBudget,
+
+    #[error("分批比例必须在0到1之间且合计不超过1")]
+    InvalidTier,
+
+    #[error("公司行为数值必须为正数")]
+    InvalidCorporateAction,
+
+    #[error("现金余额不足")]
+    InsufficientCash,
+
+    #[error("历史K线数据不足")]
+    InsufficientHistory,
+
+    #[error("网格参数无效：价格区间需上限高于下限，格数、每格数量须为正数")]
+    InvalidGrid,
+
+    #[error("定投金额和每手股数必须为正数")]
+    InvalidDcaPlan,
+
+    #[error("建议规则无效")]
+    InvalidAdviceRule,
+
+    #[error("融资融券参数无效：负债金额和维持担保比例须为正数，利率和担保物不能为负")]
+    InvalidFinancing,
+
+    #[error("融券持仓暂不支持记录买卖流水")]
+    ShortPositionTrade,
+
+    #[error("组合中已有该股票持仓，加仓请使用 account buy")]
+    PositionExists,
+
+    #[error("组合不存在，请先使用 account create 创建")]
+    PortfolioNotFound,
 }
 
 pub type Result<T> = std::result::Result<T, StockCalcError>; 
\ No newline at end of file
@@ -11,8 +11,7 @@ use clap::Parser;
 use crate::error::Result;
 use crate::cli::{Cli, Commands, InteractiveMode};
 use crate::config::AppConfig;
-use crate::models::StockData;
-use chrono::Utc;
+use crate::models::{StockData, CashTransaction, TransactionKind};
 use std::time::Duration;
 
 #[tokio::main]
@@ -30,17 +29,55 @@ async fn main() -> Result<()> {
     });
 
     match cli.command {
-        Commands::Calculate { code, quantity, avg_price, target_profit, max_loss, save } => {
-            handle_calculate(&config, &code, quantity, avg_price, target_profit, max_loss, save).await?;
+        Commands::Calculate { code, quantity, avg_price, rules, margin, probability, volatility, save, record_buy, portfolio } => {
+            let template = margin.apply(rules.to_stock_data("", quantity, avg_price)?)?;
+            if template.direction == crate::models::PositionDirection::Short && margin.collateral.is_none() {
+                println!("💡 未指定 --collateral，融券担保资产只计卖出所得");
+            }
+            let probability = probability.then_some(volatility);
+            handle_calculate(&config, &portfolio, &code, &template, probability, save, record_buy).await?;
+        }
+        Commands::Monitor { code, interval, retry, portfolio } => {
+            handle_monitor(&config, &portfolio, &code, interval, retry).await?;
+        }
+        Commands::List { detailed, portfolio } => {
+            handle_list(&config, &portfolio, detailed).await?;
+        }
+        Commands::Portfolio { portfolio, period } => {
+            handle_portfolio(&config, &portfolio, period).await?;
+        }
+        Commands::Exposure { portfolio, top } => {
+            handle_exposure(&config, &portfolio, top).await?;
+        }
+        Commands::Correlation { portfolio, days, threshold } => {
+            handle_correlation(&config, &portfolio, days, threshold).await?;
+        }
+        Commands::Performance { portfolio, period } => {
+            handle_performance(&config, &portfolio, period).await?;
+        }
+        Commands::Snapshot { portfolio } => {
+            handle_snapshot(&config, &portfolio).await?;
         }
-        Commands::Monitor { code, interval, retry } => {
-            handle_monitor(&config, &code, interval, retry).await?;
+        Commands::EquityCurve { days, portfolio } => {
+            handle_equity_curve(&config, &portfolio, days)?;
         }
-        Commands::List { detailed } => {
-            handle_list(&config, detailed).await?;
+        Commands::Remove { code, portfolio } => {
+            handle_remove(&config, &portfolio, &code).await?;
         }
-        Commands::Remove { code } => {
-            handle_remove(&config, &code).await?;
+        Commands::Account { subcommand } => {
+            handle_account(&config, subcommand).await?;
+        }
+        Commands::Simulate { code, trade, price, portfolio } => {
+            handle_simulate(&config, &portfolio, &code, &trade, price).await?;
+        }
+        Commands::Size { equity, risk_percent, entry, stop, atr, atr_multiple, lot_size, reward_ratio } => {
+            handle_size(&config, equity, risk_percent, entry, stop, atr, atr_multiple, lot_size, reward_ratio).await?;
+        }
+        Commands::Action { subcommand, portfolio } => {
+            handle_action(&config, &portfolio, subcommand).await?;
+        }
+        Commands::Dca { subcommand, portfolio } => {
+            handle_dca(&config, &portfolio, subcommand).await?;
         }
         Commands::Interactive => {
             InteractiveMode::run().await?;
@@ -48,6 +85,25 @@ async fn main() -> Result<()> {
         Commands::Config { subcommand } => {
             handle_config(&config, subcommand).await?;
         }
+        Commands::Grid { code, lower, upper, grids, quantity, spacing, fee_rate, min_fee, monitor, interval } => {
+            let plan = crate::models::GridPlan {
+                code,
+                lower_price: lower,
+                upper_price: upper,
+                grid_count: grids,
+                spacing,
+                quantity_per_grid: quantity,
+                fee_rate,
+                min_fee,
+            };
+            handle_grid(&config, &plan, monitor.then_some(interval)).await?;
+        }
+        Commands::Backtest { code, entry_date, entry_price, quantity, rules } => {
+            handle_backtest(&config, &code, &entry_date, entry_price, quantity, &rules).await?;
+        }
+        Commands::Indicators { code, days } => {
+            handle_indicators(&config, &code, days).await?;
+        }
         Commands::Test { code } => {
             handle_test(&config, &code).await?;
         }
@@ -56,14 +112,15 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// `template` 为除股票代码外的持仓设置，对每个代码复用
 async fn handle_calculate(
     config: &AppConfig,
+    portfolio: &str,
     code: &str,
-    quantity: f64,
-    avg_price: f64,
-    target_profit: f64,
-    max_loss: f64,
+    template: &StockData,
+    probability: Option<Option<f64>>,
     save: bool,
+    record_buy: bool,
 ) -> Result<()> {
     let stock_codes = crate::cli::parse_stock_codes(code);
     
@@ -71,35 +128,69 @@ async fn handle_calculate(
         return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
     }
 
+    if record_buy && template.direction == crate::models::PositionDirection::Short {
+        return Err(crate::error::StockCalcError::ValidationError(
+            crate::error::ValidationError::ShortPositionTrade.to_string()
+        ));
+    }
+
     let api = crate::api::StockApi::new(config.clone())?;
     let storage = crate::storage::Storage::new(config.clone());
     let notifier = crate::notification::Notifier::new(config.clone());
+    let equity = book_equity(&storage, portfolio)?;
+    let advice_rules = config.load_advice_rules()?;
+    let ledger = storage
+        .portfolio(portfolio)?
+        .map(|p| p.ledger)
+        .unwrap_or_default();
 
     for (i, stock_code) in stock_codes.iter().enumerate() {
         if i > 0 {
             println!("{}", "━".repeat(50));
         }
 
-        // 验证输入
-        crate::calculator::StockCalculator::validate_input(
-            stock_code, quantity, avg_price, target_profit, max_loss,
-        )?;
-
         // 创建股票数据
-        let stock_data = StockData {
+        let mut stock_data = StockData {
             code: stock_code.clone(),
-            quantity,
-            avg_price,
-            target_profit,
-            max_loss,
-            last_updated: Utc::now(),
+            ..template.clone()
         };
 
+        // 验证输入
+        crate::calculator::StockCalculator::validate_stock_data(&stock_data)?;
+
+        // 记录买入只用于建仓，覆盖已有持仓会丢失已实现盈亏、公司行为和止盈档位；
+        // 已清仓的记录可以重新建仓，累计的已实现收益保留
+        if save && record_buy {
+            if let Some(existing) = storage.get_stock(portfolio, stock_code)? {
+                if !existing.is_closed() {
+                    return Err(crate::error::StockCalcError::ValidationError(
+                        crate::error::ValidationError::PositionExists.to_string()
+                    ));
+                }
+                stock_data.realized_profit = existing.realized_profit;
+            }
+        }
+
         // 获取实时价格
         let current_price = api.fetch_stock_price(stock_code).await?;
+        crate::calculator::StockCalculator::update_trailing_stop(&mut stock_data, current_price);
 
         // 计算分析
-        let analysis = crate::calculator::StockCalculator::calculate_analysis(&stock_data, current_price);
+        let mut analysis = crate::calculator::StockCalculator::calculate_analysis_with_config(&stock_data, current_price, config, equity);
+        if let Some(since) = crate::calculator::StockCalculator::holding_since(&ledger, stock_code) {
+            analysis.holding_cost = crate::calculator::StockCalculator::analyze_holding_cost(
+                &stock_data,
+                Some(since),
+                &config.holding_cost,
+                chrono::Local::now().date_naive(),
+            );
+        }
+        let candles = fetch_volatility_candles(&api, config, stock_code).await;
+        attach_volatility(config, &mut analysis, candles.as_deref());
+        if let Some(volatility) = probability {
+            attach_probability(config, &mut analysis, candles.as_deref(), volatility);
+        }
+        attach_advice(&api, &advice_rules, &mut analysis).await;
 
         // 显示结果
         notifier.print_analysis(&analysis);
@@ -108,9 +199,26 @@ async fn handle_calculate(
         notifier.check_alerts(&analysis).await?;
 
         // 保存到数据库
-        if save {
-            storage.add_stock(stock_data)?;
-            println!("✅ 数据已保存到数据库");
+        if save && record_buy {
+            // 融资买入只扣减自有资金部分
+            let borrowed = stock_data.financing.as_ref().map_or(0.0, |f| f.borrowed_amount);
+            let cost = stock_data.quantity * stock_data.avg_price - borrowed;
+            ensure_cash(&storage, portfolio, cost)?;
+
+            let transaction = CashTransaction::trade(
+                chrono::Local::now().date_naive(),
+                TransactionKind::Buy,
+                stock_code,
+                stock_data.quantity,
+                stock_data.avg_price,
+                0.0,
+                -cost,
+            );
+            storage.update_stock_with_transactions(portfolio, stock_data, vec![transaction])?;
+            println!("✅ 数据已保存到组合 {}，扣减现金 ¥{:.2}", portfolio, cost);
+        } else if save {
+            storage.add_stock(portfolio, stock_data)?;
+            println!("✅ 数据已保存到组合 {}", portfolio);
         }
     }
 
@@ -119,6 +227,7 @@ async fn handle_calculate(
 
 async fn handle_monitor(
     config: &AppConfig,
+    portfolio: &str,
     code: &str,
     interval: u64,
     retry: u32,
@@ -132,16 +241,24 @@ async fn handle_monitor(
     let api = crate::api::StockApi::new(config.clone())?;
     let storage = crate::storage::Storage::new(config.clone());
     let notifier = crate::notification::Notifier::new(config.clone());
+    let equity = book_equity(&storage, portfolio)?;
+    let advice_rules = config.load_advice_rules()?;
 
-    // 获取所有股票数据
-    let mut stock_data_map = std::collections::HashMap::new();
+    // 确认股票数据存在，日K线只在启动时获取一次用于波动参考
+    let mut candles_map = std::collections::HashMap::new();
     for stock_code in &stock_codes {
-        match storage.get_stock(stock_code)? {
-            Some(data) => {
-                stock_data_map.insert(stock_code.clone(), data);
+        match storage.get_stock(portfolio, stock_code)? {
+            Some(stock) if stock.is_closed() => {
+                println!("📭 组合 {} 中股票 {} 已清仓，无需监控", portfolio, stock_code);
+                return Ok(());
+            }
+            Some(_) => {
+                if let Some(candles) = fetch_volatility_candles(&api, config, stock_code).await {
+                    candles_map.insert(stock_code.clone(), candles);
+                }
             }
             None => {
-                println!("❌ 未找到股票 {} 的数据，请先使用 calculate 命令添加", stock_code);
+                println!("❌ 组合 {} 中未找到股票 {} 的数据，请先使用 calculate 命令添加", portfolio, stock_code);
                 return Ok(());
             }
         }
@@ -151,6 +268,9 @@ async fn handle_monitor(
     println!("{}", "━".repeat(50));
 
     let mut interval_timer = tokio::time::interval(Duration::from_secs(interval));
+    let mut last_snapshot = storage
+        .portfolio(portfolio)?
+        .and_then(|p| p.snapshots.last().map(|s| s.date));
 
     loop {
         interval_timer.tick().await;
@@ -165,9 +285,29 @@ async fn handle_monitor(
 
             match api.fetch_stock_price(stock_code).await {
                 Ok(current_price) => {
-                    let stock_data = &stock_data_map[stock_code];
-                    let analysis = crate::calculator::StockCalculator::calculate_analysis(stock_data, current_price);
-                    
+                    // 每次都读取最新记录，监控期间其他命令（加仓、卖出、公司行为）的修改立即生效
+                    let mut stock_data = match storage.get_stock(portfolio, stock_code)? {
+                        Some(data) if data.is_closed() => {
+                            println!("📭 {} 已清仓", stock_code);
+                            continue;
+                        }
+                        Some(data) => data,
+                        None => {
+                            println!("❌ 组合 {} 中已没有股票 {} 的数据", portfolio, stock_code);
+                            continue;
+                        }
+                    };
+                    let stock_data = &mut stock_data;
+                    let today = chrono::Local::now().date_naive();
+
+                    // 处理到期的公司行为，并更新移动止损最高价
+                    let applied_actions = crate::calculator::StockCalculator::apply_due_corporate_actions(stock_data, today);
+                    let trailing_changed = crate::calculator::StockCalculator::update_trailing_stop(stock_data, current_price);
+
+                    let mut analysis = crate::calculator::StockCalculator::calculate_analysis_with_config(stock_data, current_price, config, equity);
+                    attach_volatility(config, &mut analysis, candles_map.get(stock_code).map(Vec::as_slice));
+                    attach_advice(&api, &advice_rules, &mut analysis).await;
+
                     // 显示实时状态
                     println!("📈 {}: {} ({})", 
                         stock_code,
@@ -180,33 +320,189 @@ async fn handle_monitor(
                     );
                     println!("📊 距离目标: {:.2}% | 距离止损: {:.2}%", 
                         analysis.distance_to_target, analysis.distance_to_stop_loss);
+                    if let Some(volatility) = analysis.volatility.as_ref().filter(|v| v.too_tight) {
+                        println!("⚠️  止损距离仅 {:.2}×ATR，建议止损 ¥{:.3}",
+                            volatility.stop_distance_atr, volatility.suggested_stop_price);
+                    }
+                    for advice in analysis.advice.iter().filter(|a| a.severity != crate::models::AdviceSeverity::Info) {
+                        println!("💡 {}", advice.text);
+                    }
 
                     // 检查提醒
                     notifier.check_alerts(&analysis).await?;
+
+                    // 标记已触发的分批档位，只把最高价、触发标记和公司行为合并到最新记录中
+                    let tiers_changed = crate::calculator::StockCalculator::mark_triggered_tiers(stock_data, current_price);
+                    if !applied_actions.is_empty() || trailing_changed || tiers_changed {
+                        storage.update_stock_with(portfolio, stock_code, |stored| {
+                            let applied = crate::calculator::StockCalculator::merge_monitor_state(stored, stock_data, today);
+                            dividend_transactions(stock_code, &applied)
+                        })?;
+                    }
                 }
                 Err(e) => {
                     println!("❌ {} 获取价格失败: {}", stock_code, e);
                 }
             }
         }
+
+        // 收盘后每天自动记录一次组合快照
+        let now = chrono::Local::now();
+        let today = now.date_naive();
+        if is_after_market_close(now.naive_local()) && last_snapshot != Some(today) {
+            match take_portfolio_snapshot(&api, &storage, portfolio, today).await {
+                Ok(Some(snapshot)) => {
+                    println!("📸 已记录组合 {} 收盘快照: 总权益 {}",
+                        portfolio,
+                        crate::calculator::StockCalculator::format_currency(snapshot.equity)
+                    );
+                    last_snapshot = Some(today);
+                }
+                Ok(None) => {}
+                Err(e) => println!("❌ 记录收盘快照失败: {}", e),
+            }
+        }
         println!();
     }
 }
 
-async fn handle_list(config: &AppConfig, detailed: bool) -> Result<()> {
+/// 获取计算波动参考所需的日K线，失败时只记录日志
+async fn fetch_volatility_candles(
+    api: &crate::api::StockApi,
+    config: &AppConfig,
+    stock_code: &str,
+) -> Option<Vec<crate::models::Candle>> {
+    let count = config.volatility.atr_period.max(config.volatility.lookback_days) + 1;
+    match api.fetch_daily_candles(stock_code, count).await {
+        Ok(candles) => Some(candles),
+        Err(e) => {
+            log::warn!("{} 获取K线失败，跳过波动参考: {}", stock_code, e);
+            None
+        }
+    }
+}
+
+fn attach_volatility(
+    config: &AppConfig,
+    analysis: &mut crate::models::StockAnalysis,
+    candles: Option<&[crate::models::Candle]>,
+) {
+    analysis.volatility = candles.and_then(|candles| {
+        crate::calculator::StockCalculator::calculate_volatility_stop(
+            analysis.direction,
+            analysis.stop_loss_price,
+            analysis.current_price,
+            candles,
+            &config.volatility,
+        )
+    });
+}
+
+/// 按建议规则生成操作建议，规则用到行情字段时额外获取实时行情
+async fn attach_advice(
+    api: &crate::api::StockApi,
+    rules: &crate::models::AdviceRuleSet,
+    analysis: &mut crate::models::StockAnalysis,
+) {
+    let info = if crate::calculator::StockCalculator::advice_needs_quote(rules) {
+        api.fetch_stock_info(&analysis.code).await.ok()
+    } else {
+        None
+    };
+    analysis.advice = crate::calculator::StockCalculator::evaluate_advice(rules, analysis, info.as_ref());
+}
+
+/// 蒙特卡洛估算止盈/止损概率，未指定日波动率 (%) 时按历史K线计算
+fn attach_probability(
+    config: &AppConfig,
+    analysis: &mut crate::models::StockAnalysis,
+    candles: Option<&[crate::models::Candle]>,
+    volatility: Option<f64>,
+) {
+    let daily_volatility = volatility.map(|v| v / 100.0).or_else(|| {
+        candles.and_then(|candles| {
+            crate::calculator::StockCalculator::daily_volatility(candles, config.volatility.lookback_days)
+        })
+    });
+
+    match daily_volatility {
+        Some(daily_volatility) => {
+            analysis.probability = Some(crate::calculator::StockCalculator::simulate_exit_probability(
+                analysis,
+                daily_volatility,
+                config.simulation.days,
+                config.simulation.simulations,
+                &mut rand::thread_rng(),
+            ));
+        }
+        None => println!("⚠️  无法获取历史波动率，请使用 --volatility 指定日波动率"),
+    }
+}
+
+fn is_after_market_close(now: chrono::NaiveDateTime) -> bool {
+    use chrono::{Datelike, Timelike, Weekday};
+
+    !matches!(now.weekday(), Weekday::Sat | Weekday::Sun) && now.hour() >= crate::models::MARKET_CLOSE_HOUR
+}
+
+/// 获取组合内所有持仓的价格并保存快照，任一价格获取失败时不保存
+async fn take_portfolio_snapshot(
+    api: &crate::api::StockApi,
+    storage: &crate::storage::Storage,
+    portfolio: &str,
+    date: chrono::NaiveDate,
+) -> Result<Option<crate::models::DailySnapshot>> {
+    let portfolio_data = match storage.get_portfolio(portfolio)? {
+        Some(portfolio_data) => portfolio_data,
+        None => return Ok(None),
+    };
+
+    let mut prices = std::collections::HashMap::new();
+    let open_codes = portfolio_data.stocks.values().filter(|stock| !stock.is_closed()).map(|stock| &stock.code);
+    for code in open_codes {
+        match api.fetch_stock_price(code).await {
+            Ok(price) => {
+                prices.insert(code.clone(), price);
+            }
+            Err(e) => {
+                println!("❌ {} 获取价格失败，未记录快照: {}", code, e);
+                return Ok(None);
+            }
+        }
+    }
+
+    let snapshot = crate::calculator::StockCalculator::take_snapshot(&portfolio_data, &prices, date);
+    storage.record_snapshot(portfolio, snapshot.clone())?;
+    Ok(Some(snapshot))
+}
+
+async fn handle_list(config: &AppConfig, portfolio: &str, detailed: bool) -> Result<()> {
     let storage = crate::storage::Storage::new(config.clone());
-    let stocks = storage.list_stocks()?;
+    let stocks = storage.list_stocks(portfolio)?;
+    let ledger = storage
+        .portfolio(portfolio)?
+        .map(|p| p.ledger)
+        .unwrap_or_default();
+    let today = chrono::Local::now().date_naive();
 
     if stocks.is_empty() {
-        println!("📭 数据库中没有股票数据");
+        println!("📭 组合 {} 中没有股票数据", portfolio);
         println!("💡 使用 'stock-calc calculate' 或 'stock-calc interactive' 添加数据");
         return Ok(());
     }
 
-    println!("📋 股票列表 (共 {} 只)", stocks.len());
+    println!("📋 {} 股票列表 (共 {} 只)", portfolio, stocks.len());
     println!("{}", "━".repeat(50));
 
     for stock in stocks {
+        if stock.is_closed() {
+            println!("📭 {} - 已清仓，已实现收益 ¥{:.3}", stock.code, stock.realized_profit);
+            if detailed {
+                println!();
+            }
+            continue;
+        }
+
         println!("📈 {} - {} 股 @ ¥{:.3}", 
             stock.code, 
             stock.quantity,
@@ -214,8 +510,72 @@ async fn handle_list(config: &AppConfig, detailed: bool) -> Result<()> {
         );
         
         if detailed {
-            println!("   预期收益: ¥{:.3}", stock.target_profit);
-            println!("   最大亏损: ¥{:.3}", stock.max_loss);
+            if stock.direction == crate::models::PositionDirection::Short {
+                println!("   持仓方向: 融券卖空");
+            }
+            println!("   预期收益: {}", crate::calculator::StockCalculator::format_target_spec(stock.target_profit, stock.target_kind));
+            println!("   最大亏损: {}", crate::calculator::StockCalculator::format_target_spec(stock.max_loss, stock.stop_kind));
+            for (i, tier) in stock.take_profit_tiers.iter().enumerate() {
+                println!("   止盈第{}档: {} 卖出 {:.0}%{}",
+                    i + 1,
+                    crate::calculator::StockCalculator::format_target_spec(tier.value, tier.kind),
+                    tier.fraction * 100.0,
+                    if tier.triggered { " (已触发)" } else { "" }
+                );
+            }
+            for (i, tier) in stock.stop_loss_tiers.iter().enumerate() {
+                println!("   止损第{}档: {} 卖出 {:.0}%{}",
+                    i + 1,
+                    crate::calculator::StockCalculator::format_target_spec(tier.value, tier.kind),
+                    tier.fraction * 100.0,
+                    if tier.triggered { " (已触发)" } else { "" }
+                );
+            }
+            if stock.realized_profit != 0.0 {
+                println!("   已实现收益: ¥{:.3}", stock.realized_profit);
+            }
+            for action in &stock.corporate_actions {
+                println!("   公司行为: {} {}{}",
+                    action.ex_date,
+                    crate::calculator::StockCalculator::format_corporate_action(&action.kind),
+                    if action.applied { "" } else { " (待除权)" }
+                );
+            }
+            if let Some(trailing) = &stock.trailing_stop {
+                println!("   移动止损: {}{}",
+                    crate::calculator::StockCalculator::format_trailing_stop(trailing),
+                    trailing.high_water_mark
+                        .map(|mark| match stock.direction {
+                            crate::models::PositionDirection::Long => format!(" (最高价 ¥{:.3})", mark),
+                            crate::models::PositionDirection::Short => format!(" (最低价 ¥{:.3})", mark),
+                        })
+                        .unwrap_or_default()
+                );
+            }
+            if let Some(financing) = &stock.financing {
+                println!("   {}: ¥{:.2} 年化 {:.2}% 自 {} 起，维持担保比例不低于 {:.0}%{}",
+                    match stock.direction {
+                        crate::models::PositionDirection::Long => "融资",
+                        crate::models::PositionDirection::Short => "融券",
+                    },
+                    financing.borrowed_amount,
+                    financing.interest_rate,
+                    financing.start_date,
+                    financing.maintenance_ratio,
+                    if financing.collateral > 0.0 { format!("，其他担保物 ¥{:.2}", financing.collateral) } else { String::new() }
+                );
+            }
+            let holding_cost = crate::calculator::StockCalculator::analyze_holding_cost(
+                &stock,
+                crate::calculator::StockCalculator::holding_since(&ledger, &stock.code),
+                &config.holding_cost,
+                today,
+            );
+            if let Some(holding_cost) = &holding_cost {
+                for line in crate::notification::Notifier::format_holding_cost_lines(holding_cost, config.holding_cost.risk_free_rate) {
+                    println!("   {}", line);
+                }
+            }
             println!("   最后更新: {}", stock.last_updated.format("%Y-%m-%d %H:%M:%S"));
             println!();
         }
@@ -228,115 +588,1580 @@ async fn handle_list(config: &AppConfig, detailed: bool) -> Result<()> {
     Ok(())
 }
 
-async fn handle_remove(config: &AppConfig, code: &str) -> Result<()> {
-    let stock_codes = crate::cli::parse_stock_codes(code);
-    
-    if stock_codes.is_empty() {
-        return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
+/// 读取组合持仓并逐只获取行情，获取失败的持仓不计入。
+/// 组合不存在、没有持仓或没有任何行情时打印提示并返回 None
+async fn load_holdings(
+    config: &AppConfig,
+    api: &crate::api::StockApi,
+    portfolio: &str,
+) -> Result<Option<(crate::models::Portfolio, Vec<(StockData, crate::models::StockInfo)>)>> {
+    let storage = crate::storage::Storage::new(config.clone());
+    let portfolio_data = match storage.get_portfolio(portfolio)? {
+        Some(portfolio_data) => portfolio_data,
+        None => {
+            println!("❌ 未找到组合 {}", portfolio);
+            println!("💡 使用 'stock-calc account list' 查看所有组合");
+            return Ok(None);
+        }
+    };
+
+    // 已清仓的持仓不获取行情
+    let open_stocks: Vec<&StockData> = portfolio_data.stocks.values().filter(|stock| !stock.is_closed()).collect();
+    if open_stocks.is_empty() {
+        println!("📭 组合 {} 中没有股票数据", portfolio);
+        println!("💡 使用 'stock-calc calculate --save' 添加持仓");
+        return Ok(None);
     }
 
-    let storage = crate::storage::Storage::new(config.clone());
-    
-    for stock_code in stock_codes {
-        match storage.remove_stock(&stock_code)? {
-            Some(_) => println!("✅ 已删除股票 {} 的数据", stock_code),
-            None => println!("❌ 未找到股票 {} 的数据", stock_code),
+    let mut holdings = Vec::new();
+    for stock in open_stocks {
+        match api.fetch_stock_info(&stock.code).await {
+            Ok(info) => holdings.push((stock.clone(), info)),
+            Err(e) => println!("❌ {} 获取行情失败，未计入统计: {}", stock.code, e),
         }
     }
 
+    if holdings.is_empty() {
+        println!("❌ 未能获取任何持仓的行情");
+        return Ok(None);
+    }
+
+    Ok(Some((portfolio_data, holdings)))
+}
+
+async fn handle_portfolio(config: &AppConfig, portfolio: &str, period: Option<usize>) -> Result<()> {
+    let api = crate::api::StockApi::new(config.clone())?;
+    let (portfolio_data, holdings) = match load_holdings(config, &api, portfolio).await? {
+        Some(loaded) => loaded,
+        None => return Ok(()),
+    };
+
+    let mut summary = crate::calculator::StockCalculator::calculate_portfolio_summary(
+        &holdings,
+        portfolio_data.cash,
+        portfolio_data.net_deposits(),
+        config,
+    );
+    summary.realized_profit += portfolio_data.closed_realized_profit();
+    print_portfolio_summary(portfolio, &summary);
+
+    let positions: Vec<(String, f64)> = holdings
+        .iter()
+        .map(|(stock, _)| (stock.code.clone(), stock.direction.sign() * stock.quantity))
+        .collect();
+    print_benchmark_comparison(config, &api, &positions, period).await;
+
     Ok(())
 }
 
-async fn handle_config(config: &AppConfig, subcommand: crate::cli::ConfigSubcommand) -> Result<()> {
-    match subcommand {
-        crate::cli::ConfigSubcommand::Show => {
-            println!("📋 当前配置:");
-            println!("{}", "━".repeat(30));
-            println!("默认间隔: {} 秒", config.general.default_interval);
-            println!("启用通知: {}", config.general.enable_notifications);
-            println!("启用声音: {}", config.general.enable_sound);
-            println!("日志级别: {}", config.general.log_level);
-            println!("API超时: {} 秒", config.api.timeout);
-            println!("重试次数: {}", config.api.retry_count);
-            println!("彩色输出: {}", config.display.color_output);
-            println!("显示进度: {}", config.display.show_progress);
-        }
-        crate::cli::ConfigSubcommand::Reset => {
-            let default_config = AppConfig::default();
-            default_config.save()?;
-            println!("✅ 配置已重置为默认值");
+async fn handle_exposure(config: &AppConfig, portfolio: &str, top: Option<usize>) -> Result<()> {
+    let api = crate::api::StockApi::new(config.clone())?;
+    let (portfolio_data, holdings) = match load_holdings(config, &api, portfolio).await? {
+        Some(loaded) => loaded,
+        None => return Ok(()),
+    };
+
+    let sector_map = config.load_sector_map()?;
+    let mut sectors = std::collections::HashMap::new();
+    for (stock, _) in &holdings {
+        // 映射文件优先，其次从行情接口获取
+        let sector = match crate::calculator::StockCalculator::lookup_sector(&sector_map, &stock.code) {
+            Some(sector) => Some(sector.to_string()),
+            None if config.concentration.fetch_sector => match api.fetch_sector(&stock.code).await {
+                Ok(sector) => sector,
+                Err(e) => {
+                    println!("⚠️  {} 获取行业失败: {}", stock.code, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        if let Some(sector) = sector {
+            sectors.insert(stock.code.clone(), sector);
         }
-        crate::cli::ConfigSubcommand::Edit => {
-            println!("💡 请手动编辑配置文件:");
-            match AppConfig::get_config_path() {
-                Ok(path) => println!("   {}", path.display()),
-                Err(_) => println!("   无法获取配置文件路径"),
-            }
+    }
+
+    let summary = crate::calculator::StockCalculator::calculate_portfolio_summary(
+        &holdings,
+        portfolio_data.cash,
+        portfolio_data.net_deposits(),
+        config,
+    );
+    let concentration = crate::config::ConcentrationConfig {
+        top_n: top.unwrap_or(config.concentration.top_n),
+        ..config.concentration.clone()
+    };
+    let report = crate::calculator::StockCalculator::calculate_exposure(&summary, &sectors, &concentration);
+
+    println!("🧭 组合 {} 集中度 (总权益 ¥{:.2})", portfolio, report.equity);
+    println!("{}", "━".repeat(50));
+
+    println!("🏭 行业分布");
+    for sector in &report.sectors {
+        println!("   {:<8} {:>7.2}% {} ¥{:.2} ({})",
+            sector.sector,
+            sector.weight,
+            weight_bar(sector.weight),
+            sector.market_value,
+            sector.codes.join(", ")
+        );
+    }
+    println!("   {:<8} {:>7.2}%", "现金", report.cash_weight);
+
+    println!("\n📊 持仓权重");
+    for position in &report.positions {
+        println!("   {} {} [{}] {:>7.2}% {}",
+            position.code,
+            position.name,
+            position.sector,
+            position.weight,
+            weight_bar(position.weight)
+        );
+    }
+    println!("   前{}大持仓合计: {:.2}%", report.top_n, report.top_n_weight);
+
+    if report.warnings.is_empty() {
+        println!("\n✅ 单只股票和单个行业均未超过上限 ({:.0}% / {:.0}%)",
+            concentration.max_stock_weight,
+            concentration.max_sector_weight
+        );
+    } else {
+        println!("\n⚠️  集中度提示");
+        for warning in &report.warnings {
+            println!("   {} {} 占权益 {:.2}%，超过上限 {:.0}%",
+                match warning.kind {
+                    crate::models::ConcentrationKind::Stock => "股票",
+                    crate::models::ConcentrationKind::Sector => "行业",
+                },
+                warning.name,
+                warning.weight,
+                warning.limit
+            );
         }
     }
 
     Ok(())
 }
 
-async fn handle_test(config: &AppConfig, code: &str) -> Result<()> {
-    let stock_codes = crate::cli::parse_stock_codes(code);
-    
-    if stock_codes.is_empty() {
-        return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
+async fn handle_correlation(config: &AppConfig, portfolio: &str, days: Option<usize>, threshold: Option<f64>) -> Result<()> {
+    use colored::Colorize;
+
+    let storage = crate::storage::Storage::new(config.clone());
+    // 已全部卖出的记录不参与计算
+    let mut holdings: Vec<(String, crate::models::PositionDirection)> = storage
+        .list_stocks(portfolio)?
+        .into_iter()
+        .filter(|stock| !stock.is_closed())
+        .map(|stock| (stock.code, stock.direction))
+        .collect();
+    holdings.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if holdings.len() < 2 {
+        println!("📭 组合 {} 中的持仓少于两只，无法计算相关性", portfolio);
+        return Ok(());
     }
 
+    let correlation = crate::config::CorrelationConfig {
+        lookback_days: days.unwrap_or(config.correlation.lookback_days),
+        high_threshold: threshold.unwrap_or(config.correlation.high_threshold),
+        ..config.correlation.clone()
+    };
+
     let api = crate::api::StockApi::new(config.clone())?;
-    
-    for (i, stock_code) in stock_codes.iter().enumerate() {
-        if i > 0 {
-            println!("{}", "━".repeat(50));
+    let mut series = Vec::new();
+    for (code, direction) in holdings {
+        match fetch_cached_candles(&api, &storage, &code, correlation.lookback_days + 1).await {
+            Ok(candles) => series.push((code, direction, crate::calculator::StockCalculator::candle_closes(&candles))),
+            Err(e) => println!("❌ {} 获取K线失败，未计入相关性: {}", code, e),
         }
+    }
 
-        // 尝试获取股票信息
-        match api.fetch_stock_info(stock_code).await {
-            Ok(stock_info) => {
-                println!("✅ 股票代码 {} 可查", stock_code);
-                println!("{}", "━".repeat(50));
-                println!("📈 股票名称: {}", stock_info.name);
-                println!("🔢 股票代码: {}", stock_info.code);
-                println!("💰 当前价格: ¥{:.3}", stock_info.current_price);
-                println!("📊 涨跌金额: ¥{:.3}", stock_info.change_amount);
-                println!("📈 涨跌幅度: {:.2}%", stock_info.change_percent);
-                println!("📅 昨收价格: ¥{:.3}", stock_info.yesterday_close);
-                println!("🌅 开盘价格: ¥{:.3}", stock_info.open_price);
-                println!("📈 最高价格: ¥{:.3}", stock_info.high_price);
-                println!("📉 最低价格: ¥{:.3}", stock_info.low_price);
-                println!("📊 成交量: {} 手", stock_info.volume / 100);
-                println!("💵 成交额: ¥{:.2} 万", stock_info.turnover / 10000.0);
+    let matrix = crate::calculator::StockCalculator::calculate_correlation_matrix(&series, &correlation);
+
+    println!("🔗 组合 {} 日收益率相关性 (最近 {} 个交易日)", portfolio, correlation.lookback_days);
+    let shorts: Vec<&str> = series
+        .iter()
+        .filter(|(_, direction, _)| *direction == crate::models::PositionDirection::Short)
+        .map(|(code, _, _)| code.as_str())
+        .collect();
+    if !shorts.is_empty() {
+        println!("   融券持仓按反向收益计算: {}", shorts.join(", "));
+    }
+    println!("{}", "━".repeat(50));
+
+    print!("{:>10}", "");
+    for code in &matrix.codes {
+        print!(" {:>8}", code.chars().take(8).collect::<String>());
+    }
+    println!();
+
+    for (code, row) in matrix.codes.iter().zip(&matrix.values) {
+        print!("{:>10}", code);
+        for value in row {
+            let cell = match value {
+                Some(value) => format!("{:>8.2}", value),
+                None => format!("{:>8}", "-"),
+            };
+            // 颜色越暖相关性越高，高度相关的格子加底色
+            let cell = match value {
+                _ if !config.display.color_output => cell.normal(),
+                Some(value) if *value >= matrix.threshold => cell.white().on_red().bold(),
+                Some(value) if *value >= 0.5 => cell.red(),
+                Some(value) if *value >= 0.2 => cell.yellow(),
+                Some(value) if *value <= -0.2 => cell.cyan(),
+                _ => cell.dimmed(),
+            };
+            print!(" {}", cell);
+        }
+        println!();
+    }
+
+    if matrix.high_pairs.is_empty() {
+        println!("\n✅ 没有相关系数超过 {:.2} 的持仓组合", matrix.threshold);
+    } else {
+        println!("\n⚠️  高度相关的持仓 (≥ {:.2})，分散效果有限", matrix.threshold);
+        for pair in &matrix.high_pairs {
+            println!("   {} ↔ {}: {:.2} ({} 个交易日)", pair.first, pair.second, pair.correlation, pair.observations);
+        }
+    }
+
+    Ok(())
+}
+
+/// 当天已缓存足够的日K线时直接使用，否则重新获取并更新缓存；获取失败时退回到旧缓存
+async fn fetch_cached_candles(
+    api: &crate::api::StockApi,
+    storage: &crate::storage::Storage,
+    code: &str,
+    count: usize,
+) -> Result<Vec<crate::models::Candle>> {
+    let now = chrono::Local::now().naive_local();
+    let cached = storage.load_candle_cache()?.entries.remove(code);
+    if let Some(entry) = cached.as_ref().filter(|entry| entry.covers(count, now)) {
+        return Ok(entry.latest(count));
+    }
+
+    match api.fetch_daily_candles(code, count).await {
+        Ok(candles) => {
+            if let Err(e) = storage.save_candles(code, &candles, count, now) {
+                log::warn!("{} 保存K线缓存失败: {}", code, e);
             }
-            Err(_) => {
-                println!("❌ 请重新填写正确的股票代码: {}", stock_code);
+            Ok(candles)
+        }
+        Err(e) => stale_candles(code, cached, count, e),
+    }
+}
+
+/// 获取失败时退回到过期的缓存，没有缓存时返回原错误
+fn stale_candles(
+    code: &str,
+    cached: Option<crate::models::CachedCandles>,
+    count: usize,
+    error: crate::error::StockCalcError,
+) -> Result<Vec<crate::models::Candle>> {
+    match cached {
+        Some(entry) => {
+            println!("⚠️  {} 获取K线失败，使用 {} 缓存的数据: {}", code, entry.fetched_at.format("%Y-%m-%d %H:%M"), error);
+            Ok(entry.latest(count))
+        }
+        None => Err(error),
+    }
+}
+
+/// 权重条形图，每格 5%
+fn weight_bar(weight: f64) -> String {
+    "█".repeat((weight.abs() / 5.0).round() as usize)
+}
+
+fn print_portfolio_summary(portfolio: &str, summary: &crate::models::PortfolioSummary) {
+    use crate::calculator::StockCalculator;
+
+    println!("💼 投资组合 {} (共 {} 只)", portfolio, summary.positions.len());
+    println!("{}", "━".repeat(50));
+
+    for position in &summary.positions {
+        let analysis = &position.analysis;
+        println!("📈 {} {} - {} 股 @ ¥{:.3}",
+            analysis.code, position.name, analysis.quantity, analysis.current_price
+        );
+        println!("   市值: ¥{:.2} | 权重: {:.2}% | 盈亏: ¥{:.2} ({:+.2}%) | 今日: ¥{:+.2}",
+            position.market_value,
+            position.weight,
+            analysis.current_profit,
+            analysis.profit_ratio,
+            position.day_change
+        );
+        println!("   止损价: ¥{:.3} | 止损风险: ¥{:.2}",
+            analysis.stop_loss_price, position.risk_to_stop
+        );
+    }
+
+    println!("{}", "─".repeat(30));
+    println!("🏦 总权益: {} (现金 {} | 仓位 {:.2}%)",
+        StockCalculator::format_currency(summary.equity),
+        StockCalculator::format_currency(summary.cash),
+        summary.invested_percent
+    );
+    if summary.net_deposits > 0.0 {
+        println!("📈 累计收益: {} ({:+.2}%，净入金 {})",
+            StockCalculator::format_currency(summary.total_return),
+            summary.total_return_percent,
+            StockCalculator::format_currency(summary.net_deposits)
+        );
+    }
+    println!("💰 总市值: {}", StockCalculator::format_currency(summary.market_value));
+    println!("💵 总成本: {}", StockCalculator::format_currency(summary.cost));
+    println!("📊 浮动盈亏: {} ({:+.2}%)",
+        StockCalculator::format_currency(summary.unrealized_profit),
+        summary.unrealized_profit_ratio
+    );
+    if summary.realized_profit != 0.0 {
+        println!("💵 已实现收益: {}", StockCalculator::format_currency(summary.realized_profit));
+    }
+    println!("📅 今日涨跌: {} ({:+.2}%)",
+        StockCalculator::format_currency(summary.day_change),
+        summary.day_change_percent
+    );
+    println!("⚠️  止损风险: {} (占市值 {:.2}%)",
+        StockCalculator::format_currency(summary.risk_to_stop),
+        summary.risk_percent
+    );
+}
+
+/// 输出每只持仓和按当前持仓合成的组合相对比较基准的表现，行情获取失败时只提示不中断。
+/// positions 中的数量按方向取符号，融券持仓只单独列出，不计入合成的组合
+async fn print_benchmark_comparison(
+    config: &AppConfig,
+    api: &crate::api::StockApi,
+    positions: &[(String, f64)],
+    period: Option<usize>,
+) {
+    use crate::calculator::StockCalculator;
+
+    let period = period.unwrap_or(config.benchmark.period_days).max(1);
+    let benchmark = match api.fetch_daily_candles(&config.benchmark.code, period + 1).await {
+        Ok(candles) => StockCalculator::candle_closes(&candles),
+        Err(e) => {
+            println!("❌ 获取比较基准 {} 行情失败: {}", config.benchmark.name, e);
+            return;
+        }
+    };
+
+    println!();
+    println!("🆚 对比 {} ({}, 近{}个交易日)", config.benchmark.name, config.benchmark.code, period);
+    println!("{}", "─".repeat(30));
+
+    let print_line = |label: &str, comparison: &crate::models::BenchmarkComparison| {
+        println!("   {} {:+.2}% vs {:+.2}% | 超额 {:+.2}% | alpha {:+.2}% | beta {:.2}",
+            label,
+            comparison.period_return,
+            comparison.benchmark_return,
+            comparison.excess_return,
+            comparison.alpha,
+            comparison.beta
+        );
+    };
+
+    let mut holdings = Vec::new();
+    let mut shorts = Vec::new();
+    for (code, quantity) in positions.iter().filter(|(_, quantity)| *quantity != 0.0) {
+        match api.fetch_daily_candles(code, period + 1).await {
+            Ok(candles) => {
+                let closes = StockCalculator::candle_closes(&candles);
+                match StockCalculator::calculate_benchmark_comparison(&closes, &benchmark) {
+                    Some(comparison) => print_line(code, &comparison),
+                    None => println!("   {} 与基准没有足够的共同交易日", code),
+                }
+                if *quantity > 0.0 {
+                    holdings.push((*quantity, closes));
+                } else {
+                    shorts.push(code.as_str());
+                }
             }
+            Err(e) => println!("   ❌ {} 获取K线失败: {}", code, e),
         }
     }
 
-    Ok(())
+    if holdings.len() > 1 {
+        let series = StockCalculator::combine_value_series(&holdings);
+        if let Some(comparison) = StockCalculator::calculate_benchmark_comparison(&series, &benchmark) {
+            print_line("组合(按当前持仓)", &comparison);
+        }
+    }
+    if !shorts.is_empty() {
+        println!("   💡 融券持仓 {} 未计入组合对比", shorts.join(", "));
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+async fn handle_grid(config: &AppConfig, plan: &crate::models::GridPlan, monitor_interval: Option<u64>) -> Result<()> {
+    use crate::calculator::StockCalculator;
 
-    #[tokio::test]
-    async fn test_calculate_command() {
-        let config = AppConfig::default();
-        let result = handle_calculate(
-            &config,
-            "000001",
-            1000.0,
-            15.5,
-            5000.0,
-            2000.0,
-            false,
-        ).await;
-        
-        // 这个测试可能会因为网络问题失败，所以我们只检查函数能正常执行
-        // 实际测试中应该使用模拟的API
-        assert!(result.is_ok() || result.is_err());
+    let analysis = StockCalculator::calculate_grid(plan)?;
+    let api = crate::api::StockApi::new(config.clone())?;
+    let current_price = match api.fetch_stock_price(&plan.code).await {
+        Ok(price) => Some(price),
+        Err(e) if monitor_interval.is_none() => {
+            println!("⚠️  {} 获取当前价格失败: {}", plan.code, e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+
+    println!("🕸️  网格计划: {} ¥{:.3} ~ ¥{:.3}，{} 格，每格 {} 股",
+        plan.code, plan.lower_price, plan.upper_price, plan.grid_count, plan.quantity_per_grid
+    );
+    println!("{}", "━".repeat(50));
+    for level in analysis.levels.iter().rev() {
+        let marker = match current_price {
+            Some(price) if price >= level.buy_price && price < level.sell_price => " ◀ 当前",
+            _ => "",
+        };
+        println!("   第{:>2}格 买 ¥{:.3} → 卖 ¥{:.3} | 每格收益 {} ({:+.2}%){}",
+            level.index,
+            level.buy_price,
+            level.sell_price,
+            StockCalculator::format_currency(level.profit),
+            level.profit_percent,
+            marker
+        );
+    }
+    println!("{}", "─".repeat(30));
+    println!("💰 所需资金: {} (从上限跌到下限买满所有格，含手续费)",
+        StockCalculator::format_currency(analysis.capital_required)
+    );
+    println!("📊 每格收益: {} ~ {}",
+        StockCalculator::format_currency(analysis.min_grid_profit),
+        StockCalculator::format_currency(analysis.max_grid_profit)
+    );
+    if analysis.min_grid_profit <= 0.0 {
+        println!("⚠️  部分网格扣除手续费后不赚钱，请减少格数或增加每格数量");
+    }
+    if let Some(price) = current_price {
+        println!("📈 当前价格: ¥{:.3}", price);
+        if price < plan.lower_price || price > plan.upper_price {
+            println!("⚠️  当前价格不在网格区间内");
+        }
+    }
+
+    let (interval, mut previous) = match (monitor_interval, current_price) {
+        (Some(interval), Some(price)) => (interval, price),
+        _ => return Ok(()),
+    };
+
+    let notifier = crate::notification::Notifier::new(config.clone());
+    println!();
+    println!("🔄 网格监控: {} (每{}秒更新)", plan.code, interval);
+
+    let mut interval_timer = tokio::time::interval(Duration::from_secs(interval));
+    interval_timer.tick().await;
+
+    loop {
+        interval_timer.tick().await;
+
+        match api.fetch_stock_price(&plan.code).await {
+            Ok(price) => {
+                println!("⏰ {} ¥{:.3}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), price);
+                for crossing in StockCalculator::detect_grid_crossings(&analysis.lines, previous, price) {
+                    let level = &analysis.levels[crossing.level - 1];
+                    notifier.send_grid_alert(&plan.code, &crossing, level, price).await?;
+                }
+                previous = price;
+            }
+            Err(e) => println!("❌ {} 获取价格失败: {}", plan.code, e),
+        }
+    }
+}
+
+async fn handle_backtest(
+    config: &AppConfig,
+    code: &str,
+    entry_date: &str,
+    entry_price: Option<f64>,
+    quantity: f64,
+    rules: &crate::cli::ExitRuleArgs,
+) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let entry_date = chrono::NaiveDate::parse_from_str(entry_date.trim(), "%Y-%m-%d")
+        .map_err(|_| crate::error::StockCalcError::ParseError(format!("无效的买入日期(应为 YYYY-MM-DD): {}", entry_date)))?;
+
+    // 按自然日估算需要的K线数量，多取一些覆盖买入日
+    let today = chrono::Local::now().date_naive();
+    let count = ((today - entry_date).num_days().max(0) as usize) + 10;
+    let api = crate::api::StockApi::new(config.clone())?;
+    let candles = api.fetch_daily_candles(code, count).await?;
+
+    let entry_price = match entry_price {
+        Some(price) => price,
+        None => candles
+            .iter()
+            .rev()
+            .find(|c| c.date <= entry_date)
+            .map(|c| c.close)
+            .ok_or_else(|| crate::error::StockCalcError::ValidationError(
+                crate::error::ValidationError::InsufficientHistory.to_string()
+            ))?,
+    };
+
+    let stock_data = rules.to_stock_data(code, quantity, entry_price)?;
+    let result = StockCalculator::backtest(&stock_data, entry_date, &candles)?;
+
+    println!("🔁 回测: {} 自 {} 买入 {} 股 @ ¥{:.3}", result.code, result.entry_date, result.quantity, result.entry_price);
+    println!("{}", "━".repeat(50));
+    println!("🎯 目标价: ¥{:.3} | 🛑 初始止损价: ¥{:.3}", result.target_price, result.stop_loss_price);
+    for fill in &result.fills {
+        println!("   {} {} - 卖出 {} 股 @ ¥{:.3} ({})",
+            fill.date,
+            StockCalculator::format_backtest_exit(fill.kind),
+            fill.quantity,
+            fill.price,
+            StockCalculator::format_currency((fill.price - result.entry_price) * fill.quantity)
+        );
+    }
+    println!("{}", "─".repeat(30));
+    if let Some(first) = result.fills.first() {
+        println!("🚪 最先触发: {} ({})", StockCalculator::format_backtest_exit(first.kind), first.date);
+    }
+    println!("⏱️  持有: {} 天 ({} 个交易日，至 {})", result.holding_days, result.trading_days, result.exit_date);
+    println!("💰 盈亏: {} ({:+.2}%)", StockCalculator::format_currency(result.profit), result.profit_ratio);
+    println!("📈 期间最大涨幅: {:+.2}% | 📉 最大跌幅: {:+.2}%", result.max_gain, result.max_drawdown);
+
+    Ok(())
+}
+
+async fn handle_indicators(config: &AppConfig, code: &str, days: usize) -> Result<()> {
+    let stock_codes = crate::cli::parse_stock_codes(code);
+
+    if stock_codes.is_empty() {
+        return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
+    }
+
+    let api = crate::api::StockApi::new(config.clone())?;
+    let format_value = |value: Option<f64>| value.map_or("N/A".to_string(), |v| format!("{:.3}", v));
+
+    for (i, stock_code) in stock_codes.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let candles = match api.fetch_daily_candles(stock_code, days).await {
+            Ok(candles) => candles,
+            Err(e) => {
+                println!("❌ {} 获取K线失败: {}", stock_code, e);
+                continue;
+            }
+        };
+
+        let summary = match crate::calculator::StockCalculator::calculate_indicator_summary(&candles) {
+            Some(summary) => summary,
+            None => continue,
+        };
+
+        println!("📐 技术指标: {} ({}，收盘 ¥{:.3}，共 {} 根日K)", stock_code, summary.date, summary.close, candles.len());
+        println!("{}", "━".repeat(50));
+        println!("均线  MA5 {} | MA10 {} | MA20 {} | MA60 {}",
+            format_value(summary.ma5), format_value(summary.ma10),
+            format_value(summary.ma20), format_value(summary.ma60)
+        );
+        println!("EMA   EMA12 {} | EMA26 {}", format_value(summary.ema12), format_value(summary.ema26));
+        if let Some(macd) = &summary.macd {
+            println!("MACD  DIF {:.3} | DEA {:.3} | 柱 {:+.3}", macd.dif, macd.dea, macd.histogram);
+        }
+        println!("RSI   RSI6 {} | RSI12 {} | RSI24 {}",
+            format_value(summary.rsi6), format_value(summary.rsi12), format_value(summary.rsi24)
+        );
+        if let Some(kdj) = &summary.kdj {
+            println!("KDJ   K {:.2} | D {:.2} | J {:.2}", kdj.k, kdj.d, kdj.j);
+        }
+        if let Some(bands) = &summary.bollinger {
+            println!("BOLL  上轨 {:.3} | 中轨 {:.3} | 下轨 {:.3}", bands.upper, bands.middle, bands.lower);
+        }
+        println!("ATR14 {}", format_value(summary.atr));
+
+        if !summary.signals.is_empty() {
+            println!("{}", "─".repeat(30));
+            for signal in &summary.signals {
+                println!("💡 {}", signal);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_snapshot(config: &AppConfig, portfolio: &str) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let storage = crate::storage::Storage::new(config.clone());
+    if storage.get_portfolio(portfolio)?.is_none() {
+        println!("❌ 未找到组合 {}", portfolio);
+        println!("💡 使用 'stock-calc account list' 查看所有组合");
+        return Ok(());
+    }
+
+    let api = crate::api::StockApi::new(config.clone())?;
+    let today = chrono::Local::now().date_naive();
+    if let Some(snapshot) = take_portfolio_snapshot(&api, &storage, portfolio, today).await? {
+        println!("📸 已记录组合 {} 在 {} 的快照", portfolio, snapshot.date);
+        for position in &snapshot.positions {
+            println!("   {} - {} 股 @ ¥{:.3} = {}",
+                position.code,
+                position.quantity,
+                position.price,
+                StockCalculator::format_currency(position.market_value)
+            );
+        }
+        println!("🏦 总权益: {} (市值 {} | 现金 {})",
+            StockCalculator::format_currency(snapshot.equity),
+            StockCalculator::format_currency(snapshot.market_value),
+            StockCalculator::format_currency(snapshot.cash)
+        );
+    }
+
+    Ok(())
+}
+
+fn handle_equity_curve(config: &AppConfig, portfolio: &str, days: Option<usize>) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let storage = crate::storage::Storage::new(config.clone());
+    let snapshots = match storage.get_portfolio(portfolio)? {
+        Some(portfolio_data) => portfolio_data.snapshots,
+        None => {
+            println!("❌ 未找到组合 {}", portfolio);
+            println!("💡 使用 'stock-calc account list' 查看所有组合");
+            return Ok(());
+        }
+    };
+
+    let skip = days.map_or(0, |days| snapshots.len().saturating_sub(days));
+    let snapshots = &snapshots[skip..];
+    if snapshots.is_empty() {
+        println!("📭 组合 {} 还没有快照", portfolio);
+        println!("💡 使用 'stock-calc snapshot' 或在收盘后运行 monitor 记录快照");
+        return Ok(());
+    }
+
+    let curve = StockCalculator::calculate_equity_curve(snapshots);
+    let (min, max) = curve.points.iter().fold((f64::MAX, f64::MIN), |(min, max), p| {
+        (min.min(p.equity), max.max(p.equity))
+    });
+
+    println!("📉 净值曲线 - 组合 {} ({} 天)", portfolio, curve.points.len());
+    println!("{}", "━".repeat(50));
+    for point in &curve.points {
+        let width = if max > min { ((point.equity - min) / (max - min) * 30.0).round() as usize } else { 30 };
+        println!("{} {:>14} {:>9} {:>8.2}% {}",
+            point.date,
+            StockCalculator::format_currency(point.equity),
+            point.daily_return.map_or("-".to_string(), |r| format!("{:+.2}%", r)),
+            point.drawdown,
+            "█".repeat(width.max(1))
+        );
+    }
+
+    println!("{}", "─".repeat(30));
+    println!("📈 区间收益 (时间加权): {:+.2}%", curve.total_return);
+    match (curve.max_drawdown_start, curve.max_drawdown_end) {
+        (Some(start), Some(end)) => println!("📉 最大回撤: {:.2}% ({} → {})", curve.max_drawdown, start, end),
+        _ => println!("📉 最大回撤: 0.00%"),
+    }
+    if let Some((date, r)) = curve.best_day {
+        println!("🟢 最好的一天: {} ({:+.2}%)", date, r);
+    }
+    if let Some((date, r)) = curve.worst_day {
+        println!("🔴 最差的一天: {} ({:+.2}%)", date, r);
+    }
+
+    Ok(())
+}
+
+async fn handle_performance(config: &AppConfig, portfolio: &str, period: Option<usize>) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let storage = crate::storage::Storage::new(config.clone());
+    let portfolio_data = match storage.get_portfolio(portfolio)? {
+        Some(portfolio_data) => portfolio_data,
+        None => {
+            println!("❌ 未找到组合 {}", portfolio);
+            println!("💡 使用 'stock-calc account list' 查看所有组合");
+            return Ok(());
+        }
+    };
+
+    if portfolio_data.stocks.is_empty() && portfolio_data.ledger.is_empty() {
+        println!("📭 组合 {} 中没有持仓和流水", portfolio);
+        return Ok(());
+    }
+
+    let api = crate::api::StockApi::new(config.clone())?;
+    let mut prices = std::collections::HashMap::new();
+    let open_codes = portfolio_data.stocks.values().filter(|stock| !stock.is_closed()).map(|stock| &stock.code);
+    for code in open_codes {
+        match api.fetch_stock_price(code).await {
+            Ok(price) => {
+                prices.insert(code.clone(), price);
+            }
+            Err(e) => {
+                // 缺少价格会低估组合市值，不输出不完整的收益表现
+                println!("❌ {} 获取价格失败，无法计算收益表现: {}", code, e);
+                return Ok(());
+            }
+        }
+    }
+
+    let performance = StockCalculator::calculate_portfolio_performance(
+        &portfolio_data,
+        &prices,
+        chrono::Local::now().date_naive(),
+    );
+    let format_rate = |rate: Option<f64>| rate.map_or("N/A".to_string(), |rate| format!("{:+.2}%", rate));
+
+    println!("📈 收益表现 - 组合 {}", portfolio);
+    println!("{}", "━".repeat(50));
+
+    for position in &performance.positions {
+        println!("{} {}{} (自 {})",
+            if position.closed { "📦" } else { "📈" },
+            position.code,
+            if position.closed { " [已清仓]" } else { "" },
+            position.first_date
+        );
+        println!("   市值: {} | 总盈亏: {}",
+            StockCalculator::format_currency(position.market_value),
+            StockCalculator::format_currency(position.total_profit)
+        );
+        println!("   XIRR: {} | 时间加权: {} (年化 {})",
+            format_rate(position.xirr),
+            format_rate(position.twr),
+            format_rate(position.annualized_twr)
+        );
+    }
+
+    println!("{}", "─".repeat(30));
+    println!("🏦 总权益: {} (净入金 {})",
+        StockCalculator::format_currency(performance.equity),
+        StockCalculator::format_currency(performance.net_deposits)
+    );
+    println!("📊 组合 XIRR: {}", format_rate(performance.xirr));
+    println!("⏱️  组合时间加权: {} (年化 {})",
+        format_rate(performance.twr),
+        format_rate(performance.annualized_twr)
+    );
+    if performance.twr.is_none() {
+        println!("💡 存在多次入金/出金时需要每日快照才能计算组合时间加权收益率，使用 'stock-calc snapshot' 记录");
+    }
+
+    let positions: Vec<(String, f64)> = portfolio_data
+        .stocks
+        .values()
+        .filter(|stock| prices.contains_key(&stock.code))
+        .map(|stock| (stock.code.clone(), stock.direction.sign() * stock.quantity))
+        .collect();
+    if !positions.is_empty() {
+        print_benchmark_comparison(config, &api, &positions, period).await;
+    }
+
+    Ok(())
+}
+
+async fn handle_remove(config: &AppConfig, portfolio: &str, code: &str) -> Result<()> {
+    let stock_codes = crate::cli::parse_stock_codes(code);
+    
+    if stock_codes.is_empty() {
+        return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
+    }
+
+    let storage = crate::storage::Storage::new(config.clone());
+    
+    for stock_code in stock_codes {
+        match storage.remove_stock(portfolio, &stock_code)? {
+            Some(_) => println!("✅ 已删除组合 {} 中股票 {} 的数据", portfolio, stock_code),
+            None => println!("❌ 组合 {} 中未找到股票 {} 的数据", portfolio, stock_code),
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_simulate(
+    config: &AppConfig,
+    portfolio: &str,
+    code: &str,
+    trades: &[String],
+    price: Option<f64>,
+) -> Result<()> {
+    let storage = crate::storage::Storage::new(config.clone());
+    let notifier = crate::notification::Notifier::new(config.clone());
+
+    let stock_data = match storage.get_stock(portfolio, code)? {
+        Some(data) => data,
+        None => {
+            println!("❌ 组合 {} 中未找到股票 {} 的数据，请先使用 calculate 命令添加", portfolio, code);
+            return Ok(());
+        }
+    };
+
+    let trades = trades
+        .iter()
+        .map(|t| crate::cli::parse_trade(t))
+        .collect::<Result<Vec<_>>>()?;
+
+    let simulated = crate::calculator::StockCalculator::simulate_trades(&stock_data, &trades)?;
+
+    let current_price = match price {
+        Some(price) => price,
+        None => {
+            let api = crate::api::StockApi::new(config.clone())?;
+            api.fetch_stock_price(code).await?
+        }
+    };
+
+    println!("🧪 模拟交易: {}", code);
+    println!("{}", "━".repeat(50));
+    for trade in &trades {
+        println!("🛒 买入 {} 股 @ ¥{:.3}", trade.quantity, trade.price);
+    }
+    println!("📦 持有数量: {} 股 → {} 股", stock_data.quantity, simulated.quantity);
+    println!("💰 持仓均价: ¥{:.3} → ¥{:.3}", stock_data.avg_price, simulated.avg_price);
+    println!("⚖️  保本价格: ¥{:.3}", simulated.avg_price);
+
+    let mut analysis = crate::calculator::StockCalculator::calculate_analysis_with_config(&simulated, current_price, config, book_equity(&storage, portfolio)?);
+    analysis.advice = crate::calculator::StockCalculator::evaluate_advice(&config.load_advice_rules()?, &analysis, None);
+    notifier.print_analysis(&analysis);
+
+    println!("\n💡 模拟结果未保存到数据库");
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_size(
+    config: &AppConfig,
+    equity: f64,
+    risk_percent: f64,
+    entry: f64,
+    stop: Option<f64>,
+    atr: Option<f64>,
+    atr_multiple: f64,
+    lot_size: f64,
+    reward_ratio: f64,
+) -> Result<()> {
+    // --stop 和 --atr 由命令行参数保证恰好提供一个
+    let stop_price = match (stop, atr) {
+        (Some(stop), _) => stop,
+        (None, Some(atr)) => crate::calculator::StockCalculator::stop_price_from_atr(crate::models::PositionDirection::Long, entry, atr, atr_multiple),
+        (None, None) => {
+            return Err(crate::error::StockCalcError::ParseError("请提供 --stop 或 --atr 参数".to_string()));
+        }
+    };
+
+    let sizing = crate::calculator::StockCalculator::calculate_position_size(
+        equity, risk_percent, entry, stop_price, lot_size, reward_ratio,
+    )?;
+
+    println!("📐 仓位计算");
+    println!("{}", "━".repeat(50));
+    println!("💰 账户资金: ¥{:.2}", sizing.equity);
+    println!("🎲 风险预算: ¥{:.2} ({:.2}%)", sizing.risk_budget, sizing.risk_percent);
+    println!("🌅 买入价格: ¥{:.3}", sizing.entry_price);
+    println!("📉 止损价格: ¥{:.3} (每股风险 ¥{:.3})", sizing.stop_loss_price, sizing.risk_per_share);
+    println!("{}", "─".repeat(30));
+    println!("🛒 建议买入: {} 股 ({} 手)", sizing.quantity, sizing.quantity / lot_size);
+    println!("💵 投入资金: ¥{:.2} (占账户 {:.2}%)",
+        sizing.investment_amount,
+        sizing.investment_amount / sizing.equity * 100.0
+    );
+    println!("⚠️  最大亏损: ¥{:.2}", sizing.max_loss);
+    println!("🎯 目标价格: ¥{:.3} ({:.1}:1)", sizing.target_price, sizing.reward_ratio);
+    println!("📈 预期收益: ¥{:.2}", sizing.target_profit);
+    println!("📊 风险收益比: {:.1}:1 ({})",
+        sizing.reward_ratio,
+        crate::calculator::StockCalculator::get_risk_level(sizing.reward_ratio, &config.risk_grading)
+    );
+
+    Ok(())
+}
+
+async fn handle_dca(config: &AppConfig, portfolio: &str, subcommand: crate::cli::DcaSubcommand) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let storage = crate::storage::Storage::new(config.clone());
+    let today = chrono::Local::now().date_naive();
+
+    match subcommand {
+        crate::cli::DcaSubcommand::Add { plan } => {
+            let plan = plan.to_plan()?;
+            StockCalculator::validate_dca_plan(&plan)?;
+
+            let plan = storage.save_dca_plan(portfolio, plan)?;
+            println!("✅ 已保存 {} 定投计划: {} ¥{:.2}，自 {} 起",
+                plan.code,
+                StockCalculator::format_dca_frequency(plan.frequency),
+                plan.amount,
+                plan.start_date
+            );
+            if let Some(executed) = plan.last_executed {
+                println!("   保留最近执行: {}", executed);
+            }
+            let missed = StockCalculator::missed_dca_periods(&plan, today);
+            if missed > 0 {
+                println!("⚠️  已错过 {} 期，只会提醒最近一期", missed);
+            }
+            match StockCalculator::pending_dca_date(&plan, today) {
+                Some(pending) => println!("⏰ 待执行: {}", pending),
+                None => {
+                    let after = plan.last_executed.map_or(today, |executed| executed.max(today));
+                    if let Some(next) = StockCalculator::next_dca_date(&plan, after) {
+                        println!("📅 下一期: {}", next);
+                    }
+                }
+            }
+        }
+        crate::cli::DcaSubcommand::List => {
+            let plans = storage.portfolio(portfolio)?.map(|p| p.dca_plans).unwrap_or_default();
+            if plans.is_empty() {
+                println!("📭 组合 {} 中没有定投计划", portfolio);
+                println!("💡 使用 'stock-calc dca add' 添加定投计划");
+                return Ok(());
+            }
+
+            println!("📅 定投计划 - 组合 {} (共 {} 个)", portfolio, plans.len());
+            println!("{}", "━".repeat(50));
+            for plan in &plans {
+                println!("📈 {} - {} ¥{:.2} (自 {}，每手 {} 股)",
+                    plan.code,
+                    StockCalculator::format_dca_frequency(plan.frequency),
+                    plan.amount,
+                    plan.start_date,
+                    plan.lot_size
+                );
+                match StockCalculator::pending_dca_date(plan, today) {
+                    Some(pending) => {
+                        if StockCalculator::due_dca_date(plan, today).is_some() {
+                            println!("   ⏰ {} 一期已到期，使用 'stock-calc dca execute --code {}' 记录买入", pending, plan.code);
+                        } else {
+                            println!("   ⏰ {} 一期待执行，周末顺延到下一个工作日提醒", pending);
+                        }
+                        let missed = StockCalculator::missed_dca_periods(plan, today);
+                        if missed > 0 {
+                            println!("   ⚠️  此前已错过 {} 期", missed);
+                        }
+                    }
+                    None => {
+                        let after = plan.last_executed.map_or(today, |executed| executed.max(today));
+                        if let Some(next) = StockCalculator::next_dca_date(plan, after) {
+                            println!("   下一期: {}", next);
+                        }
+                    }
+                }
+                if let Some(executed) = plan.last_executed {
+                    println!("   最近执行: {}", executed);
+                }
+            }
+        }
+        crate::cli::DcaSubcommand::Remove { code } => {
+            if storage.remove_dca_plan(portfolio, &code)? {
+                println!("✅ 已删除 {} 的定投计划", code);
+            } else {
+                println!("❌ 组合 {} 中未找到 {} 的定投计划", portfolio, code);
+            }
+        }
+        crate::cli::DcaSubcommand::Project { plan } => {
+            let plan = plan.to_plan()?;
+            let count = ((today - plan.start_date).num_days().max(0) as usize) + 10;
+            let api = crate::api::StockApi::new(config.clone())?;
+            let candles = api.fetch_daily_candles(&plan.code, count).await?;
+            let projection = StockCalculator::project_dca(&plan, &candles)?;
+
+            println!("📅 定投模拟: {} {} ¥{:.2}，{} ~ {}",
+                plan.code,
+                StockCalculator::format_dca_frequency(plan.frequency),
+                plan.amount,
+                plan.start_date,
+                projection.final_date
+            );
+            println!("{}", "━".repeat(50));
+            for purchase in &projection.purchases {
+                println!("   {} 买入 {} 股 @ ¥{:.3} = {}",
+                    purchase.date,
+                    purchase.quantity,
+                    purchase.price,
+                    StockCalculator::format_currency(purchase.amount)
+                );
+            }
+            println!("{}", "─".repeat(30));
+            println!("🧾 共 {} 期，投入 {}，持有 {} 股，平均成本 ¥{:.3}",
+                projection.purchases.len(),
+                StockCalculator::format_currency(projection.total_invested),
+                projection.total_quantity,
+                projection.avg_cost
+            );
+            println!("💰 市值: {} (¥{:.3})", StockCalculator::format_currency(projection.market_value), projection.final_price);
+            println!("📊 盈亏: {} ({:+.2}%) | XIRR: {}",
+                StockCalculator::format_currency(projection.profit),
+                projection.profit_ratio,
+                projection.xirr.map_or("N/A".to_string(), |rate| format!("{:+.2}%", rate))
+            );
+        }
+        crate::cli::DcaSubcommand::Execute { code, price, fee } => {
+            let plan = match find_dca_plan(&storage, portfolio, &code)? {
+                Some(plan) => plan,
+                None => return Ok(()),
+            };
+
+            let scheduled_date = match StockCalculator::due_dca_date(&plan, today) {
+                Some(date) => date,
+                None => {
+                    println!("📭 {} 本期定投未到期", code);
+                    return Ok(());
+                }
+            };
+
+            let price = match price {
+                Some(price) => price,
+                None => crate::api::StockApi::new(config.clone())?.fetch_stock_price(&code).await?,
+            };
+            execute_dca(&storage, portfolio, &plan, price, fee, scheduled_date)?;
+        }
+        crate::cli::DcaSubcommand::Monitor { interval, auto_record } => {
+            let api = crate::api::StockApi::new(config.clone())?;
+            let notifier = crate::notification::Notifier::new(config.clone());
+            let mut reminded = std::collections::HashSet::new();
+
+            println!("🔄 定投监控: 组合 {} (每{}秒检查)", portfolio, interval);
+            let mut interval_timer = tokio::time::interval(Duration::from_secs(interval));
+
+            loop {
+                interval_timer.tick().await;
+
+                let today = chrono::Local::now().date_naive();
+                let plans = storage.portfolio(portfolio)?.map(|p| p.dca_plans).unwrap_or_default();
+                for plan in &plans {
+                    let scheduled_date = match StockCalculator::due_dca_date(plan, today) {
+                        Some(date) => date,
+                        None => continue,
+                    };
+                    if !reminded.insert((plan.code.clone(), scheduled_date)) {
+                        continue;
+                    }
+
+                    notifier.send_dca_reminder(plan, scheduled_date).await?;
+                    if auto_record {
+                        let result = match api.fetch_stock_price(&plan.code).await {
+                            Ok(price) => execute_dca(&storage, portfolio, plan, price, 0.0, scheduled_date),
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            println!("❌ {} 自动记录定投失败: {}", plan.code, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn find_dca_plan(
+    storage: &crate::storage::Storage,
+    portfolio: &str,
+    code: &str,
+) -> Result<Option<crate::models::DcaPlan>> {
+    let plan = storage
+        .portfolio(portfolio)?
+        .and_then(|p| p.dca_plans.into_iter().find(|plan| plan.code == code));
+
+    if plan.is_none() {
+        println!("❌ 组合 {} 中未找到 {} 的定投计划", portfolio, code);
+        println!("💡 使用 'stock-calc dca add' 添加定投计划");
+    }
+    Ok(plan)
+}
+
+/// 按定投金额买入整手，没有持仓时按计划的止盈/止损百分比建仓
+fn execute_dca(
+    storage: &crate::storage::Storage,
+    portfolio: &str,
+    plan: &crate::models::DcaPlan,
+    price: f64,
+    fee: f64,
+    scheduled_date: chrono::NaiveDate,
+) -> Result<()> {
+    use crate::calculator::StockCalculator;
+
+    let quantity = StockCalculator::dca_quantity(plan.amount, price, plan.lot_size);
+    if quantity <= 0.0 {
+        println!("❌ {} 定投金额 ¥{:.2} 不足一手 (¥{:.3} × {})", plan.code, plan.amount, price, plan.lot_size);
+        return Ok(());
+    }
+
+    let mut stock = storage.get_stock(portfolio, &plan.code)?.unwrap_or_else(|| StockData {
+        target_kind: crate::models::TargetKind::Percent,
+        stop_kind: crate::models::TargetKind::Percent,
+        ..StockData::new(plan.code.clone(), 0.0, 0.0, plan.target_percent, plan.stop_percent)
+    });
+
+    let amount = StockCalculator::apply_buy(&mut stock, quantity, price, fee)?;
+    ensure_cash(storage, portfolio, -amount)?;
+
+    println!("✅ 定投 {} ({}) 买入 {} 股 @ ¥{:.3}，现金 ¥{:.2}，持仓 {} 股 @ ¥{:.3}",
+        plan.code, scheduled_date, quantity, price, amount, stock.quantity, stock.avg_price
+    );
+    let transaction = CashTransaction::trade(
+        chrono::Local::now().date_naive(),
+        TransactionKind::Buy,
+        &plan.code,
+        quantity,
+        price,
+        fee,
+        amount,
+    );
+    storage.record_dca_execution(portfolio, stock, transaction, scheduled_date)
+}
+
+async fn handle_action(
+    config: &AppConfig,
+    portfolio: &str,
+    subcommand: crate::cli::ActionSubcommand,
+) -> Result<()> {
+    let storage = crate::storage::Storage::new(config.clone());
+    let today = chrono::Local::now().date_naive();
+
+    match subcommand {
+        crate::cli::ActionSubcommand::Add { code, ex_date, cash, bonus, split } => {
+            let mut actions = Vec::new();
+            for (kind, value) in [("cash", cash), ("bonus", bonus), ("split", split)] {
+                if let Some(value) = value {
+                    actions.push(crate::cli::parse_corporate_action(&ex_date, kind, value)?);
+                }
+            }
+
+            if actions.is_empty() {
+                return Err(crate::error::StockCalcError::ParseError("请至少提供 --cash、--bonus 或 --split 之一".to_string()));
+            }
+
+            record_corporate_actions(&storage, portfolio, &code, actions, today)?;
+        }
+        crate::cli::ActionSubcommand::Import { file } => {
+            let content = std::fs::read_to_string(&file)?;
+
+            // 按代码分组，保持文件中的顺序
+            let mut grouped: Vec<(String, Vec<crate::models::CorporateAction>)> = Vec::new();
+            for line in content.lines() {
+                if let Some((code, action)) = crate::cli::parse_corporate_action_line(line)? {
+                    match grouped.iter_mut().find(|(c, _)| *c == code) {
+                        Some((_, actions)) => actions.push(action),
+                        None => grouped.push((code, vec![action])),
+                    }
+                }
+            }
+
+            if grouped.is_empty() {
+                println!("📭 文件中没有公司行为记录");
+                return Ok(());
+            }
+
+            for (code, actions) in grouped {
+                record_corporate_actions(&storage, portfolio, &code, actions, today)?;
+            }
+        }
+        crate::cli::ActionSubcommand::List { code } => {
+            match storage.get_stock(portfolio, &code)? {
+                Some(stock) if !stock.corporate_actions.is_empty() => {
+                    println!("📋 {} 公司行为 (共 {} 条)", code, stock.corporate_actions.len());
+                    println!("{}", "━".repeat(50));
+                    for action in &stock.corporate_actions {
+                        println!("📅 {} {}{}",
+                            action.ex_date,
+                            crate::calculator::StockCalculator::format_corporate_action(&action.kind),
+                            if action.applied { " ✅" } else { " ⏳ 待除权" }
+                        );
+                    }
+                    println!("💵 已实现收益: ¥{:.3}", stock.realized_profit);
+                }
+                Some(_) => println!("📭 股票 {} 没有公司行为记录", code),
+                None => println!("❌ 未找到股票 {} 的数据", code),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 记录公司行为并立即处理已到除权除息日的部分
+fn record_corporate_actions(
+    storage: &crate::storage::Storage,
+    portfolio: &str,
+    code: &str,
+    actions: Vec<crate::models::CorporateAction>,
+    today: chrono::NaiveDate,
+) -> Result<()> {
+    let mut stock = match storage.get_stock(portfolio, code)? {
+        Some(stock) => stock,
+        None => {
+            println!("❌ 组合 {} 中未找到股票 {} 的数据，请先使用 calculate 命令添加", portfolio, code);
+            return Ok(());
+        }
+    };
+
+    for action in &actions {
+        crate::calculator::StockCalculator::validate_corporate_action(action)?;
+        println!("✅ 已记录 {} 的公司行为: {} {}",
+            code,
+            action.ex_date,
+            crate::calculator::StockCalculator::format_corporate_action(&action.kind)
+        );
+    }
+
+    let (quantity, avg_price) = (stock.quantity, stock.avg_price);
+    stock.corporate_actions.extend(actions);
+
+    let applied = crate::calculator::StockCalculator::apply_due_corporate_actions(&mut stock, today);
+    if !applied.is_empty() {
+        println!("🔄 已除权除息: {} 股 @ ¥{:.3} → {} 股 @ ¥{:.3}，已实现收益 ¥{:.3}",
+            quantity, avg_price, stock.quantity, stock.avg_price, stock.realized_profit
+        );
+    }
+
+    let dividends = dividend_transactions(code, &applied);
+    storage.update_stock_with_transactions(portfolio, stock, dividends)
+}
+
+/// 将已除息的公司行为转换为分红现金流水
+fn dividend_transactions(code: &str, applied: &[crate::models::CorporateAction]) -> Vec<CashTransaction> {
+    applied
+        .iter()
+        .filter(|action| action.cash_received > 0.0)
+        .map(|action| {
+            let mut transaction = CashTransaction::new(action.ex_date, TransactionKind::Dividend, action.cash_received);
+            transaction.code = Some(code.to_string());
+            transaction
+        })
+        .collect()
+}
+
+/// 校验组合现金是否足够支付
+fn ensure_cash(storage: &crate::storage::Storage, portfolio: &str, amount: f64) -> Result<()> {
+    let cash = storage.portfolio(portfolio)?.map(|p| p.cash).unwrap_or_default();
+
+    if cash + 1e-9 < amount {
+        return Err(crate::error::StockCalcError::ValidationError(format!(
+            "{}: 组合 {} 现金 ¥{:.2}，需要 ¥{:.2}",
+            crate::error::ValidationError::InsufficientCash, portfolio, cash, amount
+        )));
+    }
+
+    Ok(())
+}
+
+/// 组合按成本估算的权益，用于按权益占比划分投资规模；组合为空时返回 None。
+/// 有意使用成本而不是市值：不需要逐只请求行情，结果也不随价格波动。
+/// 只包含已保存的持仓，尚未保存的仓位按加入前的权益计算占比
+fn book_equity(storage: &crate::storage::Storage, portfolio: &str) -> Result<Option<f64>> {
+    Ok(storage
+        .portfolio(portfolio)?
+        .map(|p| p.book_equity())
+        .filter(|equity| *equity > 0.0))
+}
+
+async fn handle_account(config: &AppConfig, subcommand: crate::cli::AccountSubcommand) -> Result<()> {
+    let storage = crate::storage::Storage::new(config.clone());
+    let today = chrono::Local::now().date_naive();
+
+    match subcommand {
+        crate::cli::AccountSubcommand::Create { name, cash } => {
+            if cash < 0.0 {
+                return Err(crate::error::StockCalcError::ValidationError(
+                    crate::error::ValidationError::InvalidNumber.to_string()
+                ));
+            }
+
+            let deposit = (cash > 0.0).then(|| CashTransaction::new(today, TransactionKind::Deposit, cash));
+            if storage.create_portfolio(&name, deposit)? {
+                println!("✅ 已创建组合 {}，现金 ¥{:.2}", name, cash);
+            } else {
+                println!("❌ 组合 {} 已存在", name);
+            }
+        }
+        crate::cli::AccountSubcommand::List => {
+            let portfolios = storage.list_portfolios()?;
+
+            if portfolios.is_empty() {
+                println!("📭 还没有投资组合");
+                println!("💡 使用 'stock-calc account create --name <名称>' 创建");
+                return Ok(());
+            }
+
+            println!("💼 投资组合 (共 {} 个)", portfolios.len());
+            println!("{}", "━".repeat(50));
+            for portfolio in portfolios {
+                let cost = portfolio.stocks.values().fold(0.0, |acc, s| acc + s.quantity * s.avg_price);
+                println!("📁 {} - {} 只股票 | 持仓成本 ¥{:.2} | 现金 ¥{:.2}",
+                    portfolio.name,
+                    portfolio.stocks.values().filter(|stock| !stock.is_closed()).count(),
+                    cost,
+                    portfolio.cash
+                );
+            }
+        }
+        crate::cli::AccountSubcommand::Deposit { amount, portfolio } => {
+            if amount <= 0.0 {
+                return Err(crate::error::StockCalcError::ValidationError(
+                    crate::error::ValidationError::InvalidNumber.to_string()
+                ));
+            }
+
+            storage.record_transaction(&portfolio, CashTransaction::new(today, TransactionKind::Deposit, amount))?;
+            println!("✅ 组合 {} 入金 ¥{:.2}", portfolio, amount);
+        }
+        crate::cli::AccountSubcommand::Withdraw { amount, portfolio } => {
+            if amount <= 0.0 {
+                return Err(crate::error::StockCalcError::ValidationError(
+                    crate::error::ValidationError::InvalidNumber.to_string()
+                ));
+            }
+
+            ensure_cash(&storage, &portfolio, amount)?;
+            storage.record_transaction(&portfolio, CashTransaction::new(today, TransactionKind::Withdrawal, -amount))?;
+            println!("✅ 组合 {} 出金 ¥{:.2}", portfolio, amount);
+        }
+        crate::cli::AccountSubcommand::Buy { code, quantity, price, fee, portfolio } => {
+            let mut stock = match storage.get_stock(&portfolio, &code)? {
+                Some(stock) => stock,
+                None => {
+                    println!("❌ 组合 {} 中未找到股票 {} 的数据，请先使用 calculate --save --record-buy 建仓", portfolio, code);
+                    return Ok(());
+                }
+            };
+
+            let amount = crate::calculator::StockCalculator::apply_buy(&mut stock, quantity, price, fee)?;
+            ensure_cash(&storage, &portfolio, -amount)?;
+
+            println!("✅ 买入 {} {} 股 @ ¥{:.3}，现金 ¥{:.2}，持仓 {} 股 @ ¥{:.3}",
+                code, quantity, price, amount, stock.quantity, stock.avg_price
+            );
+            let transaction = CashTransaction::trade(today, TransactionKind::Buy, &code, quantity, price, fee, amount);
+            storage.update_stock_with_transactions(&portfolio, stock, vec![transaction])?;
+        }
+        crate::cli::AccountSubcommand::Sell { code, quantity, price, fee, portfolio } => {
+            let mut stock = match storage.get_stock(&portfolio, &code)? {
+                Some(stock) => stock,
+                None => {
+                    println!("❌ 组合 {} 中未找到股票 {} 的数据", portfolio, code);
+                    return Ok(());
+                }
+            };
+
+            let realized_before = stock.realized_profit;
+            let amount = crate::calculator::StockCalculator::apply_sell(&mut stock, quantity, price, fee)?;
+
+            println!("✅ 卖出 {} {} 股 @ ¥{:.3}，现金 +¥{:.2}，本次实现收益 ¥{:.2}",
+                code, quantity, price, amount, stock.realized_profit - realized_before
+            );
+            if stock.quantity <= 0.0 {
+                println!("📭 {} 已清仓", code);
+            }
+            let transaction = CashTransaction::trade(today, TransactionKind::Sell, &code, quantity, price, fee, amount);
+            storage.update_stock_with_transactions(&portfolio, stock, vec![transaction])?;
+        }
+        crate::cli::AccountSubcommand::Ledger { portfolio } => {
+            let portfolio = match storage.get_portfolio(&portfolio)? {
+                Some(portfolio) => portfolio,
+                None => {
+                    println!("❌ 未找到组合 {}", portfolio);
+                    return Ok(());
+                }
+            };
+
+            if portfolio.ledger.is_empty() {
+                println!("📭 组合 {} 没有现金流水", portfolio.name);
+                return Ok(());
+            }
+
+            println!("📒 {} 现金流水 (共 {} 笔)", portfolio.name, portfolio.ledger.len());
+            println!("{}", "━".repeat(50));
+            for transaction in &portfolio.ledger {
+                let kind = match transaction.kind {
+                    TransactionKind::Deposit => "入金",
+                    TransactionKind::Withdrawal => "出金",
+                    TransactionKind::Buy => "买入",
+                    TransactionKind::Sell => "卖出",
+                    TransactionKind::Dividend => "分红",
+                };
+                let detail = match (&transaction.code, transaction.quantity > 0.0) {
+                    (Some(code), true) => format!(" {} {} 股 @ ¥{:.3}", code, transaction.quantity, transaction.price),
+                    (Some(code), false) => format!(" {}", code),
+                    _ => String::new(),
+                };
+                println!("📅 {} {}{} ¥{:+.2}", transaction.date, kind, detail, transaction.amount);
+            }
+            println!("{}", "─".repeat(30));
+            println!("💰 现金余额: ¥{:.2} | 净入金: ¥{:.2}", portfolio.cash, portfolio.net_deposits());
+        }
+        crate::cli::AccountSubcommand::Delete { name } => {
+            match storage.remove_portfolio(&name)? {
+                Some(portfolio) => println!("✅ 已删除组合 {} ({} 只股票)", name, portfolio.stocks.len()),
+                None => println!("❌ 未找到组合 {}", name),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_config(config: &AppConfig, subcommand: crate::cli::ConfigSubcommand) -> Result<()> {
+    match subcommand {
+        crate::cli::ConfigSubcommand::Show => {
+            println!("📋 当前配置:");
+            println!("{}", "━".repeat(30));
+            println!("默认间隔: {} 秒", config.general.default_interval);
+            println!("启用通知: {}", config.general.enable_notifications);
+            println!("启用声音: {}", config.general.enable_sound);
+            println!("日志级别: {}", config.general.log_level);
+            println!("API超时: {} 秒", config.api.timeout);
+            println!("重试次数: {}", config.api.retry_count);
+            println!("彩色输出: {}", config.display.color_output);
+            println!("显示进度: {}", config.display.show_progress);
+            println!("比较基准: {} ({}, 近{}个交易日)",
+                config.benchmark.name, config.benchmark.code, config.benchmark.period_days);
+            println!("波动止损: ATR{} | 建议 {:.1}×ATR | 低于 {:.1}×ATR 提示过紧",
+                config.volatility.atr_period,
+                config.volatility.suggested_atr_multiple,
+                config.volatility.min_stop_atr_multiple);
+            println!("接近目标: {} | 接近止损: {}",
+                crate::calculator::StockCalculator::format_near_threshold(&config.status.near_target),
+                crate::calculator::StockCalculator::format_near_threshold(&config.status.near_stop_loss));
+            println!("投资规模分档{}: {}",
+                if config.scale.relative_to_equity { "(占权益%)" } else { "(金额)" },
+                config.scale.buckets
+                    .iter()
+                    .map(|bucket| match bucket.below {
+                        Some(below) => format!("{} <{}", bucket.label, below),
+                        None => bucket.label.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" | "));
+            println!("风险收益比评级: {}",
+                config.risk_grading.grades
+                    .iter()
+                    .map(|grade| format!("{} ≥{:.1}", grade.label, grade.min_ratio))
+                    .collect::<Vec<_>>()
+                    .join(" | "));
+            println!("无风险利率: {:.2}%/年 | 平仓费率: {:.3}% (最低 ¥{:.2})",
+                config.holding_cost.risk_free_rate,
+                config.holding_cost.exit_fee_rate,
+                config.holding_cost.min_exit_fee);
+            println!("集中度上限: 单只 {:.0}% | 单个行业 {:.0}% | 统计前 {} 大持仓",
+                config.concentration.max_stock_weight,
+                config.concentration.max_sector_weight,
+                config.concentration.top_n);
+            println!("相关性: 最近 {} 个交易日 | 高度相关 ≥{:.2} | 至少 {} 个共同交易日",
+                config.correlation.lookback_days,
+                config.correlation.high_threshold,
+                config.correlation.min_observations);
+        }
+        crate::cli::ConfigSubcommand::Reset => {
+            let default_config = AppConfig::default();
+            default_config.save()?;
+            println!("✅ 配置已重置为默认值");
+        }
+        crate::cli::ConfigSubcommand::Edit => {
+            println!("💡 请手动编辑配置文件:");
+            match AppConfig::get_config_path() {
+                Ok(path) => println!("   {}", path.display()),
+                Err(_) => println!("   无法获取配置文件路径"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_test(config: &AppConfig, code: &str) -> Result<()> {
+    let stock_codes = crate::cli::parse_stock_codes(code);
+    
+    if stock_codes.is_empty() {
+        return Err(crate::error::StockCalcError::ParseError("未提供有效的股票代码".to_string()));
+    }
+
+    let api = crate::api::StockApi::new(config.clone())?;
+    
+    for (i, stock_code) in stock_codes.iter().enumerate() {
+        if i > 0 {
+            println!("{}", "━".repeat(50));
+        }
+
+        // 尝试获取股票信息
+        match api.fetch_stock_info(stock_code).await {
+            Ok(stock_info) => {
+                println!("✅ 股票代码 {} 可查", stock_code);
+                println!("{}", "━".repeat(50));
+                println!("📈 股票名称: {}", stock_info.name);
+                println!("🔢 股票代码: {}", stock_info.code);
+                println!("💰 当前价格: ¥{:.3}", stock_info.current_price);
+                println!("📊 涨跌金额: ¥{:.3}", stock_info.change_amount);
+                println!("📈 涨跌幅度: {:.2}%", stock_info.change_percent);
+                println!("📅 昨收价格: ¥{:.3}", stock_info.yesterday_close);
+                println!("🌅 开盘价格: ¥{:.3}", stock_info.open_price);
+                println!("📈 最高价格: ¥{:.3}", stock_info.high_price);
+                println!("📉 最低价格: ¥{:.3}", stock_info.low_price);
+                println!("📊 成交量: {} 手", stock_info.volume / 100);
+                println!("💵 成交额: ¥{:.2} 万", stock_info.turnover / 10000.0);
+            }
+            Err(_) => {
+                println!("❌ 请重新填写正确的股票代码: {}", stock_code);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_calculate_command() {
+        let config = AppConfig::default();
+        let template = StockData::new(String::new(), 1000.0, 15.5, 5000.0, 2000.0);
+        let result = handle_calculate(
+            &config,
+            crate::models::DEFAULT_PORTFOLIO,
+            "000001",
+            &template,
+            None,
+            false,
+            false,
+        ).await;
+        
+        // 这个测试可能会因为网络问题失败，所以我们只检查函数能正常执行
+        // 实际测试中应该使用模拟的API
+        assert!(result.is_ok() || result.is_err());
+    }
+
+    #[test]
+    fn test_stale_candles() {
+        let candle = |close: f64| crate::models::Candle {
+            date: chrono::NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 0.0,
+        };
+        let entry = crate::models::CachedCandles {
+            fetched_at: chrono::NaiveDate::from_ymd_opt(2026, 10, 15).unwrap().and_hms_opt(16, 0, 0).unwrap(),
+            count: 3,
+            candles: vec![candle(10.0), candle(11.0), candle(12.0)],
+        };
+        let error = || crate::error::StockCalcError::ApiResponseError("timeout".to_string());
+
+        let candles = stale_candles("000001", Some(entry), 2, error()).unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].close, 12.0);
+        assert!(stale_candles("000001", None, 2, error()).is_err());
     }
 } 
\ No newline at end of file
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Timelike, Utc};
+use crate::error::{Result, StockCalcError, ValidationError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockInfo {
@@ -16,6 +17,90 @@ pub struct StockInfo {
     pub change_percent: f64,
 }
 
+/// 日K线（前复权）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub date: NaiveDate,
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub volume: f64,
+}
+
+/// 按股票缓存的日K线，避免重复请求
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CandleCache {
+    #[serde(default)]
+    pub entries: std::collections::HashMap<String, CachedCandles>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedCandles {
+    /// 获取时间（本地时间）
+    pub fetched_at: NaiveDateTime,
+    /// 获取时请求的K线数量
+    pub count: usize,
+    pub candles: Vec<Candle>,
+}
+
+impl CachedCandles {
+    /// 当天获取且数量足够时可以直接使用；盘中获取的K线不含当天收盘价，收盘后需要重新获取
+    pub fn covers(&self, count: usize, now: NaiveDateTime) -> bool {
+        self.fetched_at.date() == now.date()
+            && self.count >= count
+            && (self.fetched_at.hour() >= MARKET_CLOSE_HOUR || now.hour() < MARKET_CLOSE_HOUR)
+    }
+
+    /// 最近 count 个交易日的K线
+    pub fn latest(&self, count: usize) -> Vec<Candle> {
+        self.candles[self.candles.len().saturating_sub(count)..].to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Macd {
+    pub dif: f64,
+    pub dea: f64,
+    pub histogram: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Kdj {
+    pub k: f64,
+    pub d: f64,
+    pub j: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BollingerBands {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// 最新一根K线的技术指标，数据不足时对应指标为 None
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndicatorSummary {
+    pub date: NaiveDate,
+    pub close: f64,
+    pub ma5: Option<f64>,
+    pub ma10: Option<f64>,
+    pub ma20: Option<f64>,
+    pub ma60: Option<f64>,
+    pub ema12: Option<f64>,
+    pub ema26: Option<f64>,
+    pub macd: Option<Macd>,
+    pub rsi6: Option<f64>,
+    pub rsi12: Option<f64>,
+    pub rsi24: Option<f64>,
+    pub kdj: Option<Kdj>,
+    pub bollinger: Option<BollingerBands>,
+    pub atr: Option<f64>,
+    /// 金叉/死叉、超买/超卖等提示
+    pub signals: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockData {
     pub code: String,
@@ -23,9 +108,210 @@ pub struct StockData {
     pub avg_price: f64,
     pub target_profit: f64,
     pub max_loss: f64,
+    #[serde(default)]
+    pub target_kind: TargetKind,
+    #[serde(default)]
+    pub stop_kind: TargetKind,
+    #[serde(default)]
+    pub trailing_stop: Option<TrailingStop>,
+    #[serde(default)]
+    pub take_profit_tiers: Vec<ExitTier>,
+    #[serde(default)]
+    pub stop_loss_tiers: Vec<ExitTier>,
+    #[serde(default)]
+    pub corporate_actions: Vec<CorporateAction>,
+    /// 分红等已实现收益
+    #[serde(default)]
+    pub realized_profit: f64,
+    /// 接近目标价的提示阈值，为空时使用全局配置
+    #[serde(default)]
+    pub near_target: Option<NearThreshold>,
+    /// 接近止损价的提示阈值，为空时使用全局配置
+    #[serde(default)]
+    pub near_stop_loss: Option<NearThreshold>,
+    #[serde(default)]
+    pub direction: PositionDirection,
+    /// 融资买入或融券卖出的负债信息，普通持仓为空
+    #[serde(default)]
+    pub financing: Option<Financing>,
     pub last_updated: DateTime<Utc>,
 }
 
+impl StockData {
+    pub fn new(code: String, quantity: f64, avg_price: f64, target_profit: f64, max_loss: f64) -> Self {
+        Self {
+            code,
+            quantity,
+            avg_price,
+            target_profit,
+            max_loss,
+            target_kind: TargetKind::Amount,
+            stop_kind: TargetKind::Amount,
+            trailing_stop: None,
+            take_profit_tiers: Vec::new(),
+            stop_loss_tiers: Vec::new(),
+            corporate_actions: Vec::new(),
+            realized_profit: 0.0,
+            near_target: None,
+            near_stop_loss: None,
+            direction: PositionDirection::Long,
+            financing: None,
+            last_updated: Utc::now(),
+        }
+    }
+
+    /// 已全部卖出，记录保留用于累计已实现收益
+    pub fn is_closed(&self) -> bool {
+        self.quantity <= 0.0
+    }
+
+    /// 按方向计算的持仓市值，融券持仓为负（对应需买回的负债）
+    pub fn signed_value(&self, price: f64) -> f64 {
+        self.direction.sign() * self.quantity * price
+    }
+
+    /// 持仓对账户权益的净贡献，扣除融资负债和融资融券利息。
+    /// 融券卖出所得留在信用账户中、不计入现金，空头按卖出所得减去买回市值计算
+    pub fn net_value(&self, price: f64, today: NaiveDate) -> f64 {
+        let interest = self.financing.as_ref().map_or(0.0, |f| f.accrued_interest(today).1);
+        match self.direction {
+            PositionDirection::Long => {
+                let borrowed = self.financing.as_ref().map_or(0.0, |f| f.borrowed_amount);
+                self.quantity * price - borrowed - interest
+            }
+            PositionDirection::Short => self.quantity * (self.avg_price - price) - interest,
+        }
+    }
+}
+
+/// 持仓方向，融券卖出为空头
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum PositionDirection {
+    #[default]
+    Long,       // 多头
+    Short,      // 空头（融券卖出）
+}
+
+impl PositionDirection {
+    /// 价格上涨对盈亏的方向，多头为 1，空头为 -1
+    pub fn sign(self) -> f64 {
+        match self {
+            PositionDirection::Long => 1.0,
+            PositionDirection::Short => -1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for PositionDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "long" => Ok(PositionDirection::Long),
+            "short" => Ok(PositionDirection::Short),
+            _ => Err(format!("无效的持仓方向: {}（可选 long/short）", s)),
+        }
+    }
+}
+
+/// 融资融券负债，多头为融资买入，空头为融券卖出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Financing {
+    /// 融资金额，或融券卖出时借入证券的市值
+    pub borrowed_amount: f64,
+    /// 年化利率/费率 (%)，按 360 天计息
+    pub interest_rate: f64,
+    pub start_date: NaiveDate,
+    /// 最低维持担保比例 (%)，低于该值时提示
+    pub maintenance_ratio: f64,
+    /// 本持仓之外的担保物价值（现金和证券）
+    #[serde(default)]
+    pub collateral: f64,
+}
+
+impl Financing {
+    /// 计息天数和应计利息（360 天计息）
+    pub fn accrued_interest(&self, today: NaiveDate) -> (i64, f64) {
+        let holding_days = (today - self.start_date).num_days().max(0);
+        let interest = self.borrowed_amount * self.interest_rate / 100.0 * holding_days as f64 / 360.0;
+        (holding_days, interest)
+    }
+}
+
+/// 预期收益/最大亏损的设定方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TargetKind {
+    #[default]
+    Amount,     // 金额
+    Percent,    // 相对成本的百分比
+    Price,      // 指定价格
+}
+
+impl std::str::FromStr for TargetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "amount" => Ok(TargetKind::Amount),
+            "percent" => Ok(TargetKind::Percent),
+            "price" => Ok(TargetKind::Price),
+            _ => Err(format!("无效的设定方式: {}（可选 amount/percent/price）", s)),
+        }
+    }
+}
+
+/// “接近目标/止损”状态的提示阈值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NearThreshold {
+    pub kind: NearThresholdKind,
+    pub value: f64,
+}
+
+impl Default for NearThreshold {
+    fn default() -> Self {
+        Self {
+            kind: NearThresholdKind::Percent,
+            value: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum NearThresholdKind {
+    #[default]
+    Percent,    // 目标价/止损价的百分比
+    Price,      // 距目标价/止损价的价差
+    Distance,   // 成本价到目标价/止损价剩余距离的百分比
+}
+
+impl std::str::FromStr for NearThreshold {
+    type Err = String;
+
+    /// 格式为 方式:值，如 percent:3、price:0.05、distance:20
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("无效的提示阈值: {}（格式为 方式:值，如 percent:3）", s))?;
+
+        let kind = match kind.trim().to_lowercase().as_str() {
+            "percent" => NearThresholdKind::Percent,
+            "price" => NearThresholdKind::Price,
+            "distance" => NearThresholdKind::Distance,
+            _ => return Err(format!("无效的提示阈值方式: {}（可选 percent/price/distance）", kind)),
+        };
+
+        let value: f64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("无效的提示阈值: {}", s))?;
+        if value < 0.0 {
+            return Err(format!("提示阈值不能为负: {}", s));
+        }
+
+        Ok(NearThreshold { kind, value })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockAnalysis {
     pub code: String,
@@ -36,22 +322,653 @@ pub struct StockAnalysis {
     pub max_loss: f64,
     pub target_price: f64,
     pub stop_loss_price: f64,
+    pub trailing_stop_price: Option<f64>,
+    pub high_water_mark: Option<f64>,
     pub current_profit: f64,
+    pub realized_profit: f64,
+    pub total_profit: f64,
     pub profit_ratio: f64,
     pub distance_to_target: f64,
     pub distance_to_stop_loss: f64,
     pub risk_reward_ratio: f64,
     pub investment_amount: f64,
-    pub investment_scale: InvestmentScale,
+    /// 按配置分档的投资规模标签
+    pub investment_scale: String,
+    pub tiers: Vec<TierAnalysis>,
     pub status: StockStatus,
+    /// 基于历史K线的波动止损参考，需要额外获取K线，默认为空
+    #[serde(default)]
+    pub volatility: Option<VolatilityStop>,
+    /// 蒙特卡洛估算的止盈/止损概率，按需计算，默认为空
+    #[serde(default)]
+    pub probability: Option<ExitProbability>,
+    #[serde(default)]
+    pub direction: PositionDirection,
+    /// 融资融券的利息和维持担保比例，普通持仓为空
+    #[serde(default)]
+    pub financing: Option<FinancingAnalysis>,
+    /// 按建议规则生成的操作建议
+    #[serde(default)]
+    pub advice: Vec<Advice>,
+    /// 持仓已全部卖出时为空
+    #[serde(default)]
+    pub holding_cost: Option<HoldingCostAnalysis>,
     pub last_updated: DateTime<Utc>,
 }
 
+/// 盈亏平衡价和资金占用的机会成本，持有起始日未知时不计算机会成本
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HoldingCostAnalysis {
+    /// 扣除平仓费用和融资融券利息后不亏不赚的价格
+    pub break_even_price: f64,
+    /// 按盈亏平衡价平仓的预估费用
+    pub exit_fee: f64,
+    pub accrued_interest: f64,
+    pub holding_since: Option<NaiveDate>,
+    pub holding_days: Option<i64>,
+    /// 自有资金按无风险利率计算的收益
+    pub opportunity_cost: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum InvestmentScale {
-    Small,      // < 1万
-    Medium,     // 1-10万
-    Large,      // > 10万
+pub struct FinancingAnalysis {
+    pub borrowed_amount: f64,
+    pub interest_rate: f64,
+    pub holding_days: i64,
+    pub accrued_interest: f64,
+    /// 当前维持担保比例 (%) = 担保资产 / 负债
+    pub maintenance_ratio: f64,
+    pub min_maintenance_ratio: f64,
+}
+
+/// 建议规则集，从 TOML 文件加载，按顺序匹配
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdviceRuleSet {
+    #[serde(default)]
+    pub rules: Vec<AdviceRule>,
+}
+
+impl Default for AdviceRuleSet {
+    fn default() -> Self {
+        let rule = |name: &str, when: Vec<AdviceCondition>, advice: &str, severity: AdviceSeverity, fallback: bool| AdviceRule {
+            name: name.to_string(),
+            when,
+            advice: advice.to_string(),
+            severity,
+            fallback,
+            stop: false,
+        };
+        let condition = |field: &str, op: AdviceOp, value: f64| AdviceCondition {
+            field: field.to_string(),
+            op,
+            value,
+        };
+
+        Self {
+            rules: vec![
+                rule("盈利较多", vec![condition("profit_ratio", AdviceOp::Gt, 20.0)], "考虑分批卖出", AdviceSeverity::Info, false),
+                rule("亏损较多", vec![condition("profit_ratio", AdviceOp::Lt, -10.0)], "考虑止损或补仓", AdviceSeverity::Warning, false),
+                rule("默认", Vec::new(), "继续持有观察", AdviceSeverity::Info, true),
+            ],
+        }
+    }
+}
+
+/// when 中的条件全部满足时给出建议，when 为空时总是满足
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdviceRule {
+    pub name: String,
+    #[serde(default)]
+    pub when: Vec<AdviceCondition>,
+    pub advice: String,
+    #[serde(default)]
+    pub severity: AdviceSeverity,
+    /// 仅在其他规则都未命中时使用
+    #[serde(default)]
+    pub fallback: bool,
+    /// 命中后不再匹配后续规则
+    #[serde(default)]
+    pub stop: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdviceCondition {
+    /// StockAnalysis 或 StockInfo 中的数值字段，如 profit_ratio、change_percent
+    pub field: String,
+    pub op: AdviceOp,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AdviceOp {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdviceSeverity {
+    #[default]
+    Info,       // 提示
+    Warning,    // 注意
+    Critical,   // 紧急
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Advice {
+    pub rule: String,
+    pub text: String,
+    pub severity: AdviceSeverity,
+}
+
+/// 未来 N 个交易日内先到达目标价或止损价的概率（%）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExitProbability {
+    pub days: usize,
+    pub simulations: usize,
+    /// 模拟使用的日波动率 (%)
+    pub daily_volatility: f64,
+    pub target_probability: f64,
+    pub stop_probability: f64,
+    /// N 天内两者都未触及的概率
+    pub neither_probability: f64,
+    /// 相对持仓成本的期望盈亏，未触及的路径按第 N 天价格计算
+    pub expected_profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolatilityStop {
+    pub atr: f64,
+    /// ATR 占当前价格的比例 (%)
+    pub atr_percent: f64,
+    /// 日收益率标准差 (%)
+    pub daily_volatility: f64,
+    pub suggested_atr_multiple: f64,
+    pub suggested_stop_price: f64,
+    /// 当前价格到止损价的距离相当于几倍 ATR
+    pub stop_distance_atr: f64,
+    /// 止损距离小于配置的最小 ATR 倍数
+    pub too_tight: bool,
+    /// 按随机游走估算正常波动触及止损所需的交易日数
+    pub expected_days_to_stop: Option<f64>,
+}
+
+/// 移动止损设置，最高价随监控更新并持久化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrailingStop {
+    pub kind: TrailingKind,
+    pub distance: f64,
+    /// 盈利达到该百分比后才启用移动止损
+    pub activation_profit: Option<f64>,
+    /// 持仓以来最有利的价格，多头为最高价，空头为最低价
+    pub high_water_mark: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TrailingKind {
+    #[default]
+    Percent,    // 距最高价的百分比
+    Amount,     // 距最高价的价差
+}
+
+impl std::str::FromStr for TrailingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "percent" => Ok(TrailingKind::Percent),
+            "amount" => Ok(TrailingKind::Amount),
+            _ => Err(format!("无效的移动止损方式: {}（可选 percent/amount）", s)),
+        }
+    }
+}
+
+/// 分批止盈/止损中的一档
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExitTier {
+    /// 该档卖出的持仓比例 (0, 1]
+    pub fraction: f64,
+    pub value: f64,
+    pub kind: TargetKind,
+    /// 监控中触发后标记，避免重复提醒
+    #[serde(default)]
+    pub triggered: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TierSide {
+    TakeProfit,     // 止盈
+    StopLoss,       // 止损
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierAnalysis {
+    pub side: TierSide,
+    /// 档位序号，从 1 开始
+    pub tier: usize,
+    pub price: f64,
+    pub quantity: f64,
+    pub expected_profit: f64,
+    pub triggered: bool,
+}
+
+/// 分红、送转股、拆股等公司行为，除权除息日后调整持仓
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub ex_date: NaiveDate,
+    pub kind: CorporateActionKind,
+    #[serde(default)]
+    pub applied: bool,
+    /// 除息时实际收到的现金
+    #[serde(default)]
+    pub cash_received: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CorporateActionKind {
+    CashDividend { per_share: f64 },    // 每股派息
+    BonusShares { per_share: f64 },     // 每股送转股数
+    Split { ratio: f64 },               // 1股拆为 ratio 股（小于1为合股）
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedTrade {
+    pub quantity: f64,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionSizing {
+    pub equity: f64,
+    pub risk_percent: f64,
+    pub entry_price: f64,
+    pub stop_loss_price: f64,
+    pub risk_per_share: f64,
+    pub risk_budget: f64,
+    pub quantity: f64,
+    pub investment_amount: f64,
+    pub max_loss: f64,
+    pub reward_ratio: f64,
+    pub target_price: f64,
+    pub target_profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioPosition {
+    pub name: String,
+    pub analysis: StockAnalysis,
+    pub market_value: f64,
+    pub day_change: f64,
+    /// 占组合总权益的比例 (%)
+    pub weight: f64,
+    /// 当前价格跌到止损价的潜在亏损
+    pub risk_to_stop: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioSummary {
+    pub positions: Vec<PortfolioPosition>,
+    pub cash: f64,
+    /// 总权益 = 持仓市值 + 现金
+    pub equity: f64,
+    pub invested_percent: f64,
+    pub net_deposits: f64,
+    /// 相对净入金的总收益，净入金为0时无意义
+    pub total_return: f64,
+    pub total_return_percent: f64,
+    pub market_value: f64,
+    pub cost: f64,
+    pub unrealized_profit: f64,
+    pub unrealized_profit_ratio: f64,
+    pub realized_profit: f64,
+    pub day_change: f64,
+    pub day_change_percent: f64,
+    pub risk_to_stop: f64,
+    pub risk_percent: f64,
+}
+
+/// 用户维护的行业映射，键为股票代码（可带或不带 sh/sz/bj 前缀）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SectorMap {
+    #[serde(default)]
+    pub sectors: std::collections::HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionExposure {
+    pub code: String,
+    pub name: String,
+    pub sector: String,
+    pub market_value: f64,
+    /// 占组合总权益的比例 (%)
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectorExposure {
+    pub sector: String,
+    pub market_value: f64,
+    pub weight: f64,
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConcentrationKind {
+    Stock,      // 单只股票
+    Sector,     // 单个行业
+}
+
+/// 权重超过配置上限的股票或行业
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcentrationWarning {
+    pub kind: ConcentrationKind,
+    pub name: String,
+    pub weight: f64,
+    pub limit: f64,
+}
+
+/// 持仓集中度和行业分布，持仓和行业均按权重从高到低排列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureReport {
+    pub positions: Vec<PositionExposure>,
+    pub sectors: Vec<SectorExposure>,
+    pub equity: f64,
+    pub cash_weight: f64,
+    pub top_n: usize,
+    /// 前N大持仓的合计权重 (%)
+    pub top_n_weight: f64,
+    pub warnings: Vec<ConcentrationWarning>,
+}
+
+/// 两只持仓的日收益率相关系数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelatedPair {
+    pub first: String,
+    pub second: String,
+    pub correlation: f64,
+    /// 参与计算的共同交易日收益率个数
+    pub observations: usize,
+}
+
+/// 持仓日收益率相关系数矩阵，共同交易日不足时为空
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationMatrix {
+    pub codes: Vec<String>,
+    pub values: Vec<Vec<Option<f64>>>,
+    pub threshold: f64,
+    /// 相关系数不低于阈值的组合，按相关系数从高到低排列
+    pub high_pairs: Vec<CorrelatedPair>,
+}
+
+/// 外部现金流发生后的估值点，value 为包含当日现金流后的市值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValuationPoint {
+    pub date: NaiveDate,
+    pub value: f64,
+    /// 流入为正，流出为负
+    pub flow: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionPerformance {
+    pub code: String,
+    pub closed: bool,
+    pub first_date: NaiveDate,
+    pub market_value: f64,
+    pub total_profit: f64,
+    /// 年化内部收益率 (%)
+    pub xirr: Option<f64>,
+    /// 时间加权收益率 (%)
+    pub twr: Option<f64>,
+    pub annualized_twr: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioPerformance {
+    pub positions: Vec<PositionPerformance>,
+    pub equity: f64,
+    pub net_deposits: f64,
+    pub xirr: Option<f64>,
+    pub twr: Option<f64>,
+    pub annualized_twr: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionSnapshot {
+    pub code: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub market_value: f64,
+}
+
+/// 组合每日收盘估值快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailySnapshot {
+    pub date: NaiveDate,
+    pub positions: Vec<PositionSnapshot>,
+    pub cash: f64,
+    pub market_value: f64,
+    pub equity: f64,
+    /// 距上一个快照以来的净入金（入金为正），用于剔除外部现金流对日收益的影响
+    #[serde(default)]
+    pub net_flow: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquityCurvePoint {
+    pub date: NaiveDate,
+    pub equity: f64,
+    /// 剔除入金/出金后的日收益率 (%)
+    pub daily_return: Option<f64>,
+    /// 相对历史最高净值的回撤 (%)
+    pub drawdown: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquityCurve {
+    pub points: Vec<EquityCurvePoint>,
+    /// 区间时间加权收益率 (%)
+    pub total_return: f64,
+    /// 最大回撤 (%)，为负数或0
+    pub max_drawdown: f64,
+    pub max_drawdown_start: Option<NaiveDate>,
+    pub max_drawdown_end: Option<NaiveDate>,
+    pub best_day: Option<(NaiveDate, f64)>,
+    pub worst_day: Option<(NaiveDate, f64)>,
+}
+
+/// 相对比较基准的表现，收益率均为百分比
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkComparison {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub trading_days: usize,
+    pub period_return: f64,
+    pub benchmark_return: f64,
+    pub excess_return: f64,
+    pub beta: f64,
+    /// 年化 Jensen's alpha（无风险利率取0）
+    pub alpha: f64,
+}
+
+/// 回测中的一笔卖出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestFill {
+    pub date: NaiveDate,
+    pub price: f64,
+    pub quantity: f64,
+    pub kind: BacktestExitKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BacktestExitKind {
+    Target,
+    StopLoss,
+    TrailingStop,
+    TakeProfitTier(usize),
+    StopLossTier(usize),
+    /// 数据结束仍未触发，按最后收盘价估值
+    EndOfData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestResult {
+    pub code: String,
+    pub entry_date: NaiveDate,
+    pub entry_price: f64,
+    pub quantity: f64,
+    pub target_price: f64,
+    pub stop_loss_price: f64,
+    pub fills: Vec<BacktestFill>,
+    pub exit_date: NaiveDate,
+    /// 持有的自然日和交易日数
+    pub holding_days: i64,
+    pub trading_days: usize,
+    pub profit: f64,
+    pub profit_ratio: f64,
+    /// 持有期间最高价/最低价相对买入价的幅度 (%)
+    pub max_gain: f64,
+    pub max_drawdown: f64,
+}
+
+/// 网格交易计划
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridPlan {
+    pub code: String,
+    pub lower_price: f64,
+    pub upper_price: f64,
+    pub grid_count: usize,
+    pub spacing: GridSpacing,
+    /// 每格买卖数量
+    pub quantity_per_grid: f64,
+    /// 手续费率 (%)
+    pub fee_rate: f64,
+    /// 单笔最低手续费
+    pub min_fee: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum GridSpacing {
+    #[default]
+    Arithmetic, // 等差，每格价差相同
+    Geometric,  // 等比，每格涨跌幅相同
+}
+
+impl std::str::FromStr for GridSpacing {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "arithmetic" => Ok(GridSpacing::Arithmetic),
+            "geometric" => Ok(GridSpacing::Geometric),
+            _ => Err(format!("无效的网格方式: {}（可选 arithmetic/geometric）", s)),
+        }
+    }
+}
+
+/// 一格：在 buy_price 买入，涨到 sell_price 卖出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridLevel {
+    pub index: usize,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub quantity: f64,
+    /// 扣除买卖手续费后的每格收益
+    pub profit: f64,
+    pub profit_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridAnalysis {
+    /// 从低到高的网格线价格
+    pub lines: Vec<f64>,
+    pub levels: Vec<GridLevel>,
+    /// 从上限跌到下限买满所有格所需资金（含手续费）
+    pub capital_required: f64,
+    pub min_grid_profit: f64,
+    pub max_grid_profit: f64,
+}
+
+/// 价格穿越网格线，向下穿越提示买入，向上穿越提示卖出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridCrossing {
+    pub line_price: f64,
+    pub upward: bool,
+    /// 对应的格（1 起），向下穿越为该线作为买入价的格，向上穿越为该线作为卖出价的格
+    pub level: usize,
+}
+
+/// 定投计划
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcaPlan {
+    pub code: String,
+    /// 每期投入金额
+    pub amount: f64,
+    pub frequency: DcaFrequency,
+    pub start_date: NaiveDate,
+    /// 每手股数，买入数量按此向下取整
+    pub lot_size: f64,
+    /// 首次建仓时使用的止盈/止损百分比（相对均价）
+    pub target_percent: f64,
+    pub stop_percent: f64,
+    /// 最近一次执行对应的计划日期
+    #[serde(default)]
+    pub last_executed: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DcaFrequency {
+    Daily,
+    #[default]
+    Weekly,
+    Biweekly,
+    Monthly,
+}
+
+impl std::str::FromStr for DcaFrequency {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "daily" => Ok(DcaFrequency::Daily),
+            "weekly" => Ok(DcaFrequency::Weekly),
+            "biweekly" => Ok(DcaFrequency::Biweekly),
+            "monthly" => Ok(DcaFrequency::Monthly),
+            _ => Err(format!("无效的定投频率: {}（可选 daily/weekly/biweekly/monthly）", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcaPurchase {
+    /// 计划日期和实际成交的交易日
+    pub scheduled_date: NaiveDate,
+    pub date: NaiveDate,
+    pub price: f64,
+    pub quantity: f64,
+    pub amount: f64,
+}
+
+/// 按历史K线模拟定投的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcaProjection {
+    pub purchases: Vec<DcaPurchase>,
+    pub total_invested: f64,
+    pub total_quantity: f64,
+    pub avg_cost: f64,
+    pub final_date: NaiveDate,
+    pub final_price: f64,
+    pub market_value: f64,
+    pub profit: f64,
+    pub profit_ratio: f64,
+    /// 年化内部收益率 (%)
+    pub xirr: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,34 +979,246 @@ pub enum StockStatus {
     AtStopLoss,     // 达到止损
     NearTarget,     // 接近目标
     NearStopLoss,   // 接近止损
+    AtTakeProfitTier(usize),    // 达到第N档止盈
+    AtStopLossTier(usize),      // 达到第N档止损
+    MaintenanceWarning,         // 维持担保比例低于下限
 }
 
+/// 未指定组合时使用的默认组合名称
+pub const DEFAULT_PORTFOLIO: &str = "default";
+
+/// A股收盘时间
+pub const MARKET_CLOSE_HOUR: u32 = 15;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StockDatabase {
+pub struct Portfolio {
+    pub name: String,
+    #[serde(default)]
+    pub cash: f64,
+    #[serde(default)]
     pub stocks: std::collections::HashMap<String, StockData>,
+    /// 现金流水，按记录顺序保存
+    #[serde(default)]
+    pub ledger: Vec<CashTransaction>,
+    /// 每日估值快照，按日期排序
+    #[serde(default)]
+    pub snapshots: Vec<DailySnapshot>,
+    #[serde(default)]
+    pub dca_plans: Vec<DcaPlan>,
+}
+
+impl Portfolio {
+    pub fn new(name: &str, cash: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            cash,
+            stocks: std::collections::HashMap::new(),
+            ledger: Vec::new(),
+            snapshots: Vec::new(),
+            dca_plans: Vec::new(),
+        }
+    }
+
+    /// 记录一笔现金流水并更新现金余额
+    pub fn record_transaction(&mut self, transaction: CashTransaction) {
+        self.cash += transaction.amount;
+        self.ledger.push(transaction);
+    }
+
+    /// 保存快照，同一天重复记录时覆盖旧快照
+    pub fn record_snapshot(&mut self, snapshot: DailySnapshot) {
+        self.snapshots.retain(|s| s.date != snapshot.date);
+        self.snapshots.push(snapshot);
+        self.snapshots.sort_by_key(|s| s.date);
+    }
+
+    /// 净入金 = 入金 - 出金
+    pub fn net_deposits(&self) -> f64 {
+        self.ledger
+            .iter()
+            .filter(|t| matches!(t.kind, TransactionKind::Deposit | TransactionKind::Withdrawal))
+            .fold(0.0, |acc, t| acc + t.amount)
+    }
+
+    /// 账户净权益 = 现金 + 各持仓净值（见 [`StockData::net_value`]），
+    /// 组合汇总、收益表现、快照和按成本估算的权益都按此计算
+    pub fn net_equity<'a>(cash: f64, holdings: impl IntoIterator<Item = (&'a StockData, f64)>, today: NaiveDate) -> f64 {
+        holdings
+            .into_iter()
+            .fold(cash, |acc, (stock, price)| acc + stock.net_value(price, today))
+    }
+
+    /// 按给定价格计算的净权益，没有价格的持仓按成本价计价
+    pub fn equity_at(&self, prices: &std::collections::HashMap<String, f64>, today: NaiveDate) -> f64 {
+        let holdings = self
+            .stocks
+            .values()
+            .map(|stock| (stock, prices.get(&stock.code).copied().unwrap_or(stock.avg_price)));
+        Self::net_equity(self.cash, holdings, today)
+    }
+
+    /// 按持仓成本估算的账户权益，不需要实时行情
+    pub fn book_equity(&self) -> f64 {
+        self.equity_at(&std::collections::HashMap::new(), Local::now().date_naive())
+    }
+
+    /// 已清仓持仓累计的已实现收益
+    pub fn closed_realized_profit(&self) -> f64 {
+        self.stocks
+            .values()
+            .filter(|stock| stock.is_closed())
+            .map(|stock| stock.realized_profit)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashTransaction {
+    pub date: NaiveDate,
+    pub kind: TransactionKind,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub quantity: f64,
+    #[serde(default)]
+    pub price: f64,
+    #[serde(default)]
+    pub fee: f64,
+    /// 对现金余额的影响，流入为正
+    pub amount: f64,
+}
+
+impl CashTransaction {
+    pub fn new(date: NaiveDate, kind: TransactionKind, amount: f64) -> Self {
+        Self {
+            date,
+            kind,
+            code: None,
+            quantity: 0.0,
+            price: 0.0,
+            fee: 0.0,
+            amount,
+        }
+    }
+
+    pub fn trade(date: NaiveDate, kind: TransactionKind, code: &str, quantity: f64, price: f64, fee: f64, amount: f64) -> Self {
+        Self {
+            code: Some(code.to_string()),
+            quantity,
+            price,
+            fee,
+            ..Self::new(date, kind, amount)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    Deposit,        // 入金
+    Withdrawal,     // 出金
+    Buy,            // 买入
+    Sell,           // 卖出
+    Dividend,       // 分红
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockDatabase {
+    #[serde(default)]
+    pub portfolios: std::collections::HashMap<String, Portfolio>,
+    /// 旧版本的单一持仓表，加载时迁移到默认组合
+    #[serde(default, skip_serializing)]
+    stocks: std::collections::HashMap<String, StockData>,
 }
 
 impl StockDatabase {
     pub fn new() -> Self {
         Self {
+            portfolios: std::collections::HashMap::new(),
             stocks: std::collections::HashMap::new(),
         }
     }
 
-    pub fn add_stock(&mut self, stock: StockData) {
-        self.stocks.insert(stock.code.clone(), stock);
+    /// 将旧版本的持仓迁移到默认组合
+    pub fn migrate_legacy(&mut self) {
+        if self.stocks.is_empty() {
+            return;
+        }
+
+        let legacy = std::mem::take(&mut self.stocks);
+        self.default_portfolio_mut().stocks.extend(legacy);
+    }
+
+    fn default_portfolio_mut(&mut self) -> &mut Portfolio {
+        self.portfolios
+            .entry(DEFAULT_PORTFOLIO.to_string())
+            .or_insert_with(|| Portfolio::new(DEFAULT_PORTFOLIO, 0.0))
+    }
+
+    /// 获取组合，只有默认组合在不存在时自动创建，其他组合需要先创建，避免组合名称拼写错误时写入新组合
+    pub fn portfolio_mut(&mut self, name: &str) -> Result<&mut Portfolio> {
+        if name == DEFAULT_PORTFOLIO {
+            return Ok(self.default_portfolio_mut());
+        }
+
+        self.portfolios.get_mut(name).ok_or_else(|| {
+            StockCalcError::ValidationError(format!("{}: {}", ValidationError::PortfolioNotFound, name))
+        })
+    }
+
+    /// 只读获取组合，默认组合尚未创建时返回 None，其他组合不存在时报错，避免拼写错误时显示为空组合
+    pub fn portfolio(&self, name: &str) -> Result<Option<&Portfolio>> {
+        match self.portfolios.get(name) {
+            Some(portfolio) => Ok(Some(portfolio)),
+            None if name == DEFAULT_PORTFOLIO => Ok(None),
+            None => Err(StockCalcError::ValidationError(format!(
+                "{}: {}",
+                ValidationError::PortfolioNotFound,
+                name
+            ))),
+        }
+    }
+
+    pub fn get_portfolio(&self, name: &str) -> Option<&Portfolio> {
+        self.portfolios.get(name)
+    }
+
+    pub fn remove_portfolio(&mut self, name: &str) -> Option<Portfolio> {
+        self.portfolios.remove(name)
     }
 
-    pub fn remove_stock(&mut self, code: &str) -> Option<StockData> {
-        self.stocks.remove(code)
+    pub fn record_transaction(&mut self, portfolio: &str, transaction: CashTransaction) -> Result<()> {
+        self.portfolio_mut(portfolio)?.record_transaction(transaction);
+        Ok(())
     }
 
-    pub fn get_stock(&self, code: &str) -> Option<&StockData> {
-        self.stocks.get(code)
+    pub fn list_portfolios(&self) -> Vec<&Portfolio> {
+        let mut portfolios: Vec<&Portfolio> = self.portfolios.values().collect();
+        portfolios.sort_by(|a, b| a.name.cmp(&b.name));
+        portfolios
     }
 
-    pub fn list_stocks(&self) -> Vec<&StockData> {
-        self.stocks.values().collect()
+    pub fn add_stock(&mut self, portfolio: &str, stock: StockData) -> Result<()> {
+        self.portfolio_mut(portfolio)?.stocks.insert(stock.code.clone(), stock);
+        Ok(())
+    }
+
+    pub fn remove_stock(&mut self, portfolio: &str, code: &str) -> Result<Option<StockData>> {
+        Ok(self.portfolio_mut(portfolio)?.stocks.remove(code))
+    }
+
+    pub fn get_stock(&self, portfolio: &str, code: &str) -> Result<Option<&StockData>> {
+        Ok(self.portfolio(portfolio)?.and_then(|p| p.stocks.get(code)))
+    }
+
+    pub fn list_stocks(&self, portfolio: &str) -> Result<Vec<&StockData>> {
+        Ok(self
+            .portfolio(portfolio)?
+            .map(|p| p.stocks.values().collect())
+            .unwrap_or_default())
+    }
+
+    pub fn total_stocks(&self) -> usize {
+        self.portfolios.values().map(|p| p.stocks.len()).sum()
     }
 }
 
@@ -97,4 +1226,55 @@ impl Default for StockDatabase {
     fn default() -> Self {
         Self::new()
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(fetched_at: &str, count: usize) -> CachedCandles {
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        CachedCandles {
+            fetched_at: NaiveDateTime::parse_from_str(fetched_at, "%Y-%m-%d %H:%M").unwrap(),
+            count,
+            candles: (0..count)
+                .map(|i| Candle {
+                    date: start + chrono::Duration::days(i as i64),
+                    open: 10.0,
+                    close: 10.0 + i as f64,
+                    high: 10.0,
+                    low: 10.0,
+                    volume: 0.0,
+                })
+                .collect(),
+        }
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_cached_candles_covers() {
+        let intraday = cached("2026-10-16 10:30", 60);
+        assert!(intraday.covers(60, at("2026-10-16 14:59")));
+        assert!(intraday.covers(30, at("2026-10-16 11:00")));
+        assert!(!intraday.covers(61, at("2026-10-16 11:00")));
+        // 盘中缓存在收盘后和第二天都需要刷新
+        assert!(!intraday.covers(60, at("2026-10-16 15:00")));
+        assert!(!intraday.covers(60, at("2026-10-17 09:00")));
+
+        let after_close = cached("2026-10-16 15:30", 60);
+        assert!(after_close.covers(60, at("2026-10-16 20:00")));
+    }
+
+    #[test]
+    fn test_cached_candles_latest() {
+        let entry = cached("2026-10-16 15:30", 5);
+        let latest = entry.latest(3);
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[0].close, 12.0);
+        assert_eq!(latest[2].close, 14.0);
+        assert_eq!(entry.latest(10).len(), 5);
+    }
+}
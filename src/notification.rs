@@ -1,5 +1,5 @@
 use crate::error::Result;
-use crate::models::{StockAnalysis, StockStatus};
+use crate::models::{AdviceSeverity, DcaPlan, PositionDirection, GridCrossing, GridLevel, StockAnalysis, StockStatus, TierSide};
 use crate::config::AppConfig;
 use colored::*;
 use notify_rust::Notification;
@@ -28,6 +28,14 @@ impl Notifier {
             StockStatus::NearStopLoss => {
                 self.send_near_stop_loss_alert(analysis).await?;
             }
+            StockStatus::AtTakeProfitTier(_) | StockStatus::AtStopLossTier(_) => {
+                for tier in crate::calculator::StockCalculator::newly_reached_tiers(analysis) {
+                    self.send_tier_alert(analysis, tier.side, tier.tier).await?;
+                }
+            }
+            StockStatus::MaintenanceWarning => {
+                self.send_maintenance_alert(analysis).await?;
+            }
             _ => {}
         }
         Ok(())
@@ -49,10 +57,16 @@ impl Notifier {
 
     async fn send_stop_loss_alert(&self, analysis: &StockAnalysis) -> Result<()> {
         let title = "⚠️ 止损触发!";
-        let body = format!(
-            "股票 {} 已达到止损价格 ¥{:.2}",
-            analysis.code, analysis.current_price
-        );
+        let body = match analysis.trailing_stop_price {
+            Some(trailing_price) if trailing_price >= analysis.stop_loss_price => format!(
+                "股票 {} 已触发移动止损 ¥{:.2}，当前价格 ¥{:.2}",
+                analysis.code, analysis.stop_loss_price, analysis.current_price
+            ),
+            _ => format!(
+                "股票 {} 已达到止损价格 ¥{:.2}",
+                analysis.code, analysis.current_price
+            ),
+        };
 
         self.send_system_notification(title, &body).await?;
         self.play_sound_alert("warning").await?;
@@ -87,6 +101,94 @@ impl Notifier {
         Ok(())
     }
 
+    async fn send_maintenance_alert(&self, analysis: &StockAnalysis) -> Result<()> {
+        let title = "🏦 维持担保比例不足!";
+        let body = analysis
+            .financing
+            .as_ref()
+            .map(|financing| format!(
+                "股票 {} 维持担保比例 {:.1}%，低于 {:.1}%，请追加担保物或减仓",
+                analysis.code, financing.maintenance_ratio, financing.min_maintenance_ratio
+            ))
+            .unwrap_or_default();
+
+        self.send_system_notification(title, &body).await?;
+        self.play_sound_alert("warning").await?;
+        self.print_colored_alert(title, &body, "red");
+
+        Ok(())
+    }
+
+    async fn send_tier_alert(&self, analysis: &StockAnalysis, side: TierSide, tier: usize) -> Result<()> {
+        let (title, side_text, sound, color) = match side {
+            TierSide::TakeProfit => ("💰 分批止盈!", "止盈", "success", "green"),
+            TierSide::StopLoss => ("🔻 分批止损!", "止损", "warning", "red"),
+        };
+
+        let quantity = analysis
+            .tiers
+            .iter()
+            .find(|t| t.side == side && t.tier == tier)
+            .map(|t| t.quantity)
+            .unwrap_or_default();
+        let body = format!(
+            "股票 {} 达到第{}档{}价格 ¥{:.2}，计划{} {:.0} 股",
+            analysis.code, tier, side_text, analysis.current_price, Self::close_action(analysis), quantity
+        );
+
+        self.send_system_notification(title, &body).await?;
+        self.play_sound_alert(sound).await?;
+        self.print_colored_alert(title, &body, color);
+
+        Ok(())
+    }
+
+    /// 价格穿越网格线时提醒买入或卖出对应格
+    pub async fn send_grid_alert(
+        &self,
+        code: &str,
+        crossing: &GridCrossing,
+        level: &GridLevel,
+        current_price: f64,
+    ) -> Result<()> {
+        let (title, sound, color) = if crossing.upward {
+            ("📤 网格卖出!", "success", "green")
+        } else {
+            ("📥 网格买入!", "warning", "yellow")
+        };
+        let body = format!(
+            "{} {}穿网格线 ¥{:.3}，当前价格 ¥{:.3}，第{}格{} {:.0} 股",
+            code,
+            if crossing.upward { "上" } else { "下" },
+            crossing.line_price,
+            current_price,
+            level.index,
+            if crossing.upward { "卖出" } else { "买入" },
+            level.quantity
+        );
+
+        self.send_system_notification(title, &body).await?;
+        self.play_sound_alert(sound).await?;
+        self.print_colored_alert(title, &body, color);
+
+        Ok(())
+    }
+
+    /// 定投计划到期提醒
+    pub async fn send_dca_reminder(&self, plan: &DcaPlan, scheduled_date: chrono::NaiveDate) -> Result<()> {
+        let title = "📅 定投提醒";
+        let body = format!(
+            "{} 定投计划 ({}) 已到期，本期投入 ¥{:.2}",
+            plan.code, scheduled_date, plan.amount
+        );
+
+        self.send_system_notification(title, &body).await?;
+        self.play_sound_alert("info").await?;
+        self.print_colored_alert(title, &body, "yellow");
+
+        Ok(())
+    }
+
     async fn send_system_notification(&self, title: &str, body: &str) -> Result<()> {
         if !self.config.general.enable_notifications {
             return Ok(());
@@ -169,14 +271,17 @@ impl Notifier {
         // 投资信息
         println!("💰 {}", "投资信息".green().bold());
         println!("   持有数量: {} 股", analysis.quantity);
+        if analysis.direction == PositionDirection::Short {
+            println!("   持仓方向: {}", "融券卖空".yellow());
+        }
         println!("   购买均价: {}", crate::calculator::StockCalculator::format_currency(analysis.avg_price));
         println!("   投资总额: {}", crate::calculator::StockCalculator::format_currency(analysis.investment_amount));
 
         // 盈利目标
         println!("\n📈 {}", "盈利目标".blue().bold());
-        println!("   目标售价: {} ({})", 
+        println!("   目标价格: {} ({})", 
             crate::calculator::StockCalculator::format_currency(analysis.target_price),
-            format!("+{:.2}%", ((analysis.target_price - analysis.avg_price) / analysis.avg_price) * 100.0).green()
+            format!("{:+.2}%", ((analysis.target_price - analysis.avg_price) / analysis.avg_price) * 100.0).green()
         );
         println!("   预期收益: {}", crate::calculator::StockCalculator::format_currency(analysis.target_profit));
         println!("   距离目标: {}", 
@@ -191,9 +296,16 @@ impl Notifier {
         println!("\n📉 {}", "止损目标".red().bold());
         println!("   止损价格: {} ({})", 
             crate::calculator::StockCalculator::format_currency(analysis.stop_loss_price),
-            format!("-{:.2}%", ((analysis.avg_price - analysis.stop_loss_price) / analysis.avg_price) * 100.0).red()
+            format!("{:+.2}%", ((analysis.stop_loss_price - analysis.avg_price) / analysis.avg_price) * 100.0).red()
         );
         println!("   最大亏损: {}", crate::calculator::StockCalculator::format_currency(analysis.max_loss));
+        if let (Some(trailing_price), Some(high)) = (analysis.trailing_stop_price, analysis.high_water_mark) {
+            println!("   移动止损: {} ({} {})",
+                crate::calculator::StockCalculator::format_currency(trailing_price).yellow(),
+                Self::water_mark_label(analysis),
+                crate::calculator::StockCalculator::format_currency(high)
+            );
+        }
         println!("   安全空间: {}", 
             if analysis.distance_to_stop_loss > 0.0 {
                 format!("+{:.2}%", analysis.distance_to_stop_loss).green()
@@ -202,6 +314,72 @@ impl Notifier {
             }
         );
 
+        // 波动参考
+        if let Some(volatility) = &analysis.volatility {
+            println!("\n🌊 {}", "波动参考".cyan().bold());
+            for line in Self::format_volatility_lines(volatility) {
+                println!("   {}", line);
+            }
+            if volatility.too_tight {
+                println!("   {}", Self::volatility_warning(volatility).yellow());
+            }
+        }
+
+        // 融资融券
+        if let Some(financing) = &analysis.financing {
+            println!("\n🏦 {}", "融资融券".cyan().bold());
+            for line in Self::format_financing_lines(analysis.direction, financing) {
+                println!("   {}", line);
+            }
+            if matches!(analysis.status, StockStatus::MaintenanceWarning) {
+                println!("   {}", Self::maintenance_warning(financing).red().bold());
+            }
+        }
+
+        // 持有成本
+        if let Some(holding_cost) = &analysis.holding_cost {
+            println!("\n⏳ {}", "持有成本".cyan().bold());
+            for line in Self::format_holding_cost_lines(holding_cost, self.config.holding_cost.risk_free_rate) {
+                println!("   {}", line);
+            }
+            if let Some(opportunity_cost) = holding_cost.opportunity_cost {
+                let excess = analysis.total_profit - opportunity_cost;
+                println!("   超额收益: {}",
+                    if excess > 0.0 {
+                        format!("+{}", crate::calculator::StockCalculator::format_currency(excess)).green()
+                    } else {
+                        crate::calculator::StockCalculator::format_currency(excess).red()
+                    }
+                );
+            }
+        }
+
+        // 概率估算
+        if let Some(probability) = &analysis.probability {
+            println!("\n🎲 {}", "概率估算".cyan().bold());
+            for line in Self::format_probability_lines(probability) {
+                println!("   {}", line);
+            }
+        }
+
+        // 分批计划
+        if !analysis.tiers.is_empty() {
+            println!("\n🪜 {}", "分批计划".magenta().bold());
+            for tier in &analysis.tiers {
+                let line = Self::format_tier_line(analysis, tier);
+                if tier.triggered {
+                    println!("   {}", line.dimmed());
+                } else if tier.side == TierSide::TakeProfit {
+                    println!("   {}", line.green());
+                } else {
+                    println!("   {}", line.red());
+                }
+            }
+            println!("   剩余计划收益: {}",
+                crate::calculator::StockCalculator::format_currency(Self::remaining_tier_profit(analysis))
+            );
+        }
+
         // 当前状态
         println!("\n📊 {}", "当前状态".cyan().bold());
         println!("   当前价格: {} ({})", 
@@ -226,23 +404,33 @@ impl Notifier {
                 format!("{:.2}%", analysis.profit_ratio).red()
             }
         );
+        if analysis.realized_profit != 0.0 || analysis.financing.is_some() {
+            println!("   已实现收益: {}", crate::calculator::StockCalculator::format_currency(analysis.realized_profit));
+            println!("   累计盈亏: {}", 
+                if analysis.total_profit > 0.0 {
+                    format!("+{}", crate::calculator::StockCalculator::format_currency(analysis.total_profit)).green()
+                } else {
+                    crate::calculator::StockCalculator::format_currency(analysis.total_profit).red()
+                }
+            );
+        }
 
         // 风险提示
         println!("\n⚠️  {}", "风险提示".yellow().bold());
         println!("   风险收益比: {:.1}:1 ({})", 
             analysis.risk_reward_ratio,
-            crate::calculator::StockCalculator::get_risk_level(analysis.risk_reward_ratio)
+            crate::calculator::StockCalculator::get_risk_level(analysis.risk_reward_ratio, &self.config.risk_grading)
         );
-        println!("   投资规模: {}", 
-            crate::calculator::StockCalculator::get_investment_scale_text(&analysis.investment_scale)
-        );
-        
-        if analysis.profit_ratio > 20.0 {
-            println!("   💡 建议: 考虑分批卖出");
-        } else if analysis.profit_ratio < -10.0 {
-            println!("   💡 建议: 考虑止损或补仓");
-        } else {
-            println!("   💡 建议: 继续持有观察");
+        println!("   投资规模: {}", analysis.investment_scale);
+
+        for advice in &analysis.advice {
+            let text = format!("建议: {}", advice.text);
+            let text = match advice.severity {
+                AdviceSeverity::Info => text.normal(),
+                AdviceSeverity::Warning => text.yellow(),
+                AdviceSeverity::Critical => text.red().bold(),
+            };
+            println!("   {} {}", Self::advice_icon(advice.severity), text);
         }
     }
 
@@ -252,11 +440,14 @@ impl Notifier {
 
         println!("💰 投资信息");
         println!("   持有数量: {} 股", analysis.quantity);
+        if analysis.direction == PositionDirection::Short {
+            println!("   持仓方向: 融券卖空");
+        }
         println!("   购买均价: ¥{:.3}", analysis.avg_price);
         println!("   投资总额: ¥{:.3}", analysis.investment_amount);
 
         println!("\n📈 盈利目标");
-        println!("   目标售价: ¥{:.3} (+{:.2}%)", 
+        println!("   目标价格: ¥{:.3} ({:+.2}%)", 
             analysis.target_price,
             ((analysis.target_price - analysis.avg_price) / analysis.avg_price) * 100.0
         );
@@ -264,25 +455,206 @@ impl Notifier {
         println!("   距离目标: {:.2}%", analysis.distance_to_target);
 
         println!("\n📉 止损目标");
-        println!("   止损价格: ¥{:.3} (-{:.2}%)", 
+        println!("   止损价格: ¥{:.3} ({:+.2}%)", 
             analysis.stop_loss_price,
-            ((analysis.avg_price - analysis.stop_loss_price) / analysis.avg_price) * 100.0
+            ((analysis.stop_loss_price - analysis.avg_price) / analysis.avg_price) * 100.0
         );
         println!("   最大亏损: ¥{:.3}", analysis.max_loss);
+        if let (Some(trailing_price), Some(high)) = (analysis.trailing_stop_price, analysis.high_water_mark) {
+            println!("   移动止损: ¥{:.3} ({} ¥{:.3})", trailing_price, Self::water_mark_label(analysis), high);
+        }
         println!("   安全空间: {:.2}%", analysis.distance_to_stop_loss);
 
+        if let Some(volatility) = &analysis.volatility {
+            println!("\n🌊 波动参考");
+            for line in Self::format_volatility_lines(volatility) {
+                println!("   {}", line);
+            }
+            if volatility.too_tight {
+                println!("   {}", Self::volatility_warning(volatility));
+            }
+        }
+
+        if let Some(financing) = &analysis.financing {
+            println!("\n🏦 融资融券");
+            for line in Self::format_financing_lines(analysis.direction, financing) {
+                println!("   {}", line);
+            }
+            if matches!(analysis.status, StockStatus::MaintenanceWarning) {
+                println!("   {}", Self::maintenance_warning(financing));
+            }
+        }
+
+        if let Some(holding_cost) = &analysis.holding_cost {
+            println!("\n⏳ 持有成本");
+            for line in Self::format_holding_cost_lines(holding_cost, self.config.holding_cost.risk_free_rate) {
+                println!("   {}", line);
+            }
+            if let Some(opportunity_cost) = holding_cost.opportunity_cost {
+                println!("   超额收益: ¥{:.3}", analysis.total_profit - opportunity_cost);
+            }
+        }
+
+        if let Some(probability) = &analysis.probability {
+            println!("\n🎲 概率估算");
+            for line in Self::format_probability_lines(probability) {
+                println!("   {}", line);
+            }
+        }
+
+        if !analysis.tiers.is_empty() {
+            println!("\n🪜 分批计划");
+            for tier in &analysis.tiers {
+                println!("   {}", Self::format_tier_line(analysis, tier));
+            }
+            println!("   剩余计划收益: ¥{:.3}", Self::remaining_tier_profit(analysis));
+        }
+
         println!("\n📊 当前状态");
         println!("   当前价格: ¥{:.3} ({:.2}%)", analysis.current_price, analysis.profit_ratio);
         println!("   当前盈亏: {:.3}", analysis.current_profit);
         println!("   盈亏比例: {:.2}%", analysis.profit_ratio);
+        if analysis.realized_profit != 0.0 || analysis.financing.is_some() {
+            println!("   已实现收益: ¥{:.3}", analysis.realized_profit);
+            println!("   累计盈亏: ¥{:.3}", analysis.total_profit);
+        }
 
         println!("\n⚠️  风险提示");
         println!("   风险收益比: {:.1}:1 ({})", 
             analysis.risk_reward_ratio,
-            crate::calculator::StockCalculator::get_risk_level(analysis.risk_reward_ratio)
-        );
-        println!("   投资规模: {}", 
-            crate::calculator::StockCalculator::get_investment_scale_text(&analysis.investment_scale)
+            crate::calculator::StockCalculator::get_risk_level(analysis.risk_reward_ratio, &self.config.risk_grading)
         );
+        println!("   投资规模: {}", analysis.investment_scale);
+
+        for advice in &analysis.advice {
+            println!("   {} 建议: {}", Self::advice_icon(advice.severity), advice.text);
+        }
+    }
+
+    fn advice_icon(severity: AdviceSeverity) -> &'static str {
+        match severity {
+            AdviceSeverity::Info => "💡",
+            AdviceSeverity::Warning => "⚠️ ",
+            AdviceSeverity::Critical => "🚨",
+        }
     }
-} 
\ No newline at end of file
+
+    fn format_volatility_lines(volatility: &crate::models::VolatilityStop) -> Vec<String> {
+        let mut lines = vec![
+            format!("ATR: ¥{:.3} ({:.2}%) | 日波动率: {:.2}%",
+                volatility.atr, volatility.atr_percent, volatility.daily_volatility
+            ),
+            format!("建议止损: ¥{:.3} ({:.1}×ATR)",
+                volatility.suggested_stop_price, volatility.suggested_atr_multiple
+            ),
+        ];
+
+        let mut distance = format!("止损距离: {:.2}×ATR", volatility.stop_distance_atr);
+        if let Some(days) = volatility.expected_days_to_stop {
+            distance.push_str(&format!(" | 正常波动约 {:.1} 个交易日可能触及止损", days));
+        }
+        lines.push(distance);
+        lines
+    }
+
+    fn format_probability_lines(probability: &crate::models::ExitProbability) -> Vec<String> {
+        vec![
+            format!("{} 个交易日内 (日波动率 {:.2}%，{} 次模拟)",
+                probability.days, probability.daily_volatility, probability.simulations
+            ),
+            format!("先到目标: {:.1}% | 先到止损: {:.1}% | 均未触及: {:.1}%",
+                probability.target_probability, probability.stop_probability, probability.neither_probability
+            ),
+            format!("期望盈亏: ¥{:.3}", probability.expected_profit),
+        ]
+    }
+
+    fn format_financing_lines(direction: PositionDirection, financing: &crate::models::FinancingAnalysis) -> Vec<String> {
+        let (debt_label, rate_label) = match direction {
+            PositionDirection::Long => ("融资负债", "融资利率"),
+            PositionDirection::Short => ("融券负债", "融券费率"),
+        };
+
+        vec![
+            format!("{}: ¥{:.2} | {}: {:.2}%/年", debt_label, financing.borrowed_amount, rate_label, financing.interest_rate),
+            format!("已计利息: ¥{:.2} ({} 天)", financing.accrued_interest, financing.holding_days),
+            format!("维持担保比例: {:.1}% (最低 {:.1}%)", financing.maintenance_ratio, financing.min_maintenance_ratio),
+        ]
+    }
+
+    /// 盈亏平衡价和机会成本，持有起始日未知时只显示平衡价
+    pub fn format_holding_cost_lines(holding_cost: &crate::models::HoldingCostAnalysis, risk_free_rate: f64) -> Vec<String> {
+        let mut lines = vec![format!("盈亏平衡价: ¥{:.3} (含平仓费 ¥{:.2}{})",
+            holding_cost.break_even_price,
+            holding_cost.exit_fee,
+            if holding_cost.accrued_interest > 0.0 {
+                format!("、利息 ¥{:.2}", holding_cost.accrued_interest)
+            } else {
+                String::new()
+            }
+        )];
+
+        if let (Some(since), Some(days), Some(cost)) = (holding_cost.holding_since, holding_cost.holding_days, holding_cost.opportunity_cost) {
+            lines.push(format!("持有天数: {} 天 (自 {})", days, since));
+            lines.push(format!("机会成本: ¥{:.2} (无风险利率 {:.2}%/年)", cost, risk_free_rate));
+        }
+
+        lines
+    }
+
+    fn maintenance_warning(financing: &crate::models::FinancingAnalysis) -> String {
+        format!("⚠️  维持担保比例低于 {:.1}%，请追加担保物或减仓，否则可能被强制平仓",
+            financing.min_maintenance_ratio
+        )
+    }
+
+    fn water_mark_label(analysis: &StockAnalysis) -> &'static str {
+        match analysis.direction {
+            PositionDirection::Long => "最高价",
+            PositionDirection::Short => "最低价",
+        }
+    }
+
+    /// 平仓动作，多头卖出，空头买回
+    fn close_action(analysis: &StockAnalysis) -> &'static str {
+        match analysis.direction {
+            PositionDirection::Long => "卖出",
+            PositionDirection::Short => "买回",
+        }
+    }
+
+    fn volatility_warning(volatility: &crate::models::VolatilityStop) -> String {
+        format!("⚠️  止损距离仅 {:.2}×ATR，处于日常波动范围内，容易被噪音触发",
+            volatility.stop_distance_atr
+        )
+    }
+
+    fn format_tier_line(analysis: &StockAnalysis, tier: &crate::models::TierAnalysis) -> String {
+        let side_text = match tier.side {
+            TierSide::TakeProfit => "止盈",
+            TierSide::StopLoss => "止损",
+        };
+
+        format!(
+            "{}第{}档: ¥{:.3} ({:+.2}%) {} {:.0} 股, 预期盈亏 ¥{:.3}{}",
+            side_text,
+            tier.tier,
+            tier.price,
+            (tier.price - analysis.avg_price) / analysis.avg_price * 100.0,
+            Self::close_action(analysis),
+            tier.quantity,
+            tier.expected_profit,
+            if tier.triggered { " [已触发]" } else { "" }
+        )
+    }
+
+    /// 未触发的止盈档位合计的预期收益
+    fn remaining_tier_profit(analysis: &StockAnalysis) -> f64 {
+        analysis
+            .tiers
+            .iter()
+            .filter(|tier| tier.side == TierSide::TakeProfit && !tier.triggered)
+            .map(|tier| tier.expected_profit)
+            .sum()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::Result;
-use crate::models::{StockDatabase, StockData};
+use crate::models::{StockDatabase, StockData, Portfolio, CashTransaction, DailySnapshot, DcaPlan, Candle, CandleCache, CachedCandles};
 use crate::config::AppConfig;
 use serde_json;
 use std::fs;
@@ -22,7 +22,8 @@ impl Storage {
         }
 
         let content = fs::read_to_string(&data_path)?;
-        let database: StockDatabase = serde_json::from_str(&content)?;
+        let mut database: StockDatabase = serde_json::from_str(&content)?;
+        database.migrate_legacy();
         Ok(database)
     }
 
@@ -41,15 +42,46 @@ impl Storage {
         Ok(())
     }
 
-    pub fn add_stock(&self, stock: StockData) -> Result<()> {
+    /// 读取日K线缓存，缓存文件与数据文件在同一目录，不存在或无法解析时返回空缓存
+    pub fn load_candle_cache(&self) -> Result<CandleCache> {
+        let cache_path = AppConfig::get_data_path()?.with_file_name("candles.json");
+
+        if !cache_path.exists() {
+            return Ok(CandleCache::default());
+        }
+
+        let content = fs::read_to_string(&cache_path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("K线缓存无法解析，已忽略: {}", e);
+            CandleCache::default()
+        }))
+    }
+
+    pub fn save_candles(&self, code: &str, candles: &[Candle], count: usize, now: chrono::NaiveDateTime) -> Result<()> {
+        let cache_path = AppConfig::get_data_path()?.with_file_name("candles.json");
+        let mut cache = self.load_candle_cache()?;
+        cache.entries.insert(code.to_string(), CachedCandles {
+            fetched_at: now,
+            count,
+            candles: candles.to_vec(),
+        });
+
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&cache_path, serde_json::to_string(&cache)?)?;
+        Ok(())
+    }
+
+    pub fn add_stock(&self, portfolio: &str, stock: StockData) -> Result<()> {
         let mut database = self.load_database()?;
-        database.add_stock(stock);
+        database.add_stock(portfolio, stock)?;
         self.save_database(&database)
     }
 
-    pub fn remove_stock(&self, code: &str) -> Result<Option<StockData>> {
+    pub fn remove_stock(&self, portfolio: &str, code: &str) -> Result<Option<StockData>> {
         let mut database = self.load_database()?;
-        let removed = database.remove_stock(code);
+        let removed = database.remove_stock(portfolio, code)?;
         
         if removed.is_some() {
             self.save_database(&database)?;
@@ -58,22 +90,167 @@ impl Storage {
         Ok(removed)
     }
 
-    pub fn get_stock(&self, code: &str) -> Result<Option<StockData>> {
+    pub fn get_stock(&self, portfolio: &str, code: &str) -> Result<Option<StockData>> {
         let database = self.load_database()?;
-        Ok(database.get_stock(code).cloned())
+        Ok(database.get_stock(portfolio, code)?.cloned())
     }
 
-    pub fn list_stocks(&self) -> Result<Vec<StockData>> {
+    pub fn list_stocks(&self, portfolio: &str) -> Result<Vec<StockData>> {
         let database = self.load_database()?;
-        Ok(database.list_stocks().into_iter().cloned().collect())
+        Ok(database.list_stocks(portfolio)?.into_iter().cloned().collect())
+    }
+
+    pub fn update_stock(&self, portfolio: &str, stock: StockData) -> Result<()> {
+        let mut database = self.load_database()?;
+        database.add_stock(portfolio, stock)?;
+        self.save_database(&database)
+    }
+
+    /// 保存持仓并记录相关现金流水，持仓数量为0时保留记录，已实现收益不会丢失
+    pub fn update_stock_with_transactions(
+        &self,
+        portfolio: &str,
+        stock: StockData,
+        transactions: Vec<CashTransaction>,
+    ) -> Result<()> {
+        let mut database = self.load_database()?;
+
+        database.add_stock(portfolio, stock)?;
+
+        for transaction in transactions {
+            database.record_transaction(portfolio, transaction)?;
+        }
+
+        self.save_database(&database)
+    }
+
+    /// 重新读取持仓后再修改并保存，避免用过期的副本覆盖其他命令的修改。
+    /// 持仓不存在时返回 None，修改返回的流水一并记录
+    pub fn update_stock_with<F>(&self, portfolio: &str, code: &str, update: F) -> Result<Option<StockData>>
+    where
+        F: FnOnce(&mut StockData) -> Vec<CashTransaction>,
+    {
+        let mut database = self.load_database()?;
+        let stock = match database.portfolio_mut(portfolio)?.stocks.get_mut(code) {
+            Some(stock) => stock,
+            None => return Ok(None),
+        };
+
+        let transactions = update(stock);
+        let updated = stock.clone();
+        for transaction in transactions {
+            database.record_transaction(portfolio, transaction)?;
+        }
+
+        self.save_database(&database)?;
+        Ok(Some(updated))
+    }
+
+    pub fn record_transaction(&self, portfolio: &str, transaction: CashTransaction) -> Result<()> {
+        let mut database = self.load_database()?;
+        database.record_transaction(portfolio, transaction)?;
+        self.save_database(&database)
+    }
+
+    pub fn record_snapshot(&self, portfolio: &str, snapshot: DailySnapshot) -> Result<()> {
+        let mut database = self.load_database()?;
+        database.portfolio_mut(portfolio)?.record_snapshot(snapshot);
+        self.save_database(&database)
+    }
+
+    /// 保存定投计划，同一股票只保留一个计划；替换旧计划时保留最近执行日期，已执行的期数不会再次到期。
+    /// 返回保存后的计划
+    pub fn save_dca_plan(&self, portfolio: &str, mut plan: DcaPlan) -> Result<DcaPlan> {
+        let mut database = self.load_database()?;
+        let plans = &mut database.portfolio_mut(portfolio)?.dca_plans;
+        let previous = plans.iter().find(|p| p.code == plan.code).and_then(|p| p.last_executed);
+        plan.last_executed = plan.last_executed.or(previous);
+        plans.retain(|p| p.code != plan.code);
+        plans.push(plan.clone());
+        self.save_database(&database)?;
+        Ok(plan)
+    }
+
+    pub fn remove_dca_plan(&self, portfolio: &str, code: &str) -> Result<bool> {
+        let mut database = self.load_database()?;
+        let plans = &mut database.portfolio_mut(portfolio)?.dca_plans;
+        let before = plans.len();
+        plans.retain(|p| p.code != code);
+
+        if plans.len() == before {
+            return Ok(false);
+        }
+        self.save_database(&database)?;
+        Ok(true)
     }
 
-    pub fn update_stock(&self, stock: StockData) -> Result<()> {
+    /// 记录一次定投买入：更新持仓、现金流水和计划的最近执行日期
+    pub fn record_dca_execution(
+        &self,
+        portfolio: &str,
+        stock: StockData,
+        transaction: CashTransaction,
+        scheduled_date: chrono::NaiveDate,
+    ) -> Result<()> {
         let mut database = self.load_database()?;
-        database.add_stock(stock);
+        if let Some(plan) = database
+            .portfolio_mut(portfolio)?
+            .dca_plans
+            .iter_mut()
+            .find(|p| p.code == stock.code)
+        {
+            plan.last_executed = Some(scheduled_date);
+        }
+
+        database.add_stock(portfolio, stock)?;
+        database.record_transaction(portfolio, transaction)?;
         self.save_database(&database)
     }
 
+    /// 创建组合，初始现金记为一笔入金
+    pub fn create_portfolio(&self, name: &str, initial_deposit: Option<CashTransaction>) -> Result<bool> {
+        let mut database = self.load_database()?;
+        if database.get_portfolio(name).is_some() {
+            return Ok(false);
+        }
+
+        let mut portfolio = Portfolio::new(name, 0.0);
+        if let Some(deposit) = initial_deposit {
+            portfolio.record_transaction(deposit);
+        }
+
+        database.portfolios.insert(name.to_string(), portfolio);
+        self.save_database(&database)?;
+        Ok(true)
+    }
+
+    /// 读取组合，组合名称不存在时报错，默认组合尚未创建时返回 None
+    pub fn portfolio(&self, name: &str) -> Result<Option<Portfolio>> {
+        let database = self.load_database()?;
+        Ok(database.portfolio(name)?.cloned())
+    }
+
+    pub fn get_portfolio(&self, name: &str) -> Result<Option<Portfolio>> {
+        let database = self.load_database()?;
+        Ok(database.get_portfolio(name).cloned())
+    }
+
+    pub fn list_portfolios(&self) -> Result<Vec<Portfolio>> {
+        let database = self.load_database()?;
+        Ok(database.list_portfolios().into_iter().cloned().collect())
+    }
+
+    pub fn remove_portfolio(&self, name: &str) -> Result<Option<Portfolio>> {
+        let mut database = self.load_database()?;
+        let removed = database.remove_portfolio(name);
+
+        if removed.is_some() {
+            self.save_database(&database)?;
+        }
+
+        Ok(removed)
+    }
+
     pub fn backup_database(&self) -> Result<()> {
         let data_path = AppConfig::get_data_path()?;
         let backup_path = data_path.with_extension("json.backup");
@@ -118,7 +295,7 @@ impl Storage {
         };
 
         Ok(DatabaseInfo {
-            total_stocks: database.stocks.len(),
+            total_stocks: database.total_stocks(),
             file_size,
             data_path: data_path.to_string_lossy().to_string(),
         })
@@ -135,8 +312,7 @@ pub struct DatabaseInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::StockData;
-    use chrono::Utc;
+    use crate::models::{StockData, DEFAULT_PORTFOLIO};
 
     #[test]
     fn test_storage_operations() {
@@ -144,28 +320,57 @@ mod tests {
         let storage = Storage::new(config);
         
         // 测试添加股票
-        let stock = StockData {
-            code: "000001".to_string(),
-            quantity: 1000.0,
-            avg_price: 15.5,
-            target_profit: 5000.0,
-            max_loss: 2000.0,
-            last_updated: Utc::now(),
-        };
+        let stock = StockData::new("000001".to_string(), 1000.0, 15.5, 5000.0, 2000.0);
 
-        assert!(storage.add_stock(stock.clone()).is_ok());
+        assert!(storage.add_stock(DEFAULT_PORTFOLIO, stock.clone()).is_ok());
         
         // 测试获取股票
-        let retrieved = storage.get_stock("000001").unwrap();
+        let retrieved = storage.get_stock(DEFAULT_PORTFOLIO, "000001").unwrap();
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().code, "000001");
         
         // 测试删除股票
-        let removed = storage.remove_stock("000001").unwrap();
+        let removed = storage.remove_stock(DEFAULT_PORTFOLIO, "000001").unwrap();
         assert!(removed.is_some());
         
         // 验证已删除
-        let retrieved = storage.get_stock("000001").unwrap();
+        let retrieved = storage.get_stock(DEFAULT_PORTFOLIO, "000001").unwrap();
         assert!(retrieved.is_none());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_migrate_legacy_database() {
+        let legacy = r#"{"stocks":{"000001":{"code":"000001","quantity":1000.0,"avg_price":15.5,"target_profit":5000.0,"max_loss":2000.0,"last_updated":"2024-01-01T12:00:00Z"}}}"#;
+
+        let mut database: StockDatabase = serde_json::from_str(legacy).unwrap();
+        database.migrate_legacy();
+
+        assert_eq!(database.total_stocks(), 1);
+        assert!(database.get_stock(DEFAULT_PORTFOLIO, "000001").unwrap().is_some());
+
+        // 迁移后只写入新格式
+        let json = serde_json::to_value(&database).unwrap();
+        assert!(json.get("stocks").is_none());
+        assert!(json["portfolios"][DEFAULT_PORTFOLIO]["stocks"]["000001"].is_object());
+    }
+
+    #[test]
+    fn test_unknown_portfolio() {
+        let mut database = StockDatabase::new();
+        let stock = StockData::new("000001".to_string(), 1000.0, 15.5, 5000.0, 2000.0);
+
+        // 默认组合自动创建，其他组合名称不存在时报错而不是新建
+        assert!(database.add_stock(DEFAULT_PORTFOLIO, stock.clone()).is_ok());
+        assert!(database.add_stock("growht", stock.clone()).is_err());
+        assert!(database.get_portfolio("growht").is_none());
+        // 读取同样不会把拼写错误的组合当作空组合
+        assert!(database.get_stock("growht", "000001").is_err());
+        assert!(database.list_stocks("growht").is_err());
+        assert!(database.remove_stock("growht", "000001").is_err());
+        assert_eq!(database.list_stocks(DEFAULT_PORTFOLIO).unwrap().len(), 1);
+
+        database.portfolios.insert("growth".to_string(), crate::models::Portfolio::new("growth", 0.0));
+        assert!(database.add_stock("growth", stock).is_ok());
+        assert_eq!(database.total_stocks(), 2);
+    }
+}